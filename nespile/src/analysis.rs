@@ -0,0 +1,2 @@
+pub mod callgraph;
+pub mod cfg;
@@ -0,0 +1,121 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::analysis::cfg::{BlockId, ControlFlowGraph, EdgeKind};
+use crate::parser::disassembler::Vector;
+use crate::symbols::SymbolTable;
+
+
+
+/// Which functions call which, with functions identified by their entry block.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    /// Functions each function calls with `JSR`, or jumps into as a tail call.
+    pub callees: BTreeMap<BlockId, BTreeSet<BlockId>>,
+    pub callers: BTreeMap<BlockId, BTreeSet<BlockId>>,
+    /// Entry function of each interrupt vector.
+    pub vectors: Vec<(Vector, BlockId)>,
+}
+impl CallGraph {
+    pub fn build(cfg: &ControlFlowGraph) -> Self {
+        let mut graph = CallGraph {
+            vectors: cfg.vectors.clone(),
+            ..CallGraph::default()
+        };
+        for &entry in cfg.functions.keys() {
+            graph.callees.entry(entry).or_default();
+            graph.callers.entry(entry).or_default();
+        }
+
+        for function in cfg.functions.values() {
+            let calls = function.blocks.iter()
+                .flat_map(|block| cfg.successors(*block))
+                .filter(|edge| match edge.kind {
+                    EdgeKind::Call => true,
+                    EdgeKind::Jump => edge.to != function.entry && cfg.functions.contains_key(&edge.to),
+                    _ => false,
+                });
+            for edge in calls {
+                graph.callees.entry(function.entry).or_default().insert(edge.to);
+                graph.callers.entry(edge.to).or_default().insert(function.entry);
+            }
+        }
+        graph
+    }
+
+    /// Every function that can run from a call to `entry`, including itself.
+    pub fn reachable_from(&self, entry: BlockId) -> BTreeSet<BlockId> {
+        let mut reached = BTreeSet::from([entry]);
+        let mut pending = vec![entry];
+        while let Some(function) = pending.pop() {
+            for &callee in self.callees.get(&function).into_iter().flatten() {
+                if reached.insert(callee) {
+                    pending.push(callee);
+                }
+            }
+        }
+        reached
+    }
+    /// Every function that can run from an interrupt vector.
+    pub fn reached_from_vector(&self, vector: Vector) -> BTreeSet<BlockId> {
+        self.vectors.iter()
+            .filter(|(other, _)| *other == vector)
+            .flat_map(|(_, entry)| self.reachable_from(*entry))
+            .collect()
+    }
+    /// The vectors whose handlers can end up calling `function`.
+    pub fn vectors_reaching(&self, function: BlockId) -> Vec<Vector> {
+        let mut vectors: Vec<Vector> = self.vectors.iter()
+            .filter(|(_, entry)| self.reachable_from(*entry).contains(&function))
+            .map(|(vector, _)| *vector)
+            .collect();
+        vectors.dedup();
+        vectors
+    }
+
+    /// Whether `function` can call itself, directly or through other functions.
+    pub fn is_recursive(&self, function: BlockId) -> bool {
+        self.callees.get(&function).into_iter().flatten()
+            .any(|callee| self.reachable_from(*callee).contains(&function))
+    }
+    /// Functions no interrupt vector can reach, such as ones only jumped to indirectly.
+    pub fn unreachable(&self) -> Vec<BlockId> {
+        let reached: BTreeSet<BlockId> = self.vectors.iter()
+            .flat_map(|(_, entry)| self.reachable_from(*entry))
+            .collect();
+        self.callees.keys().filter(|function| !reached.contains(function)).copied().collect()
+    }
+
+    /// Lists each function's callers, callees, recursion and the vectors it runs from.
+    pub fn report(&self, cfg: &ControlFlowGraph, symbols: &SymbolTable) -> String {
+        let name = |function: &BlockId| {
+            symbols.rom_symbol(*function)
+                .map(|symbol| symbol.name.clone())
+                .unwrap_or_else(|| format!("${:04x}", cfg.blocks[function].address()))
+        };
+        let names = |functions: &BTreeSet<BlockId>| match functions.is_empty() {
+            true => "-".to_string(),
+            false => functions.iter().map(name).collect::<Vec<_>>().join(", "),
+        };
+
+        let mut lines = Vec::new();
+        for (function, callees) in &self.callees {
+            let block = &cfg.blocks[function];
+            lines.push(format!("{} (${:04x}, bank {})", name(function), block.address(), block.bank()));
+
+            let vectors = self.vectors_reaching(*function);
+            if !vectors.is_empty() {
+                let vectors: Vec<String> = vectors.iter().map(|vector| format!("{:?}", vector)).collect();
+                lines.push(format!("    runs from: {}", vectors.join(", ")));
+            }
+            lines.push(format!("    calls: {}", names(callees)));
+            lines.push(format!("    called by: {}", names(&self.callers[function])));
+            if self.is_recursive(*function) {
+                lines.push("    recursive".to_string());
+            }
+        }
+
+        let unreachable: BTreeSet<BlockId> = self.unreachable().into_iter().collect();
+        lines.push(format!("\nunreachable: {}", names(&unreachable)));
+        lines.join("\n") + "\n"
+    }
+}
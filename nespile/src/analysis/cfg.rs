@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::cpu::ControlFlow;
+use crate::parser::NesProgram;
+use crate::parser::disassembler::Vector;
+use crate::parser::instruction::Instruction;
+
+
+
+/// A basic block, identified by the PRG-ROM offset of its first instruction.
+pub type BlockId = usize;
+
+/// A straight-line run of instructions, entered only at the top and left only at the bottom.
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub id: BlockId,
+    pub instructions: Vec<Instruction>,
+}
+impl BasicBlock {
+    /// CPU address of the first instruction.
+    pub fn address(&self) -> u16 {
+        self.instructions[0].address
+    }
+    pub fn bank(&self) -> usize {
+        self.instructions[0].bank
+    }
+    pub fn last(&self) -> &Instruction {
+        self.instructions.last().expect("Blocks are never empty")
+    }
+    /// How control leaves the block, from its last instruction.
+    pub fn exit(&self) -> ControlFlow {
+        self.last().opcode.semantics().control_flow
+    }
+    /// PRG-ROM offset just past the block's last instruction.
+    pub fn end(&self) -> usize {
+        self.last().prg_offset + self.last().size()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EdgeKind {
+    /// Into the following block, including past a branch not taken and back from a `JSR`.
+    Fallthrough,
+    /// A relative branch taken.
+    Branch,
+    /// A direct `JMP`.
+    Jump,
+    /// A `JMP (ptr)` or pushed-address `RTS`, to each target of its recovered jump table.
+    Indirect,
+    /// A `JSR`, to the entry of the called function.
+    Call,
+    /// An `RTS`, back to the block following each call of its function.
+    Return,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Edge {
+    pub from: BlockId,
+    pub to: BlockId,
+    pub kind: EdgeKind,
+}
+
+/// The blocks reached from a `JSR` target or interrupt vector without calling or returning.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub entry: BlockId,
+    /// Every block in the function, including its entry. Blocks shared by several
+    /// functions, such as a common tail, belong to each of them.
+    pub blocks: BTreeSet<BlockId>,
+}
+
+/// Control-flow graph over every instruction the disassembler reached.
+#[derive(Debug, Clone)]
+pub struct ControlFlowGraph {
+    pub blocks: BTreeMap<BlockId, BasicBlock>,
+    /// Every edge, sorted by source block.
+    pub edges: Vec<Edge>,
+    /// Functions keyed by entry block.
+    pub functions: BTreeMap<BlockId, Function>,
+    /// Entry block of each interrupt vector.
+    pub vectors: Vec<(Vector, BlockId)>,
+}
+impl ControlFlowGraph {
+    pub fn build(program: &NesProgram) -> Self {
+        let instructions = &program.instructions;
+        let power_on = program.mapper.power_on_context();
+        let vectors: Vec<(Vector, BlockId)> = program.vectors.iter()
+            .filter_map(|(vector, addr)| Some((*vector, program.mapper.resolve(*addr, &power_on)?.prg_offset)))
+            .filter(|(_, offset)| instructions.contains_key(offset))
+            .collect();
+
+        // Blocks start at entry points, at jump targets and after any instruction that leaves the straight line.
+        let mut leaders: BTreeSet<usize> = vectors.iter().map(|(_, offset)| *offset).collect();
+        leaders.extend(program.jump_tables.iter()
+            .flat_map(|table| &table.targets)
+            .map(|(_, location)| location.prg_offset)
+            .filter(|offset| instructions.contains_key(offset)));
+        for instruction in instructions.values() {
+            if let Some(target) = instruction.target.filter(|target| instructions.contains_key(&target.prg_offset)) {
+                leaders.insert(target.prg_offset);
+            }
+            if instruction.opcode.semantics().control_flow != ControlFlow::Next {
+                leaders.extend(next_instruction(program, instruction).map(|next| next.prg_offset));
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+        let mut current: Option<BasicBlock> = None;
+        for instruction in instructions.values() {
+            let continues = current.as_ref().is_some_and(|block| {
+                block.exit() == ControlFlow::Next &&
+                    !leaders.contains(&instruction.prg_offset) &&
+                    next_instruction(program, block.last()).is_some_and(|next| next.prg_offset == instruction.prg_offset)
+            });
+            match &mut current {
+                Some(block) if continues => block.instructions.push(instruction.clone()),
+                _ => {
+                    if let Some(block) = current.take() {
+                        blocks.insert(block.id, block);
+                    }
+                    current = Some(BasicBlock { id: instruction.prg_offset, instructions: vec![instruction.clone()] });
+                },
+            }
+        }
+        if let Some(block) = current {
+            blocks.insert(block.id, block);
+        }
+
+        let mut edges = BTreeSet::new();
+        for block in blocks.values() {
+            let last = block.last();
+            let target = last.target.map(|target| target.prg_offset).filter(|offset| blocks.contains_key(offset));
+            let exit = block.exit();
+            let kind = match exit {
+                ControlFlow::Branch => Some(EdgeKind::Branch),
+                ControlFlow::Jump => Some(EdgeKind::Jump),
+                ControlFlow::Call => Some(EdgeKind::Call),
+                _ => None,
+            };
+            if let (Some(kind), Some(to)) = (kind, target) {
+                edges.insert(Edge { from: block.id, to, kind });
+            }
+            if exit.falls_through() {
+                if let Some(next) = next_instruction(program, last) {
+                    edges.insert(Edge { from: block.id, to: next.prg_offset, kind: EdgeKind::Fallthrough });
+                }
+            }
+        }
+
+        for table in &program.jump_tables {
+            let Some(block) = blocks.values().find(|block| block.last().prg_offset == table.prg_offset) else {
+                continue;
+            };
+            for (_, location) in &table.targets {
+                if blocks.contains_key(&location.prg_offset) {
+                    edges.insert(Edge { from: block.id, to: location.prg_offset, kind: EdgeKind::Indirect });
+                }
+            }
+        }
+
+        let mut cfg = ControlFlowGraph {
+            blocks,
+            edges: edges.into_iter().collect(),
+            functions: BTreeMap::new(),
+            vectors,
+        };
+        cfg.find_functions();
+        cfg
+    }
+
+    /// Groups blocks into functions from each call target and vector, then links their returns to their callers.
+    fn find_functions(&mut self) {
+        let entries: BTreeSet<BlockId> = self.vectors.iter().map(|(_, entry)| *entry)
+            .chain(self.edges.iter().filter(|edge| edge.kind == EdgeKind::Call).map(|edge| edge.to))
+            .collect();
+
+        for &entry in &entries {
+            let mut blocks = BTreeSet::from([entry]);
+            let mut pending = vec![entry];
+            while let Some(block) = pending.pop() {
+                let successors = self.successors(block)
+                    .filter(|edge| matches!(edge.kind, EdgeKind::Fallthrough | EdgeKind::Branch | EdgeKind::Jump | EdgeKind::Indirect))
+                    // A jump into another function is a tail call, not part of this one.
+                    .filter(|edge| edge.kind != EdgeKind::Jump || edge.to == entry || !entries.contains(&edge.to));
+                for edge in successors {
+                    if blocks.insert(edge.to) {
+                        pending.push(edge.to);
+                    }
+                }
+            }
+            self.functions.insert(entry, Function { entry, blocks });
+        }
+
+        let mut returns = BTreeSet::new();
+        for call in self.edges.iter().filter(|edge| edge.kind == EdgeKind::Call) {
+            let Some(return_site) = self.successors(call.from).find(|edge| edge.kind == EdgeKind::Fallthrough) else {
+                continue;
+            };
+            for &block in &self.functions[&call.to].blocks {
+                // An `RTS` that dispatches through a jump table doesn't return.
+                let dispatches = self.successors(block).any(|edge| edge.kind == EdgeKind::Indirect);
+                if self.blocks[&block].exit() == ControlFlow::Return && !dispatches {
+                    returns.insert(Edge { from: block, to: return_site.to, kind: EdgeKind::Return });
+                }
+            }
+        }
+        self.edges.extend(returns);
+        self.edges.sort();
+    }
+
+    pub fn successors(&self, block: BlockId) -> impl Iterator<Item = &Edge> {
+        let start = self.edges.partition_point(|edge| edge.from < block);
+        self.edges[start..].iter().take_while(move |edge| edge.from == block)
+    }
+    pub fn predecessors(&self, block: BlockId) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.to == block)
+    }
+    /// The block containing the instruction at a PRG-ROM offset.
+    pub fn block_containing(&self, prg_offset: usize) -> Option<&BasicBlock> {
+        self.blocks.range(..=prg_offset)
+            .next_back()
+            .map(|(_, block)| block)
+            .filter(|block| prg_offset < block.end())
+    }
+    /// Functions whose blocks include `block`.
+    pub fn functions_containing(&self, block: BlockId) -> impl Iterator<Item = &Function> {
+        self.functions.values().filter(move |function| function.blocks.contains(&block))
+    }
+}
+
+/// The instruction that runs after `instruction` in a straight line, past any inline data, if it was decoded.
+fn next_instruction<'p>(program: &'p NesProgram, instruction: &Instruction) -> Option<&'p Instruction> {
+    let skipped = program.inline_data_after(instruction.prg_offset).map_or(0, |data| data.len);
+    program.instructions.get(&(instruction.prg_offset + instruction.size() + skipped))
+        .filter(|next| next.bank == instruction.bank && next.address == instruction.next_address().wrapping_add(skipped as u16))
+}
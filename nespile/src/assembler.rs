@@ -0,0 +1,313 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use binrw::BinWrite;
+use thiserror::Error;
+
+use crate::parser::address_mode::AddressMode;
+use crate::parser::opcodes::Opcode;
+use syntax::{Datum, Operand, Statement};
+
+pub mod expression;
+pub mod syntax;
+
+
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum AssembleError {
+    #[error("Syntax error at line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("Undefined symbol at line {line}: {name}")]
+    UndefinedSymbol { line: usize, name: String },
+    #[error("Symbol defined twice at line {line}: {name}")]
+    DuplicateSymbol { line: usize, name: String },
+    #[error("Invalid operand at line {line}: {mnemonic} has no such address mode")]
+    InvalidOperand { line: usize, mnemonic: String },
+    #[error("Value out of range at line {line}: {value}")]
+    OutOfRange { line: usize, value: i64 },
+    #[error("Expected a single instruction")]
+    NotAnInstruction,
+}
+
+/// A run of bytes assembled from one `.org` onwards.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Section {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+    /// Each instruction, with its address.
+    pub instructions: Vec<(u16, Opcode)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Assembly {
+    pub sections: Vec<Section>,
+    /// Every label and equate.
+    pub symbols: BTreeMap<String, i64>,
+}
+impl Assembly {
+    /// The bytes of every section, back to back.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.sections.iter().flat_map(|section| section.bytes.iter().copied()).collect()
+    }
+    /// Every instruction, with its address.
+    pub fn instructions(&self) -> impl Iterator<Item = &(u16, Opcode)> {
+        self.sections.iter().flat_map(|section| section.instructions.iter())
+    }
+}
+
+
+
+/// Whether an operand is encoded with one byte or two, decided on the first pass so that
+/// addresses don't move on the second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Width {
+    Narrow,
+    Wide,
+}
+
+struct Line {
+    number: usize,
+    statements: Vec<Statement>,
+}
+
+struct Assembler {
+    symbols: HashMap<String, i64>,
+    /// Operand widths of each instruction, in source order.
+    widths: Vec<Width>,
+}
+impl Assembler {
+    /// The opcode for an instruction at `pc`, with its operand's value if known yet.
+    fn opcode(line: usize, mnemonic: &str, operand: &Operand, width: Width, value: Option<i64>, pc: u16) -> Result<Opcode, AssembleError> {
+        let invalid = || AssembleError::InvalidOperand { line, mnemonic: mnemonic.to_string() };
+        let byte = |value: Option<i64>| match value {
+            None => Ok(0),
+            Some(value @ -0x80..=0xff) => Ok(value as u8),
+            Some(value) => Err(AssembleError::OutOfRange { line, value }),
+        };
+        let word = |value: Option<i64>| match value {
+            None => Ok(0),
+            Some(value @ 0..=0xffff) => Ok(value as u16),
+            Some(value) => Err(AssembleError::OutOfRange { line, value }),
+        };
+
+        let candidates = match (operand, width) {
+            (Operand::None, _) => vec![None, Some(AddressMode::Implied), Some(AddressMode::Accumulator)],
+            (Operand::Accumulator, _) => vec![Some(AddressMode::Accumulator)],
+            (Operand::Immediate(_), _) => vec![Some(AddressMode::Immediate(byte(value)?))],
+            (Operand::Direct(_), _) if Opcode::from_argument(mnemonic, Some(AddressMode::Relative(0))).is_some() => {
+                let offset = match value {
+                    Some(target) => {
+                        let offset = target - (pc as i64 + 2);
+                        i8::try_from(offset).map_err(|_| AssembleError::OutOfRange { line, value: offset })? as u8
+                    },
+                    None => 0,
+                };
+                vec![Some(AddressMode::Relative(offset))]
+            },
+            (Operand::Direct(_), Width::Narrow) =>
+                vec![Some(AddressMode::ZeroPage(byte(value)?)), Some(AddressMode::Absolute(word(value)?))],
+            (Operand::Direct(_), Width::Wide) => vec![Some(AddressMode::Absolute(word(value)?))],
+            (Operand::IndexedX(_), Width::Narrow) =>
+                vec![Some(AddressMode::ZeroPageX(byte(value)?)), Some(AddressMode::AbsoluteX(word(value)?))],
+            (Operand::IndexedX(_), Width::Wide) => vec![Some(AddressMode::AbsoluteX(word(value)?))],
+            (Operand::IndexedY(_), Width::Narrow) =>
+                vec![Some(AddressMode::ZeroPageY(byte(value)?)), Some(AddressMode::AbsoluteY(word(value)?))],
+            (Operand::IndexedY(_), Width::Wide) => vec![Some(AddressMode::AbsoluteY(word(value)?))],
+            (Operand::Indirect(_), _) => vec![Some(AddressMode::Indirect(word(value)?))],
+            (Operand::IndirectX(_), _) => vec![Some(AddressMode::IndirectX(byte(value)?))],
+            (Operand::IndirectY(_), _) => vec![Some(AddressMode::IndirectY(byte(value)?))],
+        };
+        candidates.into_iter()
+            .find_map(|argument| Opcode::from_argument(mnemonic, argument))
+            .ok_or_else(invalid)
+    }
+
+    fn operand_expr(operand: &Operand) -> Option<&expression::Expr> {
+        match operand {
+            Operand::None | Operand::Accumulator => None,
+            Operand::Immediate(expr) | Operand::Direct(expr) | Operand::IndexedX(expr) | Operand::IndexedY(expr) |
+            Operand::Indirect(expr) | Operand::IndirectX(expr) | Operand::IndirectY(expr) => Some(expr),
+        }
+    }
+
+    fn define(&mut self, line: usize, name: &str, value: i64) -> Result<(), AssembleError> {
+        if self.symbols.insert(name.to_string(), value).is_some() {
+            return Err(AssembleError::DuplicateSymbol { line, name: name.to_string() });
+        }
+        Ok(())
+    }
+
+    /// Lays out the program, defining labels and deciding operand widths.
+    fn first_pass(&mut self, lines: &[Line]) -> Result<(), AssembleError> {
+        let mut pc: u16 = 0;
+        let mut deferred = vec![];
+        for line in lines {
+            for statement in &line.statements {
+                let advance = match statement {
+                    Statement::Label(name) => {
+                        self.define(line.number, name, pc as i64)?;
+                        0
+                    },
+                    Statement::Equate(name, expr) => {
+                        match expr.eval(&self.symbols, pc) {
+                            Ok(value) => self.define(line.number, name, value)?,
+                            Err(_) => deferred.push((line.number, name, expr, pc)),
+                        }
+                        0
+                    },
+                    Statement::Org(expr) => {
+                        let value = expr.eval(&self.symbols, pc)
+                            .map_err(|name| AssembleError::UndefinedSymbol { line: line.number, name })?;
+                        pc = u16::try_from(value).map_err(|_| AssembleError::OutOfRange { line: line.number, value })?;
+                        0
+                    },
+                    Statement::Byte(data) => data.iter()
+                        .map(|datum| match datum {
+                            Datum::Expr(_) => 1,
+                            Datum::Text(text) => text.len(),
+                        })
+                        .sum(),
+                    Statement::Word(exprs) => exprs.len() * 2,
+                    Statement::Instruction { mnemonic, operand } => {
+                        let width = match Self::operand_expr(operand) {
+                            Some(expr) if expr.is_wide(&self.symbols, pc) => Width::Wide,
+                            _ => Width::Narrow,
+                        };
+                        self.widths.push(width);
+                        let value = Self::operand_expr(operand).and_then(|expr| expr.eval(&self.symbols, pc).ok());
+                        Self::opcode(line.number, mnemonic, operand, width, value, pc)?.size()
+                    },
+                };
+                // Code may run up to the end of the address space, but not past it.
+                let end = pc as usize + advance;
+                if end > 0x10000 {
+                    return Err(AssembleError::OutOfRange { line: line.number, value: end as i64 });
+                }
+                pc = end as u16;
+            }
+        }
+
+        // Equates using symbols defined after them, in as many rounds as their dependencies need.
+        while !deferred.is_empty() {
+            let count = deferred.len();
+            let mut remaining = vec![];
+            for (line, name, expr, pc) in deferred {
+                match expr.eval(&self.symbols, pc) {
+                    Ok(value) => self.define(line, name, value)?,
+                    Err(undefined) if count == 1 => return Err(AssembleError::UndefinedSymbol { line, name: undefined }),
+                    Err(_) => remaining.push((line, name, expr, pc)),
+                }
+            }
+            if remaining.len() == count {
+                let (line, _, expr, pc) = remaining[0];
+                let name = expr.eval(&self.symbols, pc).expect_err("Equate was unresolved");
+                return Err(AssembleError::UndefinedSymbol { line, name });
+            }
+            deferred = remaining;
+        }
+        Ok(())
+    }
+
+    /// Encodes the program with every symbol known.
+    fn second_pass(&self, lines: &[Line]) -> Result<Vec<Section>, AssembleError> {
+        let mut sections = vec![Section::default()];
+        let mut pc: u16 = 0;
+        let mut widths = self.widths.iter();
+        for line in lines {
+            let eval = |expr: &expression::Expr, pc: u16| expr.eval(&self.symbols, pc)
+                .map_err(|name| AssembleError::UndefinedSymbol { line: line.number, name });
+            let out_of_range = |value: i64| AssembleError::OutOfRange { line: line.number, value };
+
+            for statement in &line.statements {
+                let section = sections.last_mut().expect("There is always a section");
+                let start = section.bytes.len();
+                match statement {
+                    Statement::Label(_) | Statement::Equate(..) => {},
+                    Statement::Org(expr) => {
+                        pc = eval(expr, pc)? as u16;
+                        if section.bytes.is_empty() {
+                            section.origin = pc;
+                        } else {
+                            sections.push(Section { origin: pc, ..Section::default() });
+                        }
+                        continue;
+                    },
+                    Statement::Byte(data) => for datum in data {
+                        match datum {
+                            Datum::Expr(expr) => {
+                                let value = eval(expr, pc)?;
+                                if !(-0x80..=0xff).contains(&value) {
+                                    return Err(out_of_range(value));
+                                }
+                                section.bytes.push(value as u8);
+                            },
+                            Datum::Text(text) => section.bytes.extend(text.bytes()),
+                        }
+                    },
+                    Statement::Word(exprs) => for expr in exprs {
+                        let value = eval(expr, pc)?;
+                        if !(-0x8000..=0xffff).contains(&value) {
+                            return Err(out_of_range(value));
+                        }
+                        section.bytes.extend((value as u16).to_le_bytes());
+                    },
+                    Statement::Instruction { mnemonic, operand } => {
+                        let width = *widths.next().expect("Every instruction has a width");
+                        let value = Self::operand_expr(operand).map(|expr| eval(expr, pc)).transpose()?;
+                        let opcode = Self::opcode(line.number, mnemonic, operand, width, value, pc)?;
+                        let mut writer = std::io::Cursor::new(Vec::new());
+                        opcode.write_options(&mut writer, binrw::Endian::Little, ())
+                            .map_err(|_| AssembleError::InvalidOperand { line: line.number, mnemonic: mnemonic.clone() })?;
+                        section.bytes.extend(writer.into_inner());
+                        section.instructions.push((pc, opcode));
+                    },
+                }
+                pc = pc.wrapping_add((section.bytes.len() - start) as u16);
+            }
+        }
+        sections.retain(|section| !section.bytes.is_empty());
+        Ok(sections)
+    }
+}
+
+/// Assembles source in the syntax nespile writes instructions in (see [`Opcode::to_source_string`]),
+/// with labels (`name:`), equates (`name = expr`), `.org`, `.byte`/`.db` and `.word`/`.dw`.
+///
+/// Expressions may use `$hex`, `%binary`, decimal and `'c'` numbers, symbols, `*` for the current
+/// address, unary `-` and `~`, `<` and `>` for the low and high byte of everything after them,
+/// and `+ - * / & | ^` with parentheses.
+/// Operands are zeropage if they fit in a byte and were not written with more than two hex digits,
+/// so `$0010` is absolute; symbols defined later are always absolute. Branch operands are always
+/// targets; a raw offset is written relative to the branch itself, as `Opcode`'s own output has it (`*+$04`).
+pub fn assemble(source: &str) -> Result<Assembly, AssembleError> {
+    let lines = source.lines()
+        .enumerate()
+        .map(|(idx, text)| {
+            syntax::parse_line(text)
+                .map(|statements| Line { number: idx + 1, statements })
+                .map_err(|message| AssembleError::Syntax { line: idx + 1, message })
+        })
+        .collect::<Result<Vec<Line>, AssembleError>>()?;
+
+    let mut assembler = Assembler { symbols: HashMap::new(), widths: vec![] };
+    assembler.first_pass(&lines)?;
+    let sections = assembler.second_pass(&lines)?;
+    Ok(Assembly {
+        sections,
+        symbols: assembler.symbols.into_iter().collect(),
+    })
+}
+
+impl FromStr for Opcode {
+    type Err = AssembleError;
+
+    /// Parses a single instruction, as written by [`Opcode::to_source_string`].
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let assembly = assemble(text)?;
+        let mut instructions = assembly.instructions();
+        match (instructions.next(), instructions.next()) {
+            (Some((_, opcode)), None) => Ok(*opcode),
+            _ => Err(AssembleError::NotAnInstruction),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    /// `<`, the low byte.
+    LowByte,
+    /// `>`, the high byte.
+    HighByte,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    And,
+    Or,
+    Xor,
+}
+impl BinaryOp {
+    /// Binding strength, higher binding tighter.
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::Xor => 2,
+            BinaryOp::And => 3,
+            BinaryOp::Add | BinaryOp::Subtract => 4,
+            BinaryOp::Multiply | BinaryOp::Divide => 5,
+        }
+    }
+}
+
+/// An operand expression, evaluated once every symbol it uses is defined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number {
+        value: i64,
+        /// Hex digits it was written with, which decide between zeropage and absolute addressing.
+        hex_digits: Option<usize>,
+    },
+    Symbol(String),
+    /// `*`, the address of the current statement.
+    ProgramCounter,
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+impl Expr {
+    /// Evaluates the expression, or gives the name of the first undefined symbol it uses.
+    pub fn eval(&self, symbols: &HashMap<String, i64>, pc: u16) -> Result<i64, String> {
+        Ok(match self {
+            Expr::Number { value, .. } => *value,
+            Expr::Symbol(name) => *symbols.get(name).ok_or_else(|| name.clone())?,
+            Expr::ProgramCounter => pc as i64,
+            Expr::Unary(op, expr) => {
+                let value = expr.eval(symbols, pc)?;
+                match op {
+                    UnaryOp::Negate => -value,
+                    UnaryOp::Not => !value,
+                    UnaryOp::LowByte => value & 0xff,
+                    UnaryOp::HighByte => (value >> 8) & 0xff,
+                }
+            },
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(symbols, pc)?, rhs.eval(symbols, pc)?);
+                match op {
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Subtract => lhs.wrapping_sub(rhs),
+                    BinaryOp::Multiply => lhs.wrapping_mul(rhs),
+                    BinaryOp::Divide => lhs.checked_div(rhs).unwrap_or(0),
+                    BinaryOp::And => lhs & rhs,
+                    BinaryOp::Or => lhs | rhs,
+                    BinaryOp::Xor => lhs ^ rhs,
+                }
+            },
+        })
+    }
+
+    /// Whether the expression needs a 16-bit operand, as for `$0010` or a symbol not yet defined.
+    pub fn is_wide(&self, symbols: &HashMap<String, i64>, pc: u16) -> bool {
+        self.written_wide() || self.eval(symbols, pc).map_or(true, |value| !(0..=0xff).contains(&value))
+    }
+    fn written_wide(&self) -> bool {
+        match self {
+            Expr::Number { hex_digits, .. } => hex_digits.is_some_and(|digits| digits > 2),
+            Expr::Unary(UnaryOp::LowByte | UnaryOp::HighByte, _) => false,
+            Expr::Unary(_, expr) => expr.written_wide(),
+            Expr::Binary(_, lhs, rhs) => lhs.written_wide() || rhs.written_wide(),
+            Expr::Symbol(_) | Expr::ProgramCounter => false,
+        }
+    }
+}
+
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number { value: i64, hex_digits: Option<usize> },
+    Identifier(String),
+    Star,
+    Op(char),
+    Open,
+    Close,
+}
+
+pub fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '@'
+}
+pub fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '@'
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut idx = 0;
+    while idx < chars.len() {
+        let c = chars[idx];
+        let take_while = |start: usize, pred: fn(char) -> bool| {
+            let end = (start..chars.len()).find(|i| !pred(chars[*i])).unwrap_or(chars.len());
+            (chars[start..end].iter().collect::<String>(), end)
+        };
+        match c {
+            ' ' | '\t' => idx += 1,
+            '$' => {
+                let (digits, end) = take_while(idx + 1, |c| c.is_ascii_hexdigit());
+                let value = i64::from_str_radix(&digits, 16).map_err(|_| format!("invalid hex number '{}'", text))?;
+                tokens.push(Token::Number { value, hex_digits: Some(digits.len()) });
+                idx = end;
+            },
+            '%' => {
+                let (digits, end) = take_while(idx + 1, |c| c == '0' || c == '1');
+                let value = i64::from_str_radix(&digits, 2).map_err(|_| format!("invalid binary number '{}'", text))?;
+                tokens.push(Token::Number { value, hex_digits: None });
+                idx = end;
+            },
+            '0'..='9' => {
+                let (digits, end) = take_while(idx, |c| c.is_ascii_digit());
+                let value = digits.parse().map_err(|_| format!("invalid number '{}'", digits))?;
+                tokens.push(Token::Number { value, hex_digits: None });
+                idx = end;
+            },
+            '\'' if chars.get(idx + 2) == Some(&'\'') => {
+                tokens.push(Token::Number { value: chars[idx + 1] as i64, hex_digits: None });
+                idx += 3;
+            },
+            c if is_identifier_start(c) => {
+                let (name, end) = take_while(idx, is_identifier_char);
+                tokens.push(Token::Identifier(name));
+                idx = end;
+            },
+            '*' => {
+                tokens.push(Token::Star);
+                idx += 1;
+            },
+            '+' | '-' | '/' | '&' | '|' | '^' | '~' | '<' | '>' => {
+                tokens.push(Token::Op(c));
+                idx += 1;
+            },
+            '(' => {
+                tokens.push(Token::Open);
+                idx += 1;
+            },
+            ')' => {
+                tokens.push(Token::Close);
+                idx += 1;
+            },
+            _ => return Err(format!("unexpected '{}' in expression", c)),
+        }
+    }
+    Ok(tokens)
+}
+
+/// Precedence-climbing parser over the tokens of one expression.
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn binary_op(&self) -> Option<BinaryOp> {
+        match self.peek()? {
+            Token::Star => Some(BinaryOp::Multiply),
+            Token::Op('+') => Some(BinaryOp::Add),
+            Token::Op('-') => Some(BinaryOp::Subtract),
+            Token::Op('/') => Some(BinaryOp::Divide),
+            Token::Op('&') => Some(BinaryOp::And),
+            Token::Op('|') => Some(BinaryOp::Or),
+            Token::Op('^') => Some(BinaryOp::Xor),
+            _ => None,
+        }
+    }
+    fn expression(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.binary_op().filter(|op| op.precedence() >= min_precedence) {
+            self.position += 1;
+            let rhs = self.expression(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+    /// Byte selectors apply to the whole expression after them, so `<table + 1` is the low byte of the sum.
+    fn unary(&mut self) -> Result<Expr, String> {
+        let (op, operand) = match self.peek() {
+            Some(Token::Op('-')) => (UnaryOp::Negate, 0),
+            Some(Token::Op('~')) => (UnaryOp::Not, 0),
+            Some(Token::Op('<')) => (UnaryOp::LowByte, 1),
+            Some(Token::Op('>')) => (UnaryOp::HighByte, 1),
+            _ => return self.primary(),
+        };
+        self.position += 1;
+        let operand = match operand {
+            0 => self.unary()?,
+            _ => self.expression(0)?,
+        };
+        Ok(Expr::Unary(op, Box::new(operand)))
+    }
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number { value, hex_digits }) => Ok(Expr::Number { value, hex_digits }),
+            Some(Token::Identifier(name)) => Ok(Expr::Symbol(name)),
+            Some(Token::Star) => Ok(Expr::ProgramCounter),
+            Some(Token::Open) => {
+                let expr = self.expression(0)?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("missing ')'".to_string()),
+                }
+            },
+            Some(token) => Err(format!("unexpected {:?} in expression", token)),
+            None => Err("missing expression".to_string()),
+        }
+    }
+}
+
+pub fn parse(text: &str) -> Result<Expr, String> {
+    let mut parser = Parser { tokens: tokenize(text)?, position: 0 };
+    let expr = parser.expression(0)?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(format!("unexpected {:?} after expression", token)),
+    }
+}
@@ -0,0 +1,194 @@
+use crate::assembler::expression::{self, is_identifier_char, is_identifier_start, Expr};
+
+
+
+/// An instruction operand, by syntax; the address mode also depends on its value and the mnemonic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    /// `A`
+    Accumulator,
+    /// `#expr`
+    Immediate(Expr),
+    /// `expr`, a zeropage or absolute address, or a branch target.
+    Direct(Expr),
+    /// `expr,X`
+    IndexedX(Expr),
+    /// `expr,Y`
+    IndexedY(Expr),
+    /// `(expr)`
+    Indirect(Expr),
+    /// `(expr,X)`
+    IndirectX(Expr),
+    /// `(expr),Y`
+    IndirectY(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Datum {
+    Expr(Expr),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    /// `name:`
+    Label(String),
+    /// `name = expr`
+    Equate(String, Expr),
+    /// `.org expr`
+    Org(Expr),
+    /// `.byte` or `.db`
+    Byte(Vec<Datum>),
+    /// `.word` or `.dw`
+    Word(Vec<Expr>),
+    Instruction { mnemonic: String, operand: Operand },
+}
+
+
+
+/// The line up to any comment, which starts at a `;` outside quotes.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = None;
+    for (idx, c) in line.char_indices() {
+        match (quoted, c) {
+            (None, ';') => return &line[..idx],
+            (None, '"' | '\'') => quoted = Some(c),
+            (Some(quote), c) if c == quote => quoted = None,
+            _ => {},
+        }
+    }
+    line
+}
+
+/// Splits on commas outside quotes and parentheses.
+fn split_arguments(text: &str) -> Vec<&str> {
+    let mut arguments = vec![];
+    let (mut depth, mut quoted, mut start) = (0, false, 0);
+    for (idx, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                arguments.push(text[start..idx].trim());
+                start = idx + 1;
+            },
+            _ => {},
+        }
+    }
+    arguments.push(text[start..].trim());
+    arguments
+}
+
+/// Splits a leading identifier off `text`.
+fn split_identifier(text: &str) -> Option<(&str, &str)> {
+    if !text.starts_with(is_identifier_start) {
+        return None;
+    }
+    let end = text.find(|c| !is_identifier_char(c)).unwrap_or(text.len());
+    Some(text.split_at(end))
+}
+
+/// Strips a `,X` or `,Y` index suffix, case-insensitively.
+fn strip_index<'t>(text: &'t str, register: &str) -> Option<&'t str> {
+    let (rest, index) = text.rsplit_once(',')?;
+    index.trim().eq_ignore_ascii_case(register).then_some(rest.trim())
+}
+
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Ok(Operand::None);
+    }
+    if text.eq_ignore_ascii_case("a") {
+        return Ok(Operand::Accumulator);
+    }
+    if let Some(value) = text.strip_prefix('#') {
+        return Ok(Operand::Immediate(expression::parse(value)?));
+    }
+
+    if text.starts_with('(') {
+        // Find the parenthesis closing the first one; the operand is indirect if nothing but an index follows it.
+        let mut depth = 0;
+        let close = text.char_indices()
+            .find(|(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {},
+                }
+                depth == 0
+            })
+            .map(|(idx, _)| idx)
+            .ok_or("missing ')'")?;
+        let (inner, rest) = (&text[1..close], text[close + 1..].trim());
+        if rest.is_empty() {
+            return Ok(match strip_index(inner, "x") {
+                Some(address) => Operand::IndirectX(expression::parse(address)?),
+                None => Operand::Indirect(expression::parse(inner)?),
+            });
+        }
+        if strip_index(rest, "y") == Some("") {
+            return Ok(Operand::IndirectY(expression::parse(inner)?));
+        }
+    }
+
+    if let Some(address) = strip_index(text, "x") {
+        Ok(Operand::IndexedX(expression::parse(address)?))
+    } else if let Some(address) = strip_index(text, "y") {
+        Ok(Operand::IndexedY(expression::parse(address)?))
+    } else {
+        Ok(Operand::Direct(expression::parse(text)?))
+    }
+}
+
+fn parse_datum(text: &str) -> Result<Datum, String> {
+    match text.strip_prefix('"') {
+        Some(quoted) => quoted.strip_suffix('"')
+            .map(|text| Datum::Text(text.to_string()))
+            .ok_or_else(|| "unterminated string".to_string()),
+        None => Ok(Datum::Expr(expression::parse(text)?)),
+    }
+}
+
+/// Parses the statements of one line: any labels, then an equate, directive or instruction.
+pub fn parse_line(line: &str) -> Result<Vec<Statement>, String> {
+    let mut statements = vec![];
+    let mut rest = strip_comment(line).trim();
+
+    while let Some((name, after)) = split_identifier(rest) {
+        let after = after.trim_start();
+        if let Some(after) = after.strip_prefix(':') {
+            statements.push(Statement::Label(name.to_string()));
+            rest = after.trim_start();
+        } else if let Some(value) = after.strip_prefix('=') {
+            statements.push(Statement::Equate(name.to_string(), expression::parse(value)?));
+            return Ok(statements);
+        } else {
+            break;
+        }
+    }
+    if rest.is_empty() {
+        return Ok(statements);
+    }
+
+    if let Some(directive) = rest.strip_prefix('.') {
+        let (name, arguments) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+        let arguments = split_arguments(arguments.trim());
+        statements.push(match name.to_ascii_lowercase().as_str() {
+            "org" => Statement::Org(expression::parse(arguments[0])?),
+            "byte" | "db" => Statement::Byte(arguments.into_iter().map(parse_datum).collect::<Result<_, _>>()?),
+            "word" | "dw" => Statement::Word(arguments.into_iter().map(expression::parse).collect::<Result<_, _>>()?),
+            _ => return Err(format!("unknown directive '.{}'", name)),
+        });
+        return Ok(statements);
+    }
+
+    let (mnemonic, operand) = split_identifier(rest).ok_or_else(|| format!("expected an instruction, found '{}'", rest))?;
+    statements.push(Statement::Instruction {
+        mnemonic: mnemonic.to_ascii_uppercase(),
+        operand: parse_operand(operand)?,
+    });
+    Ok(statements)
+}
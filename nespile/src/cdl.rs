@@ -0,0 +1,130 @@
+use std::fs;
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::mapper::{Mapper, PRGROM_START};
+use crate::parser::disassembler::ByteKind;
+use crate::parser::NesProgram;
+
+
+
+/// PRG byte was executed as an opcode or operand.
+pub const CODE: u8 = 0x01;
+/// PRG byte was read as data.
+pub const DATA: u8 = 0x02;
+/// Which 8KB CPU window, from $8000, a PRG byte was last accessed through.
+pub const WINDOW_MASK: u8 = 0x0c;
+const WINDOW_SHIFT: u8 = 2;
+/// PRG byte was the target of an indirect jump.
+pub const INDIRECT_CODE: u8 = 0x10;
+/// PRG byte was read through an indirect pointer.
+pub const INDIRECT_DATA: u8 = 0x20;
+/// PRG byte was played as DPCM sample data.
+pub const PCM_DATA: u8 = 0x40;
+
+/// CHR byte was drawn by the PPU.
+pub const CHR_RENDERED: u8 = 0x01;
+/// CHR byte was read by the CPU through $2007.
+pub const CHR_READ: u8 = 0x02;
+
+const WINDOW_SIZE: usize = 0x2000;
+
+
+
+#[derive(Error, Debug)]
+pub enum CdlError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("code/data log is {found} bytes, but the ROM has {expected} bytes of PRG-ROM and CHR-ROM")]
+    SizeMismatch { expected: usize, found: usize },
+}
+
+/// An FCEUX code/data log, as also written by Mesen: one byte of flags per PRG-ROM byte, then one per CHR-ROM byte.
+///
+/// See https://fceux.com/web/help/CodeDataLogger.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeDataLog {
+    pub prg: Vec<u8>,
+    pub chr: Vec<u8>,
+}
+impl CodeDataLog {
+    pub fn parse(bytes: &[u8], prg_size: usize, chr_size: usize) -> Result<Self, CdlError> {
+        if bytes.len() != prg_size + chr_size {
+            return Err(CdlError::SizeMismatch { expected: prg_size + chr_size, found: bytes.len() });
+        }
+        let (prg, chr) = bytes.split_at(prg_size);
+        Ok(CodeDataLog { prg: prg.to_vec(), chr: chr.to_vec() })
+    }
+    pub fn load(path: impl AsRef<Path>, prg_size: usize, chr_size: usize) -> Result<Self, CdlError> {
+        CodeDataLog::parse(&fs::read(path)?, prg_size, chr_size)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [self.prg.as_slice(), self.chr.as_slice()].concat()
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CdlError> {
+        Ok(fs::write(path, self.to_bytes())?)
+    }
+
+    /// Logs what the disassembly found: reached instructions as code, jump table targets as
+    /// indirectly reached code, and jump tables and inline parameters as data.
+    pub fn from_program(program: &NesProgram, chr_size: usize) -> Self {
+        let mut prg = vec![0u8; program.prgrom.len()];
+        for instruction in program.instructions.values() {
+            let window = ((instruction.address >> 13) & 0x3) as u8;
+            for flags in &mut prg[instruction.prg_offset..instruction.prg_offset + instruction.size()] {
+                *flags = CODE | window << WINDOW_SHIFT;
+            }
+        }
+
+        for table in &program.jump_tables {
+            for (_, location) in &table.targets {
+                prg[location.prg_offset] |= INDIRECT_CODE;
+            }
+            let Some(dispatch) = program.instruction_at_offset(table.prg_offset) else {
+                continue;
+            };
+            for (lo, hi) in table.entry_bytes() {
+                for address in [lo, hi] {
+                    if let Some(location) = program.resolve_operand(dispatch, address) {
+                        prg[location.prg_offset] |= DATA;
+                    }
+                }
+            }
+        }
+        for data in &program.inline_data {
+            for flags in prg.iter_mut().skip(data.prg_offset).take(data.len) {
+                *flags |= DATA;
+            }
+        }
+
+        CodeDataLog { prg, chr: vec![0u8; chr_size] }
+    }
+
+    pub fn is_code(&self, prg_offset: usize) -> bool {
+        self.prg.get(prg_offset).is_some_and(|flags| flags & CODE != 0)
+    }
+    /// Whether a byte was only ever read as data, never executed.
+    pub fn is_data(&self, prg_offset: usize) -> bool {
+        self.prg.get(prg_offset).is_some_and(|flags| flags & (CODE | DATA) == DATA)
+    }
+
+    /// The CPU address a logged PRG byte was accessed through, or the mapper's usual address for it.
+    pub fn cpu_address(&self, prg_offset: usize, mapper: &dyn Mapper) -> u16 {
+        let flags = self.prg[prg_offset];
+        if flags & (CODE | DATA) == 0 {
+            return mapper.cpu_address(prg_offset);
+        }
+        let window = ((flags & WINDOW_MASK) >> WINDOW_SHIFT) as usize;
+        PRGROM_START + (window * WINDOW_SIZE + prg_offset % WINDOW_SIZE) as u16
+    }
+
+    /// Compares against a disassembly, as (bytes logged as code, of which the disassembly also reached).
+    pub fn coverage(&self, program: &NesProgram) -> (usize, usize) {
+        let logged = (0..self.prg.len()).filter(|offset| self.is_code(*offset));
+        let reached = logged.clone().filter(|offset| program.byte_kinds.get(*offset).is_some_and(|kind| *kind != ByteKind::Data));
+        (logged.count(), reached.count())
+    }
+}
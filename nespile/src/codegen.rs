@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::analysis::cfg::BlockId;
+use crate::ir::{Block, Expr, Function, Program, Stmt, Target, Terminator};
+use crate::parser::disassembler::Vector;
+use crate::symbols::SymbolTable;
+
+pub mod c;
+pub mod llvm;
+pub mod rust;
+pub mod wasm;
+
+
+
+/// First address past the 2KB of internal RAM and its mirrors. Backends access constant addresses
+/// below it as RAM directly, and leave everything from it up to the host.
+pub const IO_START: u16 = 0x2000;
+/// Size of internal RAM, which repeats every this many bytes below `IO_START`.
+pub const RAM_SIZE: u16 = 0x800;
+/// Describes the helper backends call before an NMI or IRQ handler, for them to comment it with.
+pub const INTERRUPT_HELPER: &str = "Pushes a return address of zero, since transpiled code has no program counter, and P.";
+
+/// Where a jump or call from one function ends up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jump {
+    /// A block of the same function.
+    Local(BlockId),
+    /// The entry of a function, which is called rather than jumped into.
+    Function(BlockId),
+    /// An address no function covers, left to the host.
+    Unresolved(u16),
+}
+
+/// Where a `BRK` goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrkTarget {
+    /// The IRQ handler's function.
+    Function(BlockId),
+    /// No function handles IRQs, so the host runs the address read from the vector at this address.
+    Vector(u16),
+}
+
+/// What every backend needs to know about the program outside the function it is emitting.
+pub struct Context<'a> {
+    pub program: &'a Program,
+    names: BTreeMap<BlockId, String>,
+    addresses: BTreeMap<BlockId, u16>,
+}
+impl<'a> Context<'a> {
+    /// Names each function after its symbol, avoiding names already taken and any the backend reserves.
+    pub fn new(program: &'a Program, symbols: &SymbolTable, reserved: impl Fn(&str) -> bool) -> Self {
+        let addresses: BTreeMap<BlockId, u16> = program.functions.values()
+            .flat_map(|function| function.blocks.values())
+            .map(|block| (block.id, block.address))
+            .collect();
+
+        let mut used = HashSet::new();
+        let mut names = BTreeMap::new();
+        for (&entry, function) in &program.functions {
+            let block = function.entry_block();
+            let name = symbols.rom_symbol(entry)
+                .map(|symbol| identifier(&symbol.name))
+                .unwrap_or_else(|| format!("sub_{:04X}", block.address));
+            let mut unique = name.clone();
+            let mut suffix = 1;
+            while reserved(&unique) || used.contains(&unique) {
+                unique = match suffix {
+                    1 => format!("{}_b{}", name, block.bank),
+                    _ => format!("{}_{}", name, suffix),
+                };
+                suffix += 1;
+            }
+            used.insert(unique.clone());
+            names.insert(entry, unique);
+        }
+        Context { program, names, addresses }
+    }
+
+    /// The function's name in generated code.
+    pub fn name(&self, entry: BlockId) -> &str {
+        &self.names[&entry]
+    }
+    pub fn functions(&self) -> impl Iterator<Item = (&str, &'a Function)> {
+        self.program.functions.iter().map(|(entry, function)| (self.name(*entry), function))
+    }
+    /// Each interrupt vector's entry function.
+    pub fn vector_function(&self, vector: Vector) -> Option<BlockId> {
+        self.program.vectors.iter().find(|(v, _)| *v == vector).map(|(_, entry)| *entry)
+    }
+
+    /// How `function` reaches `target` by jumping, where a jump into another function is a tail call.
+    pub fn jump(&self, function: &Function, target: Target) -> Jump {
+        match target {
+            Target::Block(block) if function.blocks.contains_key(&block) => Jump::Local(block),
+            target => self.call(target),
+        }
+    }
+    /// How a `JSR` reaches `target`.
+    pub fn call(&self, target: Target) -> Jump {
+        match target {
+            Target::Block(block) if self.program.functions.contains_key(&block) => Jump::Function(block),
+            Target::Block(block) => Jump::Unresolved(self.addresses.get(&block).copied().unwrap_or_default()),
+            Target::Address(address) => Jump::Unresolved(address),
+        }
+    }
+    /// Where a `BRK` through `vector` goes. Transpiled code can't resume past the `BRK` once the
+    /// handler's `RTI` pulls its address, so backends end the function there.
+    pub fn brk_target(&self, vector: u16) -> BrkTarget {
+        match self.vector_function(Vector::IRQ) {
+            Some(irq) => BrkTarget::Function(irq),
+            None => BrkTarget::Vector(vector),
+        }
+    }
+    /// The CPU address of each block an indirect jump was recovered to go to, keeping the first block at each address.
+    pub fn indirect_targets(&self, targets: &[BlockId]) -> Vec<(u16, BlockId)> {
+        let mut seen = HashSet::new();
+        targets.iter()
+            .filter_map(|block| Some((*self.addresses.get(block)?, *block)))
+            .filter(|(address, _)| seen.insert(*address))
+            .collect()
+    }
+}
+
+/// A symbol name made into an identifier every backend accepts.
+fn identifier(name: &str) -> String {
+    let mut identifier: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !identifier.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        identifier.insert(0, 'f');
+    }
+    identifier
+}
+
+/// Whether a name is that of a temporary, which every backend names `t` followed by its number.
+fn is_temp(name: &str) -> bool {
+    name.strip_prefix('t').is_some_and(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// The temporaries a block reads, other than in the address of a return, which the backends don't need.
+fn used_temps(block: &Block) -> HashSet<u32> {
+    fn visit(expr: &Expr, used: &mut HashSet<u32>) {
+        match expr {
+            Expr::Temp(temp) => { used.insert(temp.id); },
+            Expr::Load(inner) | Expr::Cast(_, inner) | Expr::Unary(_, inner) => visit(inner, used),
+            Expr::Binary(_, lhs, rhs) => {
+                visit(lhs, used);
+                visit(rhs, used);
+            },
+            _ => {},
+        }
+    }
+    let mut used = HashSet::new();
+    for stmt in &block.stmts {
+        match stmt {
+            Stmt::Let(_, value) | Stmt::SetReg(_, value) | Stmt::SetFlag(_, value) => visit(value, &mut used),
+            Stmt::Store(address, value) => {
+                visit(address, &mut used);
+                visit(value, &mut used);
+            },
+        }
+    }
+    match &block.terminator {
+        Terminator::Branch { condition, .. } => visit(condition, &mut used),
+        Terminator::JumpIndirect { address, .. } => visit(address, &mut used),
+        _ => {},
+    }
+    used
+}
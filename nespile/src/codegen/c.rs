@@ -0,0 +1,299 @@
+use std::fmt::Write;
+
+use crate::ir::{BinaryOp, Block, Expr, Flag, Function, Program, Reg, Stmt, Target, Terminator, Type, UnaryOp};
+use crate::parser::disassembler::Vector;
+use crate::symbols::SymbolTable;
+
+use super::{is_temp, used_temps, BrkTarget, Context, Jump, INTERRUPT_HELPER, IO_START, RAM_SIZE};
+
+
+
+/// C99's keywords, the names `stdbool.h` and `stdint.h` define other than their types and macros, and those the
+/// generated source uses.
+const RESERVED: [&str; 46] = [
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else", "enum", "extern",
+    "float", "for", "goto", "if", "inline", "int", "long", "register", "restrict", "return", "short", "signed",
+    "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned", "void", "volatile", "while", "bool",
+    "true", "false", "main", "cpu", "address", "_Bool", "_Complex", "_Imaginary", "read", "write", "memset",
+];
+/// The macros `stdint.h` defines that don't start with `INT` or `UINT`.
+const STDINT_LIMITS: [&str; 9] = [
+    "PTRDIFF_MIN", "PTRDIFF_MAX", "SIZE_MAX", "WCHAR_MIN", "WCHAR_MAX", "WINT_MIN", "WINT_MAX", "SIG_ATOMIC_MIN",
+    "SIG_ATOMIC_MAX",
+];
+
+
+
+/// A header declaring the CPU state, the platform layer's hooks and every function, and the source defining them.
+#[derive(Debug, Clone)]
+pub struct CSource {
+    pub header: String,
+    pub source: String,
+}
+
+/// Generates `<name>.h` and `<name>.c`.
+pub fn generate(program: &Program, symbols: &SymbolTable, name: &str) -> CSource {
+    let context = Context::new(program, symbols, |name| {
+        RESERVED.contains(&name) || name.starts_with("nes_") || is_temp(name) || is_library_name(name)
+    });
+    CSource {
+        header: header(&context, name),
+        source: source(&context, name),
+    }
+}
+
+/// Whether a name is one `stdint.h` may declare, as a type or a limit macro, or one C reserves for the implementation.
+fn is_library_name(name: &str) -> bool {
+    let limit = ["_MIN", "_MAX", "_C"].iter().any(|suffix| name.ends_with(suffix));
+    name.ends_with("_t") ||
+        ((name.starts_with("INT") || name.starts_with("UINT")) && limit) ||
+        STDINT_LIMITS.contains(&name) ||
+        name.starts_with("__") ||
+        name.strip_prefix('_').is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_uppercase()))
+}
+
+fn header(context: &Context, name: &str) -> String {
+    let guard = format!("{}_H", name.to_ascii_uppercase().replace(|c: char| !c.is_ascii_alphanumeric(), "_"));
+    let mut header = String::new();
+    writeln!(header, "/* Transpiled by nespile. */").unwrap();
+    writeln!(header, "#ifndef {}", guard).unwrap();
+    writeln!(header, "#define {}", guard).unwrap();
+    writeln!(header).unwrap();
+    writeln!(header, "#include <stdbool.h>").unwrap();
+    writeln!(header, "#include <stdint.h>").unwrap();
+    writeln!(header).unwrap();
+    writeln!(header, "/* The 6502's registers, with each status flag kept as its own bool. */").unwrap();
+    writeln!(header, "typedef struct nes_cpu {{").unwrap();
+    writeln!(header, "    uint8_t a, x, y, sp;").unwrap();
+    writeln!(header, "    bool c, z, i, d, v, n;").unwrap();
+    writeln!(header, "}} nes_cpu;").unwrap();
+    writeln!(header).unwrap();
+    writeln!(header, "/* Internal RAM, mirrored through $0000-$1FFF. */").unwrap();
+    writeln!(header, "extern uint8_t nes_ram[{:#x}];", RAM_SIZE).unwrap();
+    writeln!(header).unwrap();
+    writeln!(header, "/* Provided by the platform layer: the PPU, APU and I/O registers and the cartridge, from $2000 up. */").unwrap();
+    writeln!(header, "uint8_t nes_read(uint16_t address);").unwrap();
+    writeln!(header, "void nes_write(uint16_t address, uint8_t value);").unwrap();
+    writeln!(header, "/* Provided by the platform layer: runs code at an address that was never transpiled, until it returns. */").unwrap();
+    writeln!(header, "void nes_unresolved(nes_cpu *cpu, uint16_t address);").unwrap();
+    writeln!(header, "/* Provided by the platform layer: the CPU ran a `STP`, which only a reset recovers from. */").unwrap();
+    writeln!(header, "void nes_halt(nes_cpu *cpu);").unwrap();
+    writeln!(header).unwrap();
+    writeln!(header, "/* Sets the state at power on. */").unwrap();
+    writeln!(header, "void nes_power_on(nes_cpu *cpu);").unwrap();
+    writeln!(header, "/* Do what the CPU does on reset or an interrupt, then run its handler. */").unwrap();
+    for (_, entry_point) in entry_points(context) {
+        writeln!(header, "void {}(nes_cpu *cpu);", entry_point).unwrap();
+    }
+    writeln!(header).unwrap();
+    for (name, function) in context.functions() {
+        let entry = function.entry_block();
+        writeln!(header, "void {}(nes_cpu *cpu); /* ${:04X}, bank {} */", name, entry.address, entry.bank).unwrap();
+    }
+    writeln!(header).unwrap();
+    writeln!(header, "#endif").unwrap();
+    header
+}
+
+/// Each vector with a handler, and the name of the function entering it.
+fn entry_points(context: &Context) -> Vec<(Vector, &'static str)> {
+    [(Vector::Reset, "nes_reset"), (Vector::NMI, "nes_nmi"), (Vector::IRQ, "nes_irq")].into_iter()
+        .filter(|(vector, _)| context.vector_function(*vector).is_some())
+        .collect()
+}
+
+fn source(context: &Context, name: &str) -> String {
+    let mut source = String::new();
+    writeln!(source, "/* Transpiled by nespile. */").unwrap();
+    writeln!(source, "#include \"{}.h\"", name).unwrap();
+    writeln!(source).unwrap();
+    writeln!(source, "uint8_t nes_ram[{:#x}];", RAM_SIZE).unwrap();
+    writeln!(source).unwrap();
+    writeln!(source, "static inline uint8_t nes_load(uint16_t address) {{").unwrap();
+    writeln!(source, "    return address < {:#06x} ? nes_ram[address % {:#x}] : nes_read(address);", IO_START, RAM_SIZE).unwrap();
+    writeln!(source, "}}").unwrap();
+    writeln!(source, "static inline void nes_store(uint16_t address, uint8_t value) {{").unwrap();
+    writeln!(source, "    if (address < {:#06x}) {{", IO_START).unwrap();
+    writeln!(source, "        nes_ram[address % {:#x}] = value;", RAM_SIZE).unwrap();
+    writeln!(source, "    }} else {{").unwrap();
+    writeln!(source, "        nes_write(address, value);").unwrap();
+    writeln!(source, "    }}").unwrap();
+    writeln!(source, "}}").unwrap();
+    source.push_str(concat!(
+        "\n",
+        "void nes_power_on(nes_cpu *cpu) {\n",
+        "    *cpu = (nes_cpu){ .sp = 0xfd, .i = true };\n",
+        "}\n",
+        "\n",
+    ));
+    writeln!(source, "/* {} */", INTERRUPT_HELPER).unwrap();
+    source.push_str(concat!(
+        "static void nes_interrupt(nes_cpu *cpu) {\n",
+        "    uint8_t p = 0x20 | cpu->c | cpu->z << 1 | cpu->i << 2 | cpu->d << 3 | cpu->v << 6 | cpu->n << 7;\n",
+        "    nes_store(0x0100 | cpu->sp--, 0x00);\n",
+        "    nes_store(0x0100 | cpu->sp--, 0x00);\n",
+        "    nes_store(0x0100 | cpu->sp--, p);\n",
+        "    cpu->i = true;\n",
+        "}\n",
+    ));
+    for (vector, entry_point) in entry_points(context) {
+        let handler = context.name(context.vector_function(vector).expect("Entry points have handlers"));
+        let setup = match vector {
+            Vector::Reset => "cpu->sp -= 3;\n    cpu->i = true;",
+            Vector::NMI | Vector::IRQ => "nes_interrupt(cpu);",
+        };
+        writeln!(source).unwrap();
+        writeln!(source, "void {}(nes_cpu *cpu) {{", entry_point).unwrap();
+        writeln!(source, "    {}", setup).unwrap();
+        writeln!(source, "    {}(cpu);", handler).unwrap();
+        writeln!(source, "}}").unwrap();
+    }
+    for (name, function) in context.functions() {
+        writeln!(source).unwrap();
+        write_function(&mut source, context, name, function);
+    }
+    source
+}
+
+/// A function with a label for each block, starting at the entry block.
+fn write_function(source: &mut String, context: &Context, name: &str, function: &Function) {
+    writeln!(source, "void {}(nes_cpu *cpu) {{", name).unwrap();
+    writeln!(source, "    goto b{:x};", function.entry).unwrap();
+    for block in function.blocks.values() {
+        writeln!(source, "b{:x}: {{ /* ${:04X} */", block.id, block.address).unwrap();
+        write_block(source, context, function, block);
+        writeln!(source, "}}").unwrap();
+    }
+    writeln!(source, "}}").unwrap();
+}
+
+fn write_block(source: &mut String, context: &Context, function: &Function, block: &Block) {
+    const INDENT: &str = "    ";
+    let used = used_temps(block);
+    for stmt in &block.stmts {
+        let stmt = match stmt {
+            // Kept for the read, such as the address pulled by a return or an unofficial NOP's operand.
+            Stmt::Let(temp, value) if !used.contains(&temp.id) => format!("(void){};", expr(value)),
+            stmt => stmt_source(stmt),
+        };
+        writeln!(source, "{}{}", INDENT, stmt).unwrap();
+    }
+    let jump = |target| jump_source(context, context.jump(function, target));
+    let terminator = match &block.terminator {
+        Terminator::Goto(target) => jump(*target),
+        Terminator::Branch { condition, taken, not_taken } => format!(
+            "if ({}) {{ {} }} else {{ {} }}",
+            expr(condition), jump(*taken), jump(*not_taken),
+        ),
+        Terminator::Call { callee, return_to } => {
+            let call = match context.call(*callee) {
+                Jump::Function(callee) => format!("{}(cpu);", context.name(callee)),
+                Jump::Local(_) => unreachable!("Calls always leave the function"),
+                Jump::Unresolved(address) => format!("nes_unresolved(cpu, {:#06x});", address),
+            };
+            format!("{} {}", call, jump(*return_to))
+        },
+        Terminator::Return(_) | Terminator::ReturnFromInterrupt(_) => "return;".to_string(),
+        Terminator::JumpIndirect { address, targets } => {
+            let mut cases: Vec<String> = context.indirect_targets(targets).into_iter()
+                .map(|(address, target)| format!("case {:#06x}: {}", address, jump(Target::Block(target))))
+                .collect();
+            cases.push("default: nes_unresolved(cpu, address); return;".to_string());
+            format!("uint16_t address = {}; switch (address) {{ {} }}", expr(address), cases.join(" "))
+        },
+        Terminator::Interrupt { vector } => match context.brk_target(*vector) {
+            BrkTarget::Function(irq) => format!("{}(cpu); return;", context.name(irq)),
+            BrkTarget::Vector(vector) => format!(
+                "nes_unresolved(cpu, nes_load({:#06x}) | nes_load({:#06x}) << 8); return;",
+                vector, vector.wrapping_add(1),
+            ),
+        },
+        Terminator::Halt => "nes_halt(cpu); return;".to_string(),
+    };
+    writeln!(source, "{}{}", INDENT, terminator).unwrap();
+}
+
+fn jump_source(context: &Context, jump: Jump) -> String {
+    match jump {
+        Jump::Local(block) => format!("goto b{:x};", block),
+        Jump::Function(entry) => format!("{}(cpu); return;", context.name(entry)),
+        Jump::Unresolved(address) => format!("nes_unresolved(cpu, {:#06x}); return;", address),
+    }
+}
+
+fn stmt_source(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Let(temp, value) => format!("{} t{} = {};", type_name(temp.ty), temp.id, expr(value)),
+        Stmt::SetReg(reg, value) => format!("{} = {};", reg_name(*reg), expr(value)),
+        Stmt::SetFlag(flag, value) => format!("{} = {};", flag_name(*flag), expr(value)),
+        Stmt::Store(Expr::U16(address), value) if *address < IO_START =>
+            format!("nes_ram[{:#05x}] = {};", address % RAM_SIZE, expr(value)),
+        Stmt::Store(Expr::U16(address), value) => format!("nes_write({:#06x}, {});", address, expr(value)),
+        Stmt::Store(address, value) => format!("nes_store({}, {});", expr(address), expr(value)),
+    }
+}
+
+fn type_name(ty: Type) -> &'static str {
+    match ty {
+        Type::Bool => "bool",
+        Type::U8 => "uint8_t",
+        Type::U16 => "uint16_t",
+    }
+}
+fn reg_name(reg: Reg) -> &'static str {
+    match reg {
+        Reg::A => "cpu->a",
+        Reg::X => "cpu->x",
+        Reg::Y => "cpu->y",
+        Reg::SP => "cpu->sp",
+    }
+}
+fn flag_name(flag: Flag) -> &'static str {
+    match flag {
+        Flag::C => "cpu->c",
+        Flag::Z => "cpu->z",
+        Flag::I => "cpu->i",
+        Flag::D => "cpu->d",
+        Flag::V => "cpu->v",
+        Flag::N => "cpu->n",
+    }
+}
+
+/// An expression as C, fully parenthesized and cast back to its type wherever integer promotion could widen it.
+fn expr(value: &Expr) -> String {
+    match value {
+        Expr::Bool(value) => value.to_string(),
+        Expr::U8(value) => format!("{:#04x}", value),
+        Expr::U16(value) => format!("{:#06x}", value),
+        Expr::Reg(reg) => reg_name(*reg).to_string(),
+        Expr::Flag(flag) => flag_name(*flag).to_string(),
+        Expr::Temp(temp) => format!("t{}", temp.id),
+        Expr::Load(address) => match **address {
+            Expr::U16(address) if address < IO_START => format!("nes_ram[{:#05x}]", address % RAM_SIZE),
+            Expr::U16(address) => format!("nes_read({:#06x})", address),
+            _ => format!("nes_load({})", expr(address)),
+        },
+        Expr::Cast(Type::Bool, inner) => format!("({} != 0)", expr(inner)),
+        Expr::Cast(ty, inner) => format!("(({}){})", type_name(*ty), expr(inner)),
+        Expr::Unary(UnaryOp::Not, inner) if inner.ty() == Type::Bool => format!("(!{})", expr(inner)),
+        Expr::Unary(UnaryOp::Not, inner) => format!("(({})~{})", type_name(inner.ty()), expr(inner)),
+        Expr::Unary(UnaryOp::Negative, inner) => format!("(({} & 0x80) != 0)", expr(inner)),
+        Expr::Unary(UnaryOp::IsZero, inner) => format!("({} == 0)", expr(inner)),
+        Expr::Binary(op, lhs, rhs) => {
+            let (symbol, wraps) = match op {
+                BinaryOp::Add => ("+", true),
+                BinaryOp::Sub => ("-", true),
+                BinaryOp::Shl => ("<<", true),
+                BinaryOp::And => ("&", false),
+                BinaryOp::Or => ("|", false),
+                BinaryOp::Xor => ("^", false),
+                BinaryOp::Shr => (">>", false),
+                BinaryOp::Ge => (">=", false),
+            };
+            match wraps {
+                true => format!("(({})({} {} {}))", type_name(lhs.ty()), expr(lhs), symbol, expr(rhs)),
+                false => format!("({} {} {})", expr(lhs), symbol, expr(rhs)),
+            }
+        },
+    }
+}
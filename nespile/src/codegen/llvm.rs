@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::ir::{BinaryOp, Block, Expr, Flag, Function, Program, Reg, Stmt, Target, Terminator, Type, UnaryOp};
+use crate::parser::disassembler::Vector;
+use crate::symbols::SymbolTable;
+
+use super::{BrkTarget, Context, Jump, INTERRUPT_HELPER, IO_START, RAM_SIZE};
+
+
+
+/// The fields of `%nes_cpu`, laid out as the C backend's `nes_cpu` with each flag a byte holding 0 or 1.
+const FIELDS: [&str; 10] = ["a", "x", "y", "sp", "c", "z", "i", "d", "v", "n"];
+
+/// Declarations every module shares: the state, RAM, the platform layer's hooks and the helpers the functions use.
+fn prelude() -> String {
+    format!(
+        r#"; Transpiled by nespile. Uses opaque pointers, the default since LLVM 15.
+
+%nes_cpu = type {{ i8, i8, i8, i8, i8, i8, i8, i8, i8, i8 }}
+
+@nes_ram = global [{ram_size} x i8] zeroinitializer
+
+declare i8 @nes_read(i16)
+declare void @nes_write(i16, i8)
+declare void @nes_unresolved(ptr, i16)
+declare void @llvm.trap()
+
+define internal i8 @nes_load(i16 %address) alwaysinline {{
+  %io = icmp uge i16 %address, {io_start}
+  br i1 %io, label %read, label %ram
+read:
+  %value = call i8 @nes_read(i16 %address)
+  ret i8 %value
+ram:
+  %index = and i16 %address, {ram_mask}
+  %pointer = getelementptr inbounds [{ram_size} x i8], ptr @nes_ram, i16 0, i16 %index
+  %byte = load i8, ptr %pointer
+  ret i8 %byte
+}}
+
+define internal void @nes_store(i16 %address, i8 %value) alwaysinline {{
+  %io = icmp uge i16 %address, {io_start}
+  br i1 %io, label %write, label %ram
+write:
+  call void @nes_write(i16 %address, i8 %value)
+  ret void
+ram:
+  %index = and i16 %address, {ram_mask}
+  %pointer = getelementptr inbounds [{ram_size} x i8], ptr @nes_ram, i16 0, i16 %index
+  store i8 %value, ptr %pointer
+  ret void
+}}
+
+define void @nes_power_on(ptr %cpu) {{
+  store %nes_cpu {{ i8 0, i8 0, i8 0, i8 253, i8 0, i8 0, i8 1, i8 0, i8 0, i8 0 }}, ptr %cpu
+  ret void
+}}
+
+; {interrupt_helper}
+define internal void @nes_interrupt(ptr %cpu) {{
+  %sp.pointer = getelementptr inbounds %nes_cpu, ptr %cpu, i32 0, i32 3
+  %sp = load i8, ptr %sp.pointer
+  %p = call i8 @nes_status(ptr %cpu)
+  %sp.1 = sub i8 %sp, 1
+  %sp.2 = sub i8 %sp, 2
+  %sp.3 = sub i8 %sp, 3
+  call void @nes_push(i8 %sp, i8 0)
+  call void @nes_push(i8 %sp.1, i8 0)
+  call void @nes_push(i8 %sp.2, i8 %p)
+  store i8 %sp.3, ptr %sp.pointer
+  %i.pointer = getelementptr inbounds %nes_cpu, ptr %cpu, i32 0, i32 6
+  store i8 1, ptr %i.pointer
+  ret void
+}}
+
+define internal void @nes_push(i8 %sp, i8 %value) alwaysinline {{
+  %offset = zext i8 %sp to i16
+  %address = or i16 256, %offset
+  call void @nes_store(i16 %address, i8 %value)
+  ret void
+}}
+
+; P with the unused bit set and the break bit clear.
+define internal i8 @nes_status(ptr %cpu) alwaysinline {{
+  %c.pointer = getelementptr inbounds %nes_cpu, ptr %cpu, i32 0, i32 4
+  %c = load i8, ptr %c.pointer
+  %z.pointer = getelementptr inbounds %nes_cpu, ptr %cpu, i32 0, i32 5
+  %z = load i8, ptr %z.pointer
+  %i.pointer = getelementptr inbounds %nes_cpu, ptr %cpu, i32 0, i32 6
+  %i = load i8, ptr %i.pointer
+  %d.pointer = getelementptr inbounds %nes_cpu, ptr %cpu, i32 0, i32 7
+  %d = load i8, ptr %d.pointer
+  %v.pointer = getelementptr inbounds %nes_cpu, ptr %cpu, i32 0, i32 8
+  %v = load i8, ptr %v.pointer
+  %n.pointer = getelementptr inbounds %nes_cpu, ptr %cpu, i32 0, i32 9
+  %n = load i8, ptr %n.pointer
+  %z.bit = shl i8 %z, 1
+  %i.bit = shl i8 %i, 2
+  %d.bit = shl i8 %d, 3
+  %v.bit = shl i8 %v, 6
+  %n.bit = shl i8 %n, 7
+  %p.0 = or i8 32, %c
+  %p.1 = or i8 %p.0, %z.bit
+  %p.2 = or i8 %p.1, %i.bit
+  %p.3 = or i8 %p.2, %d.bit
+  %p.4 = or i8 %p.3, %v.bit
+  %p = or i8 %p.4, %n.bit
+  ret i8 %p
+}}
+"#,
+        io_start = IO_START, ram_size = RAM_SIZE, ram_mask = RAM_SIZE - 1, interrupt_helper = INTERRUPT_HELPER,
+    )
+}
+
+
+
+/// Generates a module with one function per recovered function, taking a pointer to `%nes_cpu`,
+/// and the same entry points and platform hooks as the C backend.
+pub fn generate(program: &Program, symbols: &SymbolTable) -> String {
+    let context = Context::new(program, symbols, |name| {
+        name.starts_with("nes_") || name.starts_with("llvm") || name == "main"
+    });
+    let mut module = prelude();
+    for (vector, entry_point) in [(Vector::Reset, "nes_reset"), (Vector::NMI, "nes_nmi"), (Vector::IRQ, "nes_irq")] {
+        let Some(handler) = context.vector_function(vector) else {
+            continue;
+        };
+        writeln!(module).unwrap();
+        writeln!(module, "define void @{}(ptr %cpu) {{", entry_point).unwrap();
+        match vector {
+            Vector::Reset => {
+                writeln!(module, "  %sp.pointer = getelementptr inbounds %nes_cpu, ptr %cpu, i32 0, i32 3").unwrap();
+                writeln!(module, "  %sp = load i8, ptr %sp.pointer").unwrap();
+                writeln!(module, "  %sp.reset = sub i8 %sp, 3").unwrap();
+                writeln!(module, "  store i8 %sp.reset, ptr %sp.pointer").unwrap();
+                writeln!(module, "  %i.pointer = getelementptr inbounds %nes_cpu, ptr %cpu, i32 0, i32 6").unwrap();
+                writeln!(module, "  store i8 1, ptr %i.pointer").unwrap();
+            },
+            Vector::NMI | Vector::IRQ => writeln!(module, "  call void @nes_interrupt(ptr %cpu)").unwrap(),
+        }
+        writeln!(module, "  call void @{}(ptr %cpu)", context.name(handler)).unwrap();
+        writeln!(module, "  ret void").unwrap();
+        writeln!(module, "}}").unwrap();
+    }
+    for (name, function) in context.functions() {
+        writeln!(module).unwrap();
+        module.push_str(&FunctionEmitter::new(&context, function).emit(name));
+    }
+    module
+}
+
+
+
+/// Emits one function, keeping each register and flag in an alloca that LLVM promotes to SSA values.
+///
+/// Z and N are computed lazily: their allocas hold the byte they were last set from, which
+/// is only compared, for zero and for bit 7, where the flag is read or written back.
+struct FunctionEmitter<'a> {
+    context: &'a Context<'a>,
+    function: &'a Function,
+    body: String,
+    next_value: u32,
+    /// The value of each temporary, which is never given an instruction of its own.
+    temps: HashMap<u32, String>,
+    /// Blocks for jumps which can't go straight to a label, such as calls in tail position.
+    trampolines: Vec<(String, String)>,
+}
+impl<'a> FunctionEmitter<'a> {
+    fn new(context: &'a Context<'a>, function: &'a Function) -> Self {
+        FunctionEmitter { context, function, body: String::new(), next_value: 0, temps: HashMap::new(), trampolines: vec![] }
+    }
+
+    fn emit(mut self, name: &str) -> String {
+        let entry = self.function.entry_block();
+        let mut source = String::new();
+        writeln!(source, "; ${:04X}, bank {}", entry.address, entry.bank).unwrap();
+        writeln!(source, "define void @{}(ptr %cpu) {{", name).unwrap();
+        writeln!(source, "entry:").unwrap();
+        for (index, field) in FIELDS.into_iter().enumerate() {
+            writeln!(source, "  %{} = alloca {}", field, alloca_type(field)).unwrap();
+            writeln!(source, "  %{}.state = getelementptr inbounds %nes_cpu, ptr %cpu, i32 0, i32 {}", field, index).unwrap();
+        }
+        self.sync_in();
+        writeln!(self.body, "  br label %b{:x}", self.function.entry).unwrap();
+        source.push_str(&std::mem::take(&mut self.body));
+
+        for block in self.function.blocks.values() {
+            writeln!(source, "b{:x}: ; ${:04X}", block.id, block.address).unwrap();
+            self.block(block);
+            source.push_str(&std::mem::take(&mut self.body));
+        }
+        for (label, body) in &self.trampolines {
+            writeln!(source, "{}:", label).unwrap();
+            source.push_str(body);
+        }
+        writeln!(source, "}}").unwrap();
+        source
+    }
+
+    fn line(&mut self, instruction: String) {
+        writeln!(self.body, "  {}", instruction).unwrap();
+    }
+    /// Emits an instruction producing a value, returning its name.
+    fn value(&mut self, instruction: String) -> String {
+        let name = format!("%v{}", self.next_value);
+        self.next_value += 1;
+        self.line(format!("{} = {}", name, instruction));
+        name
+    }
+
+    /// Loads the state into the allocas, turning Z and N back into bytes they could have been computed from.
+    fn sync_in(&mut self) {
+        for field in FIELDS {
+            let byte = self.value(format!("load i8, ptr %{}.state", field));
+            let value = match field {
+                _ if alloca_type(field) == "i1" => self.value(format!("icmp ne i8 {}, 0", byte)),
+                "z" => {
+                    let set = self.value(format!("icmp ne i8 {}, 0", byte));
+                    self.value(format!("select i1 {}, i8 0, i8 1", set))
+                },
+                "n" => self.value(format!("shl i8 {}, 7", byte)),
+                _ => byte,
+            };
+            self.line(format!("store {} {}, ptr %{}", alloca_type(field), value, field));
+        }
+    }
+    /// Stores the allocas back to the state, before a call or return.
+    fn sync_out(&mut self) {
+        for field in FIELDS {
+            let byte = match field {
+                _ if alloca_type(field) == "i1" => {
+                    let flag = self.value(format!("load i1, ptr %{}", field));
+                    self.value(format!("zext i1 {} to i8", flag))
+                },
+                "z" | "n" => {
+                    let flag = self.flag(if field == "z" { Flag::Z } else { Flag::N });
+                    self.value(format!("zext i1 {} to i8", flag))
+                },
+                _ => self.value(format!("load i8, ptr %{}", field)),
+            };
+            self.line(format!("store i8 {}, ptr %{}.state", byte, field));
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        for stmt in &block.stmts {
+            self.stmt(stmt);
+        }
+        match &block.terminator {
+            Terminator::Goto(target) => {
+                let label = self.jump_label(block, *target);
+                self.line(format!("br label %{}", label));
+            },
+            Terminator::Branch { condition, taken, not_taken } => {
+                let condition = self.expr(condition);
+                let taken = self.jump_label(block, *taken);
+                let not_taken = self.jump_label(block, *not_taken);
+                self.line(format!("br i1 {}, label %{}, label %{}", condition, taken, not_taken));
+            },
+            Terminator::Call { callee, return_to } => {
+                match self.context.call(*callee) {
+                    Jump::Function(callee) => self.call_function(callee),
+                    Jump::Local(_) => unreachable!("Calls always leave the function"),
+                    Jump::Unresolved(address) => self.call_unresolved(address),
+                }
+                let label = self.jump_label(block, *return_to);
+                self.line(format!("br label %{}", label));
+            },
+            Terminator::Return(_) | Terminator::ReturnFromInterrupt(_) => {
+                self.sync_out();
+                self.line("ret void".to_string());
+            },
+            Terminator::JumpIndirect { address, targets } => {
+                let address = self.expr(address);
+                let cases: Vec<String> = self.context.indirect_targets(targets).into_iter()
+                    .map(|(target_address, target)| {
+                        let label = self.jump_label(block, Target::Block(target));
+                        format!("i16 {}, label %{}", target_address, label)
+                    })
+                    .collect();
+                let unresolved = self.trampoline(block, |emitter| {
+                    emitter.tail(format!("call void @nes_unresolved(ptr %cpu, i16 {})", address));
+                });
+                self.line(format!("switch i16 {}, label %{} [ {} ]", address, unresolved, cases.join(" ")));
+            },
+            Terminator::Interrupt { vector } => {
+                match self.context.brk_target(*vector) {
+                    BrkTarget::Function(irq) => self.tail(format!("call void @{}(ptr %cpu)", self.context.name(irq))),
+                    BrkTarget::Vector(vector) => {
+                        let lo = self.value(format!("call i8 @nes_load(i16 {})", vector));
+                        let hi = self.value(format!("call i8 @nes_load(i16 {})", vector.wrapping_add(1)));
+                        let lo = self.value(format!("zext i8 {} to i16", lo));
+                        let hi = self.value(format!("zext i8 {} to i16", hi));
+                        let hi = self.value(format!("shl i16 {}, 8", hi));
+                        let address = self.value(format!("or i16 {}, {}", lo, hi));
+                        self.tail(format!("call void @nes_unresolved(ptr %cpu, i16 {})", address));
+                    },
+                }
+            },
+            Terminator::Halt => {
+                self.line("call void @llvm.trap()".to_string());
+                self.line("unreachable".to_string());
+            },
+        }
+    }
+
+    fn call_function(&mut self, entry: usize) {
+        self.sync_out();
+        self.line(format!("call void @{}(ptr %cpu)", self.context.name(entry)));
+        self.sync_in();
+    }
+    fn call_unresolved(&mut self, address: u16) {
+        self.sync_out();
+        self.line(format!("call void @nes_unresolved(ptr %cpu, i16 {})", address));
+        self.sync_in();
+    }
+    /// Writes the state back and makes a call the function returns after.
+    fn tail(&mut self, call: String) {
+        self.sync_out();
+        self.line(call);
+        self.line("ret void".to_string());
+    }
+
+    /// The label to branch to for a jump, adding a trampoline if it leaves the function.
+    fn jump_label(&mut self, block: &Block, target: Target) -> String {
+        match self.context.jump(self.function, target) {
+            Jump::Local(block) => format!("b{:x}", block),
+            Jump::Function(entry) => self.trampoline(block, |emitter| {
+                emitter.tail(format!("call void @{}(ptr %cpu)", emitter.context.name(entry)));
+            }),
+            Jump::Unresolved(address) => self.trampoline(block, |emitter| {
+                emitter.tail(format!("call void @nes_unresolved(ptr %cpu, i16 {})", address));
+            }),
+        }
+    }
+    fn trampoline(&mut self, block: &Block, emit: impl FnOnce(&mut Self)) -> String {
+        let label = format!("b{:x}.{}", block.id, self.trampolines.len());
+        let body = std::mem::take(&mut self.body);
+        emit(self);
+        let trampoline = std::mem::replace(&mut self.body, body);
+        self.trampolines.push((label.clone(), trampoline));
+        label
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Let(temp, value) => {
+                let value = self.expr(value);
+                self.temps.insert(temp.id, value);
+            },
+            Stmt::SetReg(reg, value) => {
+                let value = self.expr(value);
+                self.line(format!("store i8 {}, ptr %{}", value, reg_name(*reg)));
+            },
+            // Z and N keep the byte they test, where there is one.
+            Stmt::SetFlag(Flag::Z, Expr::Unary(UnaryOp::IsZero, value)) if value.ty() == Type::U8 => {
+                let value = self.expr(value);
+                self.line(format!("store i8 {}, ptr %z", value));
+            },
+            Stmt::SetFlag(Flag::N, Expr::Unary(UnaryOp::Negative, value)) if value.ty() == Type::U8 => {
+                let value = self.expr(value);
+                self.line(format!("store i8 {}, ptr %n", value));
+            },
+            Stmt::SetFlag(flag @ (Flag::Z | Flag::N), value) => {
+                let value = self.expr(value);
+                let (set, clear) = if *flag == Flag::Z { (0, 1) } else { (-128, 0) };
+                let byte = self.value(format!("select i1 {}, i8 {}, i8 {}", value, set, clear));
+                self.line(format!("store i8 {}, ptr %{}", byte, flag_name(*flag)));
+            },
+            Stmt::SetFlag(flag, value) => {
+                let value = self.expr(value);
+                self.line(format!("store i1 {}, ptr %{}", value, flag_name(*flag)));
+            },
+            Stmt::Store(Expr::U16(address), value) if *address < IO_START => {
+                let value = self.expr(value);
+                self.line(format!("store i8 {}, ptr {}", value, ram_pointer(*address)));
+            },
+            Stmt::Store(Expr::U16(address), value) => {
+                let value = self.expr(value);
+                self.line(format!("call void @nes_write(i16 {}, i8 {})", address, value));
+            },
+            Stmt::Store(address, value) => {
+                let address = self.expr(address);
+                let value = self.expr(value);
+                self.line(format!("call void @nes_store(i16 {}, i8 {})", address, value));
+            },
+        }
+    }
+
+    fn flag(&mut self, flag: Flag) -> String {
+        match flag {
+            Flag::Z => {
+                let byte = self.value("load i8, ptr %z".to_string());
+                self.value(format!("icmp eq i8 {}, 0", byte))
+            },
+            Flag::N => {
+                let byte = self.value("load i8, ptr %n".to_string());
+                self.value(format!("icmp slt i8 {}, 0", byte))
+            },
+            flag => self.value(format!("load i1, ptr %{}", flag_name(flag))),
+        }
+    }
+
+    /// Emits an expression's instructions, returning a constant or the value holding it.
+    fn expr(&mut self, value: &Expr) -> String {
+        match value {
+            Expr::Bool(value) => value.to_string(),
+            Expr::U8(value) => value.to_string(),
+            Expr::U16(value) => value.to_string(),
+            Expr::Reg(reg) => self.value(format!("load i8, ptr %{}", reg_name(*reg))),
+            Expr::Flag(flag) => self.flag(*flag),
+            Expr::Temp(temp) => self.temps[&temp.id].clone(),
+            Expr::Load(address) => match **address {
+                Expr::U16(address) if address < IO_START => self.value(format!("load i8, ptr {}", ram_pointer(address))),
+                Expr::U16(address) => self.value(format!("call i8 @nes_read(i16 {})", address)),
+                _ => {
+                    let address = self.expr(address);
+                    self.value(format!("call i8 @nes_load(i16 {})", address))
+                },
+            },
+            Expr::Cast(ty, inner) => {
+                let from = inner.ty();
+                let operand = self.expr(inner);
+                match (from, ty) {
+                    (_, Type::Bool) => self.value(format!("icmp ne {} {}, 0", type_name(from), operand)),
+                    (Type::U16, Type::U8) => self.value(format!("trunc i16 {} to i8", operand)),
+                    (from, ty) => self.value(format!("zext {} {} to {}", type_name(from), operand, type_name(*ty))),
+                }
+            },
+            Expr::Unary(op, inner) => {
+                let ty = type_name(inner.ty());
+                let operand = self.expr(inner);
+                match op {
+                    UnaryOp::Not => self.value(format!("xor {} {}, -1", ty, operand)),
+                    UnaryOp::Negative => self.value(format!("icmp slt {} {}, 0", ty, operand)),
+                    UnaryOp::IsZero => self.value(format!("icmp eq {} {}, 0", ty, operand)),
+                }
+            },
+            Expr::Binary(op, lhs, rhs) => {
+                let ty = type_name(lhs.ty());
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                let instruction = match op {
+                    BinaryOp::Add => "add",
+                    BinaryOp::Sub => "sub",
+                    BinaryOp::And => "and",
+                    BinaryOp::Or => "or",
+                    BinaryOp::Xor => "xor",
+                    BinaryOp::Shl => "shl",
+                    BinaryOp::Shr => "lshr",
+                    BinaryOp::Ge => "icmp uge",
+                };
+                self.value(format!("{} {} {}, {}", instruction, ty, lhs, rhs))
+            },
+        }
+    }
+}
+
+/// The type of a field's alloca: flags other than Z and N are kept as `i1`.
+fn alloca_type(field: &str) -> &'static str {
+    match field {
+        "c" | "i" | "d" | "v" => "i1",
+        _ => "i8",
+    }
+}
+fn type_name(ty: Type) -> &'static str {
+    match ty {
+        Type::Bool => "i1",
+        Type::U8 => "i8",
+        Type::U16 => "i16",
+    }
+}
+fn reg_name(reg: Reg) -> &'static str {
+    match reg {
+        Reg::A => "a",
+        Reg::X => "x",
+        Reg::Y => "y",
+        Reg::SP => "sp",
+    }
+}
+fn flag_name(flag: Flag) -> &'static str {
+    match flag {
+        Flag::C => "c",
+        Flag::Z => "z",
+        Flag::I => "i",
+        Flag::D => "d",
+        Flag::V => "v",
+        Flag::N => "n",
+    }
+}
+/// A constant pointer to the byte of RAM an address below `IO_START` mirrors.
+fn ram_pointer(address: u16) -> String {
+    format!("getelementptr inbounds ([{} x i8], ptr @nes_ram, i64 0, i64 {})", RAM_SIZE, address % RAM_SIZE)
+}
@@ -0,0 +1,239 @@
+use std::fmt::Write;
+
+use crate::ir::{BinaryOp, Block, Expr, Flag, Function, Program, Reg, Stmt, Target, Terminator, Type, UnaryOp};
+use crate::parser::disassembler::Vector;
+use crate::symbols::SymbolTable;
+
+use super::{is_temp, BrkTarget, Context, Jump};
+
+
+
+/// Rust's strict and reserved keywords, which can't name functions.
+const KEYWORDS: [&str; 51] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn",
+    "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self",
+    "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while", "abstract", "become",
+    "box", "do", "final", "macro", "override", "priv", "typeof", "unsized", "virtual", "yield", "try",
+];
+/// Names of the locals in every generated function, which would shadow a function of the same name.
+const LOCALS: [&str; 4] = ["cpu", "bus", "block", "address"];
+
+/// Lints the generated code would otherwise trip, since it is emitted block by block without cleanup.
+const ALLOWED_LINTS: &str = "#![allow(non_snake_case, unused_variables, unused_mut, unused_parens, unreachable_code, unused_comparisons, clippy::all)]";
+/// The runtime crate in the source tree nespile was built from, which isn't published.
+pub const DEFAULT_RUNTIME_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../nespile_runtime");
+
+
+
+/// A crate with one function per recovered function, built against the `nespile_runtime` crate.
+#[derive(Debug, Clone)]
+pub struct RustCrate {
+    /// `Cargo.toml`.
+    pub manifest: String,
+    /// `src/lib.rs`, with an entry point for each interrupt vector.
+    pub lib: String,
+    /// `src/functions.rs`, with the recovered functions.
+    pub functions: String,
+}
+
+/// Generates a crate named `name` depending on the runtime crate at `runtime_path`, or else on
+/// the one at [`DEFAULT_RUNTIME_PATH`].
+pub fn generate(program: &Program, symbols: &SymbolTable, name: &str, runtime_path: Option<&str>) -> RustCrate {
+    let context = Context::new(program, symbols, |name| {
+        KEYWORDS.contains(&name) || LOCALS.contains(&name) || is_temp(name)
+    });
+    RustCrate {
+        manifest: manifest(name, runtime_path),
+        lib: lib(&context),
+        functions: functions(&context),
+    }
+}
+
+fn manifest(name: &str, runtime_path: Option<&str>) -> String {
+    let mut manifest = String::new();
+    writeln!(manifest, "[package]").unwrap();
+    writeln!(manifest, "name = \"{}\"", name).unwrap();
+    writeln!(manifest, "version = \"0.1.0\"").unwrap();
+    writeln!(manifest, "edition = \"2021\"").unwrap();
+    writeln!(manifest).unwrap();
+    writeln!(manifest, "[dependencies]").unwrap();
+    let path = runtime_path.unwrap_or(DEFAULT_RUNTIME_PATH);
+    writeln!(manifest, "nespile_runtime = {{ path = \"{}\" }}", path.replace('\\', "/")).unwrap();
+    manifest
+}
+
+/// Entry points which do what the CPU does on reset or an interrupt, then run its handler.
+fn lib(context: &Context) -> String {
+    let mut lib = String::new();
+    writeln!(lib, "//! Transpiled by nespile.").unwrap();
+    writeln!(lib).unwrap();
+    writeln!(lib, "pub mod functions;").unwrap();
+    writeln!(lib).unwrap();
+    writeln!(lib, "pub use nespile_runtime::{{Bus, Cpu}};").unwrap();
+    for vector in [Vector::Reset, Vector::NMI, Vector::IRQ] {
+        let Some(entry) = context.vector_function(vector) else {
+            continue;
+        };
+        let (name, setup) = match vector {
+            Vector::Reset => ("reset", "cpu.reset();"),
+            Vector::NMI => ("nmi", "cpu.interrupt(bus, 0);"),
+            Vector::IRQ => ("irq", "cpu.interrupt(bus, 0);"),
+        };
+        writeln!(lib).unwrap();
+        writeln!(lib, "pub fn {}<B: Bus>(cpu: &mut Cpu, bus: &mut B) {{", name).unwrap();
+        writeln!(lib, "    {}", setup).unwrap();
+        writeln!(lib, "    functions::{}(cpu, bus);", context.name(entry)).unwrap();
+        writeln!(lib, "}}").unwrap();
+    }
+    lib
+}
+
+fn functions(context: &Context) -> String {
+    let mut source = String::new();
+    writeln!(source, "{}", ALLOWED_LINTS).unwrap();
+    writeln!(source).unwrap();
+    writeln!(source, "use nespile_runtime::{{Bus, Cpu}};").unwrap();
+    for (name, function) in context.functions() {
+        writeln!(source).unwrap();
+        write_function(&mut source, context, name, function);
+    }
+    source
+}
+
+/// A function as a loop dispatching on the current block.
+fn write_function(source: &mut String, context: &Context, name: &str, function: &Function) {
+    let entry = function.entry_block();
+    writeln!(source, "/// ${:04X}, bank {}", entry.address, entry.bank).unwrap();
+    writeln!(source, "pub fn {}<B: Bus>(cpu: &mut Cpu, bus: &mut B) {{", name).unwrap();
+    writeln!(source, "    let mut block = {:#x};", function.entry).unwrap();
+    writeln!(source, "    loop {{").unwrap();
+    writeln!(source, "        match block {{").unwrap();
+    for block in function.blocks.values() {
+        writeln!(source, "            // ${:04X}", block.address).unwrap();
+        writeln!(source, "            {:#x} => {{", block.id).unwrap();
+        write_block(source, context, function, block);
+        writeln!(source, "            }},").unwrap();
+    }
+    writeln!(source, "            _ => unreachable!(),").unwrap();
+    writeln!(source, "        }}").unwrap();
+    writeln!(source, "    }}").unwrap();
+    writeln!(source, "}}").unwrap();
+}
+
+fn write_block(source: &mut String, context: &Context, function: &Function, block: &Block) {
+    const INDENT: &str = "                ";
+    for stmt in &block.stmts {
+        writeln!(source, "{}{}", INDENT, stmt_source(stmt)).unwrap();
+    }
+    let jump = |target| jump_source(context, context.jump(function, target));
+    let terminator = match &block.terminator {
+        Terminator::Goto(target) => jump(*target),
+        Terminator::Branch { condition, taken, not_taken } => format!(
+            "if {} {{ {} }} else {{ {} }}",
+            expr(condition), jump(*taken), jump(*not_taken),
+        ),
+        Terminator::Call { callee, return_to } => {
+            let call = match context.call(*callee) {
+                Jump::Function(callee) => format!("{}(cpu, bus);", context.name(callee)),
+                Jump::Local(_) => unreachable!("Calls always leave the function"),
+                Jump::Unresolved(address) => format!("bus.unresolved(cpu, {:#06x});", address),
+            };
+            format!("{} {}", call, jump(*return_to))
+        },
+        Terminator::Return(_) | Terminator::ReturnFromInterrupt(_) => "return;".to_string(),
+        Terminator::JumpIndirect { address, targets } => {
+            let mut arms: Vec<String> = context.indirect_targets(targets).into_iter()
+                .map(|(address, target)| format!("{:#06x} => {{ {} }}", address, jump(Target::Block(target))))
+                .collect();
+            arms.push("address => { bus.unresolved(cpu, address); return; }".to_string());
+            format!("match {} {{ {} }}", expr(address), arms.join(" "))
+        },
+        Terminator::Interrupt { vector } => match context.brk_target(*vector) {
+            BrkTarget::Function(irq) => format!("{}(cpu, bus); return;", context.name(irq)),
+            BrkTarget::Vector(vector) => format!(
+                "let address = u16::from_le_bytes([bus.read({:#06x}), bus.read({:#06x})]); bus.unresolved(cpu, address); return;",
+                vector, vector.wrapping_add(1),
+            ),
+        },
+        Terminator::Halt => format!("panic!(\"STP in block at ${:04X}\");", block.address),
+    };
+    writeln!(source, "{}{}", INDENT, terminator).unwrap();
+}
+
+fn jump_source(context: &Context, jump: Jump) -> String {
+    match jump {
+        Jump::Local(block) => format!("block = {:#x};", block),
+        Jump::Function(entry) => format!("{}(cpu, bus); return;", context.name(entry)),
+        Jump::Unresolved(address) => format!("bus.unresolved(cpu, {:#06x}); return;", address),
+    }
+}
+
+fn stmt_source(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Let(temp, value) => format!("let t{}: {} = {};", temp.id, type_name(temp.ty), expr(value)),
+        Stmt::SetReg(reg, value) => format!("{} = {};", reg_name(*reg), expr(value)),
+        Stmt::SetFlag(flag, value) => format!("{} = {};", flag_name(*flag), expr(value)),
+        Stmt::Store(address, value) => format!("bus.write({}, {});", expr(address), expr(value)),
+    }
+}
+
+fn type_name(ty: Type) -> &'static str {
+    match ty {
+        Type::Bool => "bool",
+        Type::U8 => "u8",
+        Type::U16 => "u16",
+    }
+}
+fn reg_name(reg: Reg) -> &'static str {
+    match reg {
+        Reg::A => "cpu.a",
+        Reg::X => "cpu.x",
+        Reg::Y => "cpu.y",
+        Reg::SP => "cpu.sp",
+    }
+}
+fn flag_name(flag: Flag) -> &'static str {
+    match flag {
+        Flag::C => "cpu.c",
+        Flag::Z => "cpu.z",
+        Flag::I => "cpu.i",
+        Flag::D => "cpu.d",
+        Flag::V => "cpu.v",
+        Flag::N => "cpu.n",
+    }
+}
+
+/// An expression as Rust, parenthesized wherever precedence could matter.
+fn expr(value: &Expr) -> String {
+    match value {
+        Expr::Bool(value) => value.to_string(),
+        Expr::U8(value) => format!("{:#04x}u8", value),
+        Expr::U16(value) => format!("{:#06x}u16", value),
+        Expr::Reg(reg) => reg_name(*reg).to_string(),
+        Expr::Flag(flag) => flag_name(*flag).to_string(),
+        Expr::Temp(temp) => format!("t{}", temp.id),
+        Expr::Load(address) => format!("bus.read({})", expr(address)),
+        Expr::Cast(ty, inner) => match (inner.ty(), ty) {
+            (_, Type::Bool) => format!("({} != 0)", expr(inner)),
+            (Type::U16, Type::U8) => format!("({} as u8)", expr(inner)),
+            (_, ty) => format!("{}::from({})", type_name(*ty), expr(inner)),
+        },
+        Expr::Unary(UnaryOp::Not, inner) => format!("(!{})", expr(inner)),
+        Expr::Unary(UnaryOp::Negative, inner) => format!("({} & 0x80 != 0)", expr(inner)),
+        Expr::Unary(UnaryOp::IsZero, inner) => format!("({} == 0)", expr(inner)),
+        Expr::Binary(BinaryOp::Add, lhs, rhs) => format!("{}.wrapping_add({})", expr(lhs), expr(rhs)),
+        Expr::Binary(BinaryOp::Sub, lhs, rhs) => format!("{}.wrapping_sub({})", expr(lhs), expr(rhs)),
+        Expr::Binary(op, lhs, rhs) => {
+            let op = match op {
+                BinaryOp::And => "&",
+                BinaryOp::Or => "|",
+                BinaryOp::Xor => "^",
+                BinaryOp::Shl => "<<",
+                BinaryOp::Shr => ">>",
+                BinaryOp::Ge => ">=",
+                BinaryOp::Add | BinaryOp::Sub => unreachable!(),
+            };
+            format!("({} {} {})", expr(lhs), op, expr(rhs))
+        },
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::ir::{BinaryOp, Block, Expr, Flag, Function, Program, Reg, Stmt, Target, Terminator, Type, UnaryOp};
+use crate::parser::disassembler::Vector;
+use crate::symbols::SymbolTable;
+
+use super::{BrkTarget, Context, Jump, INTERRUPT_HELPER, IO_START, RAM_SIZE};
+
+
+
+/// Imports, the CPU state and RAM, and the helpers the functions use. Registers and flags are exported
+/// globals, and RAM is the first 2KB of the exported memory.
+fn prelude() -> String {
+    format!(
+        r#"(module
+  ;; Memory-mapped I/O from $2000 up, and code at an address that was never transpiled, provided by the host.
+  (import "nes" "read" (func $nes_read (param i32) (result i32)))
+  (import "nes" "write" (func $nes_write (param i32 i32)))
+  (import "nes" "unresolved" (func $nes_unresolved (param i32)))
+
+  (memory (export "memory") 1)
+
+  (global $a (export "a") (mut i32) (i32.const 0))
+  (global $x (export "x") (mut i32) (i32.const 0))
+  (global $y (export "y") (mut i32) (i32.const 0))
+  (global $sp (export "sp") (mut i32) (i32.const 0xfd))
+  (global $c (export "c") (mut i32) (i32.const 0))
+  (global $z (export "z") (mut i32) (i32.const 0))
+  (global $i (export "i") (mut i32) (i32.const 1))
+  (global $d (export "d") (mut i32) (i32.const 0))
+  (global $v (export "v") (mut i32) (i32.const 0))
+  (global $n (export "n") (mut i32) (i32.const 0))
+
+  (func $nes_load (param $address i32) (result i32)
+    (if (result i32) (i32.lt_u (local.get $address) (i32.const {io_start:#06x}))
+      (then (i32.load8_u (i32.and (local.get $address) (i32.const {ram_mask:#x}))))
+      (else (call $nes_read (local.get $address)))))
+
+  (func $nes_store (param $address i32) (param $value i32)
+    (if (i32.lt_u (local.get $address) (i32.const {io_start:#06x}))
+      (then (i32.store8 (i32.and (local.get $address) (i32.const {ram_mask:#x})) (local.get $value)))
+      (else (call $nes_write (local.get $address) (local.get $value)))))
+
+  (func $nes_push (param $value i32)
+    (call $nes_store (i32.or (i32.const 0x100) (global.get $sp)) (local.get $value))
+    (global.set $sp (i32.and (i32.sub (global.get $sp) (i32.const 1)) (i32.const 0xff))))
+
+  ;; {interrupt_helper}
+  (func $nes_interrupt
+    (call $nes_push (i32.const 0))
+    (call $nes_push (i32.const 0))
+    (call $nes_push
+      (i32.or (i32.const 0x20)
+        (i32.or (global.get $c)
+          (i32.or (i32.shl (global.get $z) (i32.const 1))
+            (i32.or (i32.shl (global.get $i) (i32.const 2))
+              (i32.or (i32.shl (global.get $d) (i32.const 3))
+                (i32.or (i32.shl (global.get $v) (i32.const 6)) (i32.shl (global.get $n) (i32.const 7)))))))))
+    (global.set $i (i32.const 1)))
+"#,
+        io_start = IO_START, ram_mask = RAM_SIZE - 1, interrupt_helper = INTERRUPT_HELPER,
+    )
+}
+
+
+
+/// Generates a module exporting `reset`, `nmi` and `irq` for the vectors with handlers, with every value kept in an
+/// `i32`: bytes and words masked to their width and flags as 0 or 1.
+pub fn generate(program: &Program, symbols: &SymbolTable) -> String {
+    let context = Context::new(program, symbols, |name| name.starts_with("nes_"));
+    let mut module = prelude();
+    for vector in [Vector::Reset, Vector::NMI, Vector::IRQ] {
+        let Some(handler) = context.vector_function(vector) else {
+            continue;
+        };
+        let (name, setup) = match vector {
+            Vector::Reset => ("reset", "(global.set $sp (i32.and (i32.sub (global.get $sp) (i32.const 3)) (i32.const 0xff)))\n    (global.set $i (i32.const 1))"),
+            Vector::NMI => ("nmi", "(call $nes_interrupt)"),
+            Vector::IRQ => ("irq", "(call $nes_interrupt)"),
+        };
+        writeln!(module).unwrap();
+        writeln!(module, "  (func $nes_{} (export \"{}\")", name, name).unwrap();
+        writeln!(module, "    {}", setup).unwrap();
+        writeln!(module, "    (call ${}))", context.name(handler)).unwrap();
+    }
+    for (name, function) in context.functions() {
+        writeln!(module).unwrap();
+        write_function(&mut module, &context, name, function);
+    }
+    writeln!(module, ")").unwrap();
+    module
+}
+
+
+
+/// A function as a loop around a `br_table` into nested blocks, one for each basic block.
+fn write_function(source: &mut String, context: &Context, name: &str, function: &Function) {
+    let indices: HashMap<usize, usize> = function.blocks.keys().enumerate().map(|(index, id)| (*id, index)).collect();
+    let entry = function.entry_block();
+    writeln!(source, "  ;; ${:04X}, bank {}", entry.address, entry.bank).unwrap();
+    writeln!(source, "  (func ${}", name).unwrap();
+    write!(source, "    (local $block i32) (local $address i32)").unwrap();
+    for temp in function.temps() {
+        write!(source, " (local $t{} i32)", temp.id).unwrap();
+    }
+    writeln!(source).unwrap();
+    writeln!(source, "    (local.set $block (i32.const {}))", indices[&function.entry]).unwrap();
+    writeln!(source, "    (loop $dispatch").unwrap();
+    for block in function.blocks.values().rev() {
+        writeln!(source, "    (block $b{:x}", block.id).unwrap();
+    }
+    let labels: Vec<String> = function.blocks.keys().map(|id| format!("$b{:x}", id)).collect();
+    writeln!(source, "      (br_table {} (local.get $block)))", labels.join(" ")).unwrap();
+    for (index, block) in function.blocks.values().enumerate() {
+        writeln!(source, "      ;; ${:04X}", block.address).unwrap();
+        write_block(source, context, function, &indices, block);
+        // Every block ends by branching, so the last has nothing to fall out of but the loop.
+        if index + 1 < function.blocks.len() {
+            writeln!(source, "    )").unwrap();
+        }
+    }
+    writeln!(source, "    ))").unwrap();
+}
+
+fn write_block(source: &mut String, context: &Context, function: &Function, indices: &HashMap<usize, usize>, block: &Block) {
+    const INDENT: &str = "      ";
+    for stmt in &block.stmts {
+        writeln!(source, "{}{}", INDENT, stmt_source(stmt)).unwrap();
+    }
+    let jump = |target| match context.jump(function, target) {
+        Jump::Local(block) => format!("(local.set $block (i32.const {})) (br $dispatch)", indices[&block]),
+        Jump::Function(entry) => format!("(call ${}) (return)", context.name(entry)),
+        Jump::Unresolved(address) => format!("(call $nes_unresolved (i32.const {:#06x})) (return)", address),
+    };
+    let terminator = match &block.terminator {
+        Terminator::Goto(target) => jump(*target),
+        Terminator::Branch { condition, taken, not_taken } => format!(
+            "(if {} (then {}) (else {}))",
+            expr(condition), jump(*taken), jump(*not_taken),
+        ),
+        Terminator::Call { callee, return_to } => {
+            let call = match context.call(*callee) {
+                Jump::Function(callee) => format!("(call ${})", context.name(callee)),
+                Jump::Local(_) => unreachable!("Calls always leave the function"),
+                Jump::Unresolved(address) => format!("(call $nes_unresolved (i32.const {:#06x}))", address),
+            };
+            format!("{} {}", call, jump(*return_to))
+        },
+        Terminator::Return(_) | Terminator::ReturnFromInterrupt(_) => "(return)".to_string(),
+        Terminator::JumpIndirect { address, targets } => {
+            let mut cases = vec![format!("(local.set $address {})", expr(address))];
+            cases.extend(context.indirect_targets(targets).into_iter().map(|(address, target)| format!(
+                "(if (i32.eq (local.get $address) (i32.const {:#06x})) (then {}))",
+                address, jump(Target::Block(target)),
+            )));
+            cases.push("(call $nes_unresolved (local.get $address)) (return)".to_string());
+            cases.join(&format!("\n{}", INDENT))
+        },
+        Terminator::Interrupt { vector } => match context.brk_target(*vector) {
+            BrkTarget::Function(irq) => format!("(call ${}) (return)", context.name(irq)),
+            BrkTarget::Vector(vector) => format!(
+                "(call $nes_unresolved (i32.or (call $nes_load (i32.const {:#06x})) (i32.shl (call $nes_load (i32.const {:#06x})) (i32.const 8)))) (return)",
+                vector, vector.wrapping_add(1),
+            ),
+        },
+        Terminator::Halt => "(unreachable)".to_string(),
+    };
+    writeln!(source, "{}{}", INDENT, terminator).unwrap();
+}
+
+fn stmt_source(stmt: &Stmt) -> String {
+    match stmt {
+        Stmt::Let(temp, value) => format!("(local.set $t{} {})", temp.id, expr(value)),
+        Stmt::SetReg(reg, value) => format!("(global.set ${} {})", reg_name(*reg), expr(value)),
+        Stmt::SetFlag(flag, value) => format!("(global.set ${} {})", flag_name(*flag), expr(value)),
+        Stmt::Store(Expr::U16(address), value) if *address < IO_START =>
+            format!("(i32.store8 (i32.const {:#05x}) {})", address % RAM_SIZE, expr(value)),
+        Stmt::Store(Expr::U16(address), value) => format!("(call $nes_write (i32.const {:#06x}) {})", address, expr(value)),
+        Stmt::Store(address, value) => format!("(call $nes_store {} {})", expr(address), expr(value)),
+    }
+}
+
+fn reg_name(reg: Reg) -> &'static str {
+    match reg {
+        Reg::A => "a",
+        Reg::X => "x",
+        Reg::Y => "y",
+        Reg::SP => "sp",
+    }
+}
+fn flag_name(flag: Flag) -> &'static str {
+    match flag {
+        Flag::C => "c",
+        Flag::Z => "z",
+        Flag::I => "i",
+        Flag::D => "d",
+        Flag::V => "v",
+        Flag::N => "n",
+    }
+}
+
+/// Masks an `i32` holding a value of type `ty` back to its width.
+fn wrap(ty: Type, value: String) -> String {
+    match ty {
+        Type::U8 => format!("(i32.and {} (i32.const 0xff))", value),
+        Type::U16 => format!("(i32.and {} (i32.const 0xffff))", value),
+        Type::Bool => value,
+    }
+}
+
+/// An expression as a folded instruction leaving an `i32` on the stack.
+fn expr(value: &Expr) -> String {
+    match value {
+        Expr::Bool(value) => format!("(i32.const {})", *value as u8),
+        Expr::U8(value) => format!("(i32.const {:#04x})", value),
+        Expr::U16(value) => format!("(i32.const {:#06x})", value),
+        Expr::Reg(reg) => format!("(global.get ${})", reg_name(*reg)),
+        Expr::Flag(flag) => format!("(global.get ${})", flag_name(*flag)),
+        Expr::Temp(temp) => format!("(local.get $t{})", temp.id),
+        Expr::Load(address) => match **address {
+            Expr::U16(address) if address < IO_START => format!("(i32.load8_u (i32.const {:#05x}))", address % RAM_SIZE),
+            Expr::U16(address) => format!("(call $nes_read (i32.const {:#06x}))", address),
+            _ => format!("(call $nes_load {})", expr(address)),
+        },
+        Expr::Cast(Type::Bool, inner) => format!("(i32.ne {} (i32.const 0))", expr(inner)),
+        Expr::Cast(Type::U8, inner) if inner.ty() == Type::U16 => wrap(Type::U8, expr(inner)),
+        // Widening a byte or a flag leaves its `i32` as it is.
+        Expr::Cast(_, inner) => expr(inner),
+        Expr::Unary(UnaryOp::Not, inner) => match inner.ty() {
+            Type::Bool => format!("(i32.eqz {})", expr(inner)),
+            Type::U8 => format!("(i32.xor {} (i32.const 0xff))", expr(inner)),
+            Type::U16 => format!("(i32.xor {} (i32.const 0xffff))", expr(inner)),
+        },
+        Expr::Unary(UnaryOp::Negative, inner) => format!("(i32.shr_u {} (i32.const 7))", expr(inner)),
+        Expr::Unary(UnaryOp::IsZero, inner) => format!("(i32.eqz {})", expr(inner)),
+        Expr::Binary(op, lhs, rhs) => {
+            let ty = lhs.ty();
+            let (lhs, rhs) = (expr(lhs), expr(rhs));
+            match op {
+                BinaryOp::Add => wrap(ty, format!("(i32.add {} {})", lhs, rhs)),
+                BinaryOp::Sub => wrap(ty, format!("(i32.sub {} {})", lhs, rhs)),
+                BinaryOp::Shl => wrap(ty, format!("(i32.shl {} {})", lhs, rhs)),
+                BinaryOp::And => format!("(i32.and {} {})", lhs, rhs),
+                BinaryOp::Or => format!("(i32.or {} {})", lhs, rhs),
+                BinaryOp::Xor => format!("(i32.xor {} {})", lhs, rhs),
+                BinaryOp::Shr => format!("(i32.shr_u {} {})", lhs, rhs),
+                BinaryOp::Ge => format!("(i32.ge_u {} {})", lhs, rhs),
+            }
+        },
+    }
+}
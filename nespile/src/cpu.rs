@@ -0,0 +1,8 @@
+pub mod cycles;
+pub mod interpreter;
+pub mod semantics;
+
+pub use cycles::{cycle_table, Cycles};
+pub use interpreter::Interpreter;
+pub use nespile_runtime::Bus;
+pub use semantics::{ControlFlow, Flags, MemoryAccess, Registers, Semantics, UNSTABLE_MAGIC};
@@ -0,0 +1,85 @@
+use std::io::Cursor;
+
+use binrw::{BinRead, Endian};
+
+use crate::parser::address_mode::AddressMode;
+use crate::cpu::semantics::{ControlFlow, MemoryAccess};
+use crate::parser::opcodes::Opcode;
+
+
+
+/// Clock cycles an instruction takes, per https://www.nesdev.org/wiki/6502_cycle_times
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Cycles {
+    /// Cycles when no page is crossed and no branch is taken.
+    pub base: u8,
+    /// Extra cycles when indexing crosses a page, or a taken branch lands on another page.
+    pub page_cross: u8,
+    /// Extra cycles when a branch is taken.
+    pub branch_taken: u8,
+}
+impl Cycles {
+    /// Total cycles, given whether a page was crossed and (for branches) whether the branch was taken.
+    pub fn total(&self, page_crossed: bool, branch_taken: bool) -> u8 {
+        let is_branch = self.branch_taken > 0;
+        match (is_branch, branch_taken) {
+            (true, false) => self.base,
+            (true, true) => self.base + self.branch_taken + if page_crossed { self.page_cross } else { 0 },
+            (false, _) => self.base + if page_crossed { self.page_cross } else { 0 },
+        }
+    }
+}
+
+impl Opcode {
+    pub fn cycles(&self) -> Cycles {
+        let fixed = |base| Cycles { base, ..Cycles::default() };
+        let crossing = |base| Cycles { base, page_cross: 1, ..Cycles::default() };
+        let mode = self.argument();
+
+        let semantics = self.semantics();
+
+        match (semantics.memory, mode) {
+            (_, Some(AddressMode::Relative(_))) => Cycles { base: 2, page_cross: 1, branch_taken: 1 },
+            (_, None | Some(AddressMode::Implied | AddressMode::Accumulator)) => match self {
+                Opcode::BRK => fixed(7),
+                Opcode::RTI | Opcode::RTS => fixed(6),
+                Opcode::PHA | Opcode::PHP => fixed(3),
+                Opcode::PLA | Opcode::PLP => fixed(4),
+                _ => fixed(2),
+            },
+            (_, Some(_)) if semantics.control_flow != ControlFlow::Next => match semantics.control_flow {
+                ControlFlow::Call => fixed(6),
+                ControlFlow::IndirectJump => fixed(5),
+                _ => fixed(3),
+            },
+            (access, Some(mode)) => {
+                let read = access == MemoryAccess::Read;
+                let rmw = access == MemoryAccess::ReadModifyWrite;
+                match mode {
+                    AddressMode::Immediate(_) => fixed(2),
+                    AddressMode::ZeroPage(_) => fixed(if rmw { 5 } else { 3 }),
+                    AddressMode::ZeroPageX(_) | AddressMode::ZeroPageY(_) => fixed(if rmw { 6 } else { 4 }),
+                    AddressMode::Absolute(_) => fixed(if rmw { 6 } else { 4 }),
+                    AddressMode::AbsoluteX(_) | AddressMode::AbsoluteY(_) => match access {
+                        MemoryAccess::Read => crossing(4),
+                        MemoryAccess::Write => fixed(5),
+                        _ => fixed(7),
+                    },
+                    AddressMode::IndirectX(_) => fixed(if rmw { 8 } else { 6 }),
+                    AddressMode::IndirectY(_) if read => crossing(5),
+                    AddressMode::IndirectY(_) => fixed(if rmw { 8 } else { 6 }),
+                    _ => fixed(2),
+                }
+            },
+        }
+    }
+}
+
+/// Timing of every opcode byte, decoded through the same table as the disassembler.
+pub fn cycle_table() -> [Cycles; 256] {
+    std::array::from_fn(|byte| {
+        Opcode::read_options(&mut Cursor::new([byte as u8, 0, 0]), Endian::Little, ())
+            .expect("Every opcode byte decodes")
+            .cycles()
+    })
+}
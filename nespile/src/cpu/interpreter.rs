@@ -0,0 +1,440 @@
+use std::io::Cursor;
+
+use binrw::{BinRead, Endian};
+
+use nespile_runtime::{Bus, Cpu};
+
+use crate::cpu::semantics::{Flags, UNSTABLE_MAGIC};
+use crate::parser::address_mode::AddressMode;
+use crate::parser::disassembler::{IRQ_VECTOR, NMI_VECTOR, RESET_VECTOR};
+use crate::parser::opcodes::Opcode;
+
+
+
+/// Bits 4 and 5 of P, which only exist in copies of it pushed onto the stack.
+const BREAK_BITS: u8 = 0x30;
+/// Cycles the CPU takes to enter an interrupt handler or restart on reset.
+const INTERRUPT_CYCLES: u8 = 7;
+
+/// A reference 2A03 that executes one instruction at a time, counting cycles per
+/// https://www.nesdev.org/wiki/6502_cycle_times. Like the 2A03, it ignores the decimal flag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interpreter {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    /// Status flags, with bits as in `Flags`.
+    pub p: u8,
+    pub pc: u16,
+    /// Cycles executed since power on.
+    pub cycles: u64,
+    /// Whether an `STP` has jammed the CPU, which only a reset recovers from.
+    pub halted: bool,
+    /// Whether the last effective address crossed a page from its base.
+    page_crossed: bool,
+}
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+/// Takes over from transpiled code, e.g. to run code it never reached; PC is left for the caller to set.
+impl From<Cpu> for Interpreter {
+    fn from(cpu: Cpu) -> Self {
+        Interpreter { a: cpu.a, x: cpu.x, y: cpu.y, sp: cpu.sp, p: cpu.p(), ..Interpreter::new() }
+    }
+}
+impl From<&Interpreter> for Cpu {
+    fn from(interpreter: &Interpreter) -> Self {
+        let mut cpu = Cpu { a: interpreter.a, x: interpreter.x, y: interpreter.y, sp: interpreter.sp, ..Cpu::default() };
+        cpu.set_p(interpreter.p);
+        cpu
+    }
+}
+impl Interpreter {
+    /// The CPU at power on, before the reset sequence runs.
+    pub fn new() -> Self {
+        Interpreter {
+            a: 0,
+            x: 0,
+            y: 0,
+            sp: 0,
+            p: Flags::I.bits() | 0x20,
+            pc: 0,
+            cycles: 0,
+            halted: false,
+            page_crossed: false,
+        }
+    }
+
+    pub fn flag(&self, flag: Flags) -> bool {
+        self.p & flag.bits() != 0
+    }
+    pub fn set_flag(&mut self, flag: Flags, value: bool) {
+        if value {
+            self.p |= flag.bits();
+        } else {
+            self.p &= !flag.bits();
+        }
+    }
+
+    /// Runs the reset sequence, which decrements SP by three without writing the stack.
+    pub fn reset<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        self.sp = self.sp.wrapping_sub(3);
+        self.set_flag(Flags::I, true);
+        self.pc = read_word(bus, RESET_VECTOR);
+        self.halted = false;
+        self.cycles += u64::from(INTERRUPT_CYCLES);
+        INTERRUPT_CYCLES
+    }
+    pub fn nmi<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        self.hardware_interrupt(bus, NMI_VECTOR)
+    }
+    /// Requests an IRQ, which does nothing while interrupts are disabled.
+    pub fn irq<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        if self.flag(Flags::I) {
+            return 0;
+        }
+        self.hardware_interrupt(bus, IRQ_VECTOR)
+    }
+
+    /// Fetches, decodes and executes the instruction at PC, returning the cycles it took.
+    pub fn step<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        if self.halted {
+            return 0;
+        }
+        let mut bytes = [bus.read(self.pc), 0, 0];
+        // Decode once to learn the size, so only the instruction's own bytes are read.
+        let size = decode(bytes).size();
+        for (offset, byte) in bytes.iter_mut().enumerate().take(size).skip(1) {
+            *byte = bus.read(self.pc.wrapping_add(offset as u16));
+        }
+        self.pc = self.pc.wrapping_add(size as u16);
+        self.execute(bus, decode(bytes))
+    }
+
+    /// Executes an instruction whose bytes PC has already moved past, returning the cycles it took.
+    pub fn execute<B: Bus>(&mut self, bus: &mut B, opcode: Opcode) -> u8 {
+        use Opcode::*;
+
+        let mode = opcode.argument().unwrap_or(AddressMode::Implied);
+        self.page_crossed = false;
+        let mut branch_taken = false;
+        match opcode {
+            LDA(_) => { self.a = self.operand(bus, mode); self.set_nz(self.a) },
+            LDX(_) => { self.x = self.operand(bus, mode); self.set_nz(self.x) },
+            LDY(_) => { self.y = self.operand(bus, mode); self.set_nz(self.y) },
+            STA(_) => { let address = self.address(bus, mode); bus.write(address, self.a) },
+            STX(_) => { let address = self.address(bus, mode); bus.write(address, self.x) },
+            STY(_) => { let address = self.address(bus, mode); bus.write(address, self.y) },
+
+            TAX => { self.x = self.a; self.set_nz(self.x) },
+            TAY => { self.y = self.a; self.set_nz(self.y) },
+            TXA => { self.a = self.x; self.set_nz(self.a) },
+            TYA => { self.a = self.y; self.set_nz(self.a) },
+            TSX => { self.x = self.sp; self.set_nz(self.x) },
+            TXS => self.sp = self.x,
+
+            INX => { self.x = self.x.wrapping_add(1); self.set_nz(self.x) },
+            INY => { self.y = self.y.wrapping_add(1); self.set_nz(self.y) },
+            DEX => { self.x = self.x.wrapping_sub(1); self.set_nz(self.x) },
+            DEY => { self.y = self.y.wrapping_sub(1); self.set_nz(self.y) },
+
+            AND(_) => { let value = self.operand(bus, mode); self.set_a(self.a & value) },
+            ORA(_) => { let value = self.operand(bus, mode); self.set_a(self.a | value) },
+            EOR(_) => { let value = self.operand(bus, mode); self.set_a(self.a ^ value) },
+            ADC(_) => { let value = self.operand(bus, mode); self.adc(value) },
+            SBC(_) => { let value = self.operand(bus, mode); self.adc(!value) },
+            CMP(_) => { let value = self.operand(bus, mode); self.compare(self.a, value) },
+            CPX(_) => { let value = self.operand(bus, mode); self.compare(self.x, value) },
+            CPY(_) => { let value = self.operand(bus, mode); self.compare(self.y, value) },
+            BIT(_) => {
+                let value = self.operand(bus, mode);
+                self.set_flag(Flags::Z, self.a & value == 0);
+                self.set_flag(Flags::V, value & 0x40 != 0);
+                self.set_flag(Flags::N, value & 0x80 != 0);
+            },
+
+            ASL(_) => { self.modify(bus, mode, Self::asl); },
+            LSR(_) => { self.modify(bus, mode, Self::lsr); },
+            ROL(_) => { self.modify(bus, mode, Self::rol); },
+            ROR(_) => { self.modify(bus, mode, Self::ror); },
+            INC(_) => { self.modify(bus, mode, |cpu, value| cpu.nz(value.wrapping_add(1))); },
+            DEC(_) => { self.modify(bus, mode, |cpu, value| cpu.nz(value.wrapping_sub(1))); },
+
+            CLC => self.set_flag(Flags::C, false),
+            SEC => self.set_flag(Flags::C, true),
+            CLI => self.set_flag(Flags::I, false),
+            SEI => self.set_flag(Flags::I, true),
+            CLD => self.set_flag(Flags::D, false),
+            SED => self.set_flag(Flags::D, true),
+            CLV => self.set_flag(Flags::V, false),
+
+            PHA => self.push(bus, self.a),
+            PHP => self.push(bus, self.p | BREAK_BITS),
+            PLA => { self.a = self.pull(bus); self.set_nz(self.a) },
+            PLP => { let status = self.pull(bus); self.set_p(status) },
+
+            BPL(_) => branch_taken = self.branch(mode, !self.flag(Flags::N)),
+            BMI(_) => branch_taken = self.branch(mode, self.flag(Flags::N)),
+            BVC(_) => branch_taken = self.branch(mode, !self.flag(Flags::V)),
+            BVS(_) => branch_taken = self.branch(mode, self.flag(Flags::V)),
+            BCC(_) => branch_taken = self.branch(mode, !self.flag(Flags::C)),
+            BCS(_) => branch_taken = self.branch(mode, self.flag(Flags::C)),
+            BNE(_) => branch_taken = self.branch(mode, !self.flag(Flags::Z)),
+            BEQ(_) => branch_taken = self.branch(mode, self.flag(Flags::Z)),
+
+            JMP(_) => self.pc = match mode {
+                AddressMode::Absolute(address) => address,
+                _ => self.address(bus, mode),
+            },
+            JSR(_) => {
+                self.push_word(bus, self.pc.wrapping_sub(1));
+                self.pc = mode.address().unwrap_or_default();
+            },
+            RTS => self.pc = self.pull_word(bus).wrapping_add(1),
+            RTI => {
+                let status = self.pull(bus);
+                self.set_p(status);
+                self.pc = self.pull_word(bus);
+            },
+            // The byte after `BRK` is skipped, so handlers can use it as a signature.
+            BRK => self.interrupt(bus, self.pc.wrapping_add(1), self.p | BREAK_BITS, IRQ_VECTOR),
+            STP => self.halted = true,
+            NOP(_) => {
+                // Unofficial NOPs with an operand still read it, which matters for I/O registers.
+                if mode.address().is_some() {
+                    self.operand(bus, mode);
+                }
+            },
+
+            SLO(_) => { let value = self.modify(bus, mode, Self::asl); self.set_a(self.a | value) },
+            RLA(_) => { let value = self.modify(bus, mode, Self::rol); self.set_a(self.a & value) },
+            SRE(_) => { let value = self.modify(bus, mode, Self::lsr); self.set_a(self.a ^ value) },
+            RRA(_) => { let value = self.modify(bus, mode, Self::ror); self.adc(value) },
+            DCP(_) => { let value = self.modify(bus, mode, |_, value| value.wrapping_sub(1)); self.compare(self.a, value) },
+            ISC(_) => { let value = self.modify(bus, mode, |_, value| value.wrapping_add(1)); self.adc(!value) },
+            SAX(_) => { let address = self.address(bus, mode); bus.write(address, self.a & self.x) },
+            LAX(_) => { self.a = self.operand(bus, mode); self.x = self.a; self.set_nz(self.x) },
+            ANC(_) => {
+                let value = self.operand(bus, mode);
+                self.set_a(self.a & value);
+                self.set_flag(Flags::C, self.flag(Flags::N));
+            },
+            ALR(_) => {
+                let value = self.operand(bus, mode);
+                self.a = self.lsr(self.a & value);
+            },
+            ARR(_) => {
+                let value = self.operand(bus, mode);
+                let result = ((self.a & value) >> 1) | (u8::from(self.flag(Flags::C)) << 7);
+                self.set_a(result);
+                self.set_flag(Flags::C, result & 0x40 != 0);
+                self.set_flag(Flags::V, (result ^ (result << 1)) & 0x40 != 0);
+            },
+            AXS(_) => {
+                let value = self.operand(bus, mode);
+                let masked = self.a & self.x;
+                self.set_flag(Flags::C, masked >= value);
+                self.x = self.nz(masked.wrapping_sub(value));
+            },
+
+            // The unstable opcodes behave as the lifter models them.
+            LAS(_) => {
+                let value = self.operand(bus, mode) & self.sp;
+                self.sp = value;
+                self.x = value;
+                self.set_a(value);
+            },
+            XAA(_) => { let value = self.operand(bus, mode); self.set_a((self.a | UNSTABLE_MAGIC) & self.x & value) },
+            LXA(_) => {
+                let value = self.operand(bus, mode);
+                self.a = (self.a | UNSTABLE_MAGIC) & value;
+                self.x = self.a;
+                self.set_nz(self.x);
+            },
+            AHX(_) => self.store_high_masked(bus, mode, self.a & self.x),
+            SHX(_) => self.store_high_masked(bus, mode, self.x),
+            SHY(_) => self.store_high_masked(bus, mode, self.y),
+            TAS(_) => {
+                self.sp = self.a & self.x;
+                self.store_high_masked(bus, mode, self.sp);
+            },
+        }
+
+        let cycles = opcode.cycles().total(self.page_crossed, branch_taken);
+        self.cycles += u64::from(cycles);
+        cycles
+    }
+
+
+
+    /// An NMI or IRQ, which pushes P with the break bit clear.
+    fn hardware_interrupt<B: Bus>(&mut self, bus: &mut B, vector: u16) -> u8 {
+        self.interrupt(bus, self.pc, (self.p & !BREAK_BITS) | 0x20, vector);
+        self.cycles += u64::from(INTERRUPT_CYCLES);
+        INTERRUPT_CYCLES
+    }
+    /// Pushes the return address and status, then jumps through `vector` with interrupts disabled.
+    fn interrupt<B: Bus>(&mut self, bus: &mut B, return_address: u16, status: u8, vector: u16) {
+        self.push_word(bus, return_address);
+        self.push(bus, status);
+        self.set_flag(Flags::I, true);
+        self.pc = read_word(bus, vector);
+    }
+
+    /// Sets P from a pulled byte; the break bits don't exist in the register.
+    fn set_p(&mut self, value: u8) {
+        self.p = (value & !BREAK_BITS) | 0x20;
+    }
+    fn set_nz(&mut self, value: u8) {
+        self.set_flag(Flags::Z, value == 0);
+        self.set_flag(Flags::N, value & 0x80 != 0);
+    }
+    /// Sets N and Z from a result and passes it through.
+    fn nz(&mut self, value: u8) -> u8 {
+        self.set_nz(value);
+        value
+    }
+    fn set_a(&mut self, value: u8) {
+        self.a = self.nz(value);
+    }
+
+    /// The effective address of a memory operand, noting whether indexing crossed a page.
+    fn address<B: Bus>(&mut self, bus: &mut B, mode: AddressMode) -> u16 {
+        let indexed = |cpu: &mut Self, base: u16, index: u8| {
+            let address = base.wrapping_add(u16::from(index));
+            cpu.page_crossed = address & 0xff00 != base & 0xff00;
+            address
+        };
+        match mode {
+            AddressMode::ZeroPage(address) => u16::from(address),
+            AddressMode::Absolute(address) => address,
+            AddressMode::ZeroPageX(address) => u16::from(address.wrapping_add(self.x)),
+            AddressMode::ZeroPageY(address) => u16::from(address.wrapping_add(self.y)),
+            AddressMode::AbsoluteX(address) => indexed(self, address, self.x),
+            AddressMode::AbsoluteY(address) => indexed(self, address, self.y),
+            AddressMode::Indirect(pointer) => {
+                // The high byte is read without carrying into the pointer's page.
+                let hi = (pointer & 0xff00) | (pointer.wrapping_add(1) & 0x00ff);
+                u16::from_le_bytes([bus.read(pointer), bus.read(hi)])
+            },
+            AddressMode::IndirectX(pointer) => self.zero_page_word(bus, pointer.wrapping_add(self.x)),
+            AddressMode::IndirectY(pointer) => {
+                let base = self.zero_page_word(bus, pointer);
+                indexed(self, base, self.y)
+            },
+            AddressMode::Accumulator | AddressMode::Implied | AddressMode::Immediate(_) | AddressMode::Relative(_) =>
+                unreachable!("{:?} has no effective address", mode),
+        }
+    }
+    fn zero_page_word<B: Bus>(&mut self, bus: &mut B, pointer: u8) -> u16 {
+        u16::from_le_bytes([bus.read(u16::from(pointer)), bus.read(u16::from(pointer.wrapping_add(1)))])
+    }
+
+    fn operand<B: Bus>(&mut self, bus: &mut B, mode: AddressMode) -> u8 {
+        match mode {
+            AddressMode::Immediate(value) => value,
+            AddressMode::Accumulator => self.a,
+            _ => {
+                let address = self.address(bus, mode);
+                bus.read(address)
+            },
+        }
+    }
+    /// Applies `f` to A or the byte in memory, as read-modify-write instructions do, and gives the new value.
+    fn modify<B: Bus>(&mut self, bus: &mut B, mode: AddressMode, f: impl FnOnce(&mut Self, u8) -> u8) -> u8 {
+        if mode == AddressMode::Accumulator {
+            self.a = f(self, self.a);
+            return self.a;
+        }
+        let address = self.address(bus, mode);
+        let value = bus.read(address);
+        let result = f(self, value);
+        bus.write(address, result);
+        result
+    }
+
+    fn asl(&mut self, value: u8) -> u8 {
+        self.set_flag(Flags::C, value & 0x80 != 0);
+        self.nz(value << 1)
+    }
+    fn lsr(&mut self, value: u8) -> u8 {
+        self.set_flag(Flags::C, value & 0x01 != 0);
+        self.nz(value >> 1)
+    }
+    fn rol(&mut self, value: u8) -> u8 {
+        let result = (value << 1) | u8::from(self.flag(Flags::C));
+        self.set_flag(Flags::C, value & 0x80 != 0);
+        self.nz(result)
+    }
+    fn ror(&mut self, value: u8) -> u8 {
+        let result = (value >> 1) | (u8::from(self.flag(Flags::C)) << 7);
+        self.set_flag(Flags::C, value & 0x01 != 0);
+        self.nz(result)
+    }
+
+    /// Binary add with carry; the 2A03 has no decimal mode.
+    fn adc(&mut self, value: u8) {
+        let sum = u16::from(self.a) + u16::from(value) + u16::from(self.flag(Flags::C));
+        let result = sum as u8;
+        self.set_flag(Flags::V, (self.a ^ result) & (value ^ result) & 0x80 != 0);
+        self.set_flag(Flags::C, sum > 0xff);
+        self.set_a(result);
+    }
+    fn compare(&mut self, register: u8, value: u8) {
+        self.set_nz(register.wrapping_sub(value));
+        self.set_flag(Flags::C, register >= value);
+    }
+
+    /// Takes a relative branch if `condition` holds, noting whether it lands on another page.
+    fn branch(&mut self, mode: AddressMode, condition: bool) -> bool {
+        if condition {
+            let offset = mode.relative_offset().unwrap_or_default();
+            let target = self.pc.wrapping_add_signed(offset as i16);
+            self.page_crossed = target & 0xff00 != self.pc & 0xff00;
+            self.pc = target;
+        }
+        condition
+    }
+
+    /// The store of `AHX`, `SHX`, `SHY` and `TAS`, which only keeps the bits of `value` set in the base's high byte plus one.
+    fn store_high_masked<B: Bus>(&mut self, bus: &mut B, mode: AddressMode, value: u8) {
+        let (base, index) = match mode {
+            AddressMode::AbsoluteX(base) => (base, self.x),
+            AddressMode::AbsoluteY(base) => (base, self.y),
+            AddressMode::IndirectY(pointer) => (self.zero_page_word(bus, pointer), self.y),
+            _ => unreachable!("{:?} is not indexed", mode),
+        };
+        let address = base.wrapping_add(u16::from(index));
+        bus.write(address, value & ((base >> 8) as u8).wrapping_add(1));
+    }
+
+    fn push<B: Bus>(&mut self, bus: &mut B, value: u8) {
+        bus.write(0x0100 | u16::from(self.sp), value);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+    fn push_word<B: Bus>(&mut self, bus: &mut B, value: u16) {
+        self.push(bus, (value >> 8) as u8);
+        self.push(bus, value as u8);
+    }
+    fn pull<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        bus.read(0x0100 | u16::from(self.sp))
+    }
+    fn pull_word<B: Bus>(&mut self, bus: &mut B) -> u16 {
+        let lo = self.pull(bus);
+        let hi = self.pull(bus);
+        u16::from_le_bytes([lo, hi])
+    }
+}
+
+fn read_word<B: Bus>(bus: &mut B, address: u16) -> u16 {
+    u16::from_le_bytes([bus.read(address), bus.read(address.wrapping_add(1))])
+}
+
+fn decode(bytes: [u8; 3]) -> Opcode {
+    Opcode::read_options(&mut Cursor::new(bytes), Endian::Little, ())
+        .expect("Every opcode byte decodes")
+}
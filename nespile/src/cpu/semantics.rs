@@ -0,0 +1,270 @@
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::parser::address_mode::AddressMode;
+use crate::parser::opcodes::Opcode;
+
+
+
+/// Defines a small set type over named bits.
+macro_rules! bit_set {
+    ($(#[$meta:meta])* $name:ident { $($(#[$bit_meta:meta])* $bit:ident = $value:expr),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub struct $name(u8);
+        impl $name {
+            pub const NONE: Self = $name(0);
+            $($(#[$bit_meta])* pub const $bit: Self = $name($value);)*
+            const NAMED: &'static [(Self, &'static str)] = &[$(($name::$bit, stringify!($bit))),*];
+
+            pub const fn bits(self) -> u8 {
+                self.0
+            }
+            pub const fn union(self, other: Self) -> Self {
+                $name(self.0 | other.0)
+            }
+            pub const fn contains(self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+            pub const fn is_empty(self) -> bool {
+                self.0 == 0
+            }
+            /// Each member on its own.
+            pub fn iter(self) -> impl Iterator<Item = Self> {
+                Self::NAMED.iter().map(|(bit, _)| *bit).filter(move |bit| self.contains(*bit))
+            }
+        }
+        impl BitOr for $name {
+            type Output = Self;
+            fn bitor(self, other: Self) -> Self {
+                self.union(other)
+            }
+        }
+        impl BitOrAssign for $name {
+            fn bitor_assign(&mut self, other: Self) {
+                *self = self.union(other);
+            }
+        }
+        impl std::fmt::Debug for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let names: Vec<&str> = Self::NAMED.iter()
+                    .filter(|(bit, _)| self.contains(*bit))
+                    .map(|(_, name)| *name)
+                    .collect();
+                write!(f, "{}({})", stringify!($name), names.join(" | "))
+            }
+        }
+    };
+}
+
+bit_set!(
+    /// CPU registers, other than the status flags and program counter.
+    Registers {
+        A = 0x01,
+        X = 0x02,
+        Y = 0x04,
+        /// Stack pointer.
+        SP = 0x08,
+    }
+);
+bit_set!(
+    /// Status flags, with their bit positions in P.
+    Flags {
+        /// Carry.
+        C = 0x01,
+        /// Zero.
+        Z = 0x02,
+        /// Interrupt disable.
+        I = 0x04,
+        /// Decimal, which the NES's 2A03 ignores.
+        D = 0x08,
+        /// Overflow.
+        V = 0x40,
+        /// Negative.
+        N = 0x80,
+    }
+);
+impl Flags {
+    pub const NZ: Flags = Flags::N.union(Flags::Z);
+    pub const NZC: Flags = Flags::NZ.union(Flags::C);
+    pub const NVZC: Flags = Flags::NZC.union(Flags::V);
+    pub const ALL: Flags = Flags::NVZC.union(Flags::I).union(Flags::D);
+}
+
+/// The constant ORed into A by the unstable `XAA` and `LXA` opcodes, which varies between chips.
+pub const UNSTABLE_MAGIC: u8 = 0xee;
+
+/// How an instruction accesses the memory its operand addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryAccess {
+    None,
+    Read,
+    Write,
+    ReadModifyWrite,
+}
+
+/// Where execution continues after an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlFlow {
+    /// The next instruction.
+    Next,
+    /// A relative branch, taken or not.
+    Branch,
+    /// `JMP` to an absolute address.
+    Jump,
+    /// `JMP` through a pointer.
+    IndirectJump,
+    /// `JSR`.
+    Call,
+    /// `RTS`.
+    Return,
+    /// `RTI`.
+    ReturnFromInterrupt,
+    /// `BRK`, through the IRQ vector.
+    Interrupt,
+    /// `STP` jams the CPU.
+    Halt,
+}
+impl ControlFlow {
+    /// Whether execution can continue at the following instruction.
+    pub fn falls_through(&self) -> bool {
+        matches!(self, ControlFlow::Next | ControlFlow::Branch | ControlFlow::Call)
+    }
+}
+
+/// What an instruction reads and changes, per https://www.masswerk.at/6502/6502_instruction_set.html
+/// and https://www.nesdev.org/wiki/CPU_unofficial_opcodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Semantics {
+    /// Registers read, including index registers used for addressing.
+    pub registers_read: Registers,
+    pub registers_written: Registers,
+    pub flags_read: Flags,
+    pub flags_written: Flags,
+    pub memory: MemoryAccess,
+    /// Bytes pushed onto the stack, negative for bytes pulled.
+    pub stack: i8,
+    pub control_flow: ControlFlow,
+}
+impl Default for Semantics {
+    fn default() -> Self {
+        Semantics {
+            registers_read: Registers::NONE,
+            registers_written: Registers::NONE,
+            flags_read: Flags::NONE,
+            flags_written: Flags::NONE,
+            memory: MemoryAccess::None,
+            stack: 0,
+            control_flow: ControlFlow::Next,
+        }
+    }
+}
+
+impl Opcode {
+    pub fn semantics(&self) -> Semantics {
+        use Registers as R;
+
+        let mode = self.argument();
+        let on_memory = !matches!(mode, None | Some(AddressMode::Implied | AddressMode::Accumulator | AddressMode::Immediate(_)));
+        let operand_access = |access| if on_memory { access } else { MemoryAccess::None };
+        // Shifts and rotates work on A without an address.
+        let shifted = if on_memory { R::NONE } else { R::A };
+
+        let op = |read: Registers, written: Registers, flags_read: Flags, flags_written: Flags, memory: MemoryAccess| Semantics {
+            registers_read: read,
+            registers_written: written,
+            flags_read,
+            flags_written,
+            memory: operand_access(memory),
+            ..Semantics::default()
+        };
+        let stack = |read: Registers, written: Registers, flags_read: Flags, flags_written: Flags, stack: i8, control_flow| Semantics {
+            registers_read: read | R::SP,
+            registers_written: written | R::SP,
+            flags_read,
+            flags_written,
+            stack,
+            control_flow,
+            ..Semantics::default()
+        };
+        let branch = |flag: Flags| Semantics {
+            flags_read: flag,
+            control_flow: ControlFlow::Branch,
+            ..Semantics::default()
+        };
+        use MemoryAccess::{None as NoAccess, Read, ReadModifyWrite as Rmw, Write};
+        let none = Flags::NONE;
+
+        let mut semantics = match self {
+            Opcode::ADC(_) | Opcode::SBC(_) => op(R::A, R::A, Flags::C, Flags::NVZC, Read),
+            Opcode::AND(_) | Opcode::EOR(_) | Opcode::ORA(_) => op(R::A, R::A, none, Flags::NZ, Read),
+            Opcode::ASL(_) | Opcode::LSR(_) => op(shifted, shifted, none, Flags::NZC, Rmw),
+            Opcode::ROL(_) | Opcode::ROR(_) => op(shifted, shifted, Flags::C, Flags::NZC, Rmw),
+            Opcode::BIT(_) => op(R::A, R::NONE, none, Flags::N | Flags::V | Flags::Z, Read),
+            Opcode::BCC(_) | Opcode::BCS(_) => branch(Flags::C),
+            Opcode::BEQ(_) | Opcode::BNE(_) => branch(Flags::Z),
+            Opcode::BMI(_) | Opcode::BPL(_) => branch(Flags::N),
+            Opcode::BVC(_) | Opcode::BVS(_) => branch(Flags::V),
+            Opcode::BRK => stack(R::NONE, R::NONE, Flags::ALL, Flags::I, 3, ControlFlow::Interrupt),
+            Opcode::CLC | Opcode::SEC => op(R::NONE, R::NONE, none, Flags::C, NoAccess),
+            Opcode::CLD | Opcode::SED => op(R::NONE, R::NONE, none, Flags::D, NoAccess),
+            Opcode::CLI | Opcode::SEI => op(R::NONE, R::NONE, none, Flags::I, NoAccess),
+            Opcode::CLV => op(R::NONE, R::NONE, none, Flags::V, NoAccess),
+            Opcode::CMP(_) => op(R::A, R::NONE, none, Flags::NZC, Read),
+            Opcode::CPX(_) => op(R::X, R::NONE, none, Flags::NZC, Read),
+            Opcode::CPY(_) => op(R::Y, R::NONE, none, Flags::NZC, Read),
+            Opcode::DEC(_) | Opcode::INC(_) => op(R::NONE, R::NONE, none, Flags::NZ, Rmw),
+            Opcode::DEX | Opcode::INX => op(R::X, R::X, none, Flags::NZ, NoAccess),
+            Opcode::DEY | Opcode::INY => op(R::Y, R::Y, none, Flags::NZ, NoAccess),
+            Opcode::JMP(_) => Semantics {
+                memory: if matches!(mode, Some(AddressMode::Indirect(_))) { Read } else { NoAccess },
+                control_flow: if matches!(mode, Some(AddressMode::Indirect(_))) { ControlFlow::IndirectJump } else { ControlFlow::Jump },
+                ..Semantics::default()
+            },
+            Opcode::JSR(_) => stack(R::NONE, R::NONE, none, none, 2, ControlFlow::Call),
+            Opcode::LDA(_) => op(R::NONE, R::A, none, Flags::NZ, Read),
+            Opcode::LDX(_) => op(R::NONE, R::X, none, Flags::NZ, Read),
+            Opcode::LDY(_) => op(R::NONE, R::Y, none, Flags::NZ, Read),
+            Opcode::NOP(_) => op(R::NONE, R::NONE, none, none, Read),
+            Opcode::PHA => stack(R::A, R::NONE, none, none, 1, ControlFlow::Next),
+            Opcode::PHP => stack(R::NONE, R::NONE, Flags::ALL, none, 1, ControlFlow::Next),
+            Opcode::PLA => stack(R::NONE, R::A, none, Flags::NZ, -1, ControlFlow::Next),
+            Opcode::PLP => stack(R::NONE, R::NONE, none, Flags::ALL, -1, ControlFlow::Next),
+            Opcode::RTI => stack(R::NONE, R::NONE, none, Flags::ALL, -3, ControlFlow::ReturnFromInterrupt),
+            Opcode::RTS => stack(R::NONE, R::NONE, none, none, -2, ControlFlow::Return),
+            Opcode::STA(_) => op(R::A, R::NONE, none, none, Write),
+            Opcode::STX(_) => op(R::X, R::NONE, none, none, Write),
+            Opcode::STY(_) => op(R::Y, R::NONE, none, none, Write),
+            Opcode::TAX => op(R::A, R::X, none, Flags::NZ, NoAccess),
+            Opcode::TAY => op(R::A, R::Y, none, Flags::NZ, NoAccess),
+            Opcode::TSX => op(R::SP, R::X, none, Flags::NZ, NoAccess),
+            Opcode::TXA => op(R::X, R::A, none, Flags::NZ, NoAccess),
+            Opcode::TXS => op(R::X, R::SP, none, none, NoAccess),
+            Opcode::TYA => op(R::Y, R::A, none, Flags::NZ, NoAccess),
+
+            // Unofficial opcodes.
+            Opcode::AHX(_) | Opcode::SAX(_) => op(R::A | R::X, R::NONE, none, none, Write),
+            Opcode::ALR(_) | Opcode::ANC(_) => op(R::A, R::A, none, Flags::NZC, Read),
+            Opcode::ARR(_) => op(R::A, R::A, Flags::C, Flags::NVZC, Read),
+            Opcode::AXS(_) => op(R::A | R::X, R::X, none, Flags::NZC, Read),
+            Opcode::DCP(_) => op(R::A, R::NONE, none, Flags::NZC, Rmw),
+            Opcode::ISC(_) | Opcode::RRA(_) => op(R::A, R::A, Flags::C, Flags::NVZC, Rmw),
+            Opcode::LAS(_) => op(R::SP, R::A | R::X | R::SP, none, Flags::NZ, Read),
+            Opcode::LAX(_) => op(R::NONE, R::A | R::X, none, Flags::NZ, Read),
+            Opcode::LXA(_) => op(R::A, R::A | R::X, none, Flags::NZ, Read),
+            Opcode::RLA(_) => op(R::A, R::A, Flags::C, Flags::NZC, Rmw),
+            Opcode::SHX(_) => op(R::X, R::NONE, none, none, Write),
+            Opcode::SHY(_) => op(R::Y, R::NONE, none, none, Write),
+            Opcode::SLO(_) | Opcode::SRE(_) => op(R::A, R::A, none, Flags::NZC, Rmw),
+            Opcode::TAS(_) => op(R::A | R::X, R::SP, none, none, Write),
+            Opcode::XAA(_) => op(R::A | R::X, R::A, none, Flags::NZ, Read),
+            Opcode::STP => Semantics { control_flow: ControlFlow::Halt, ..Semantics::default() },
+        };
+
+        semantics.registers_read |= match mode {
+            Some(AddressMode::AbsoluteX(_) | AddressMode::ZeroPageX(_) | AddressMode::IndirectX(_)) => R::X,
+            Some(AddressMode::AbsoluteY(_) | AddressMode::ZeroPageY(_) | AddressMode::IndirectY(_)) => R::Y,
+            _ => R::NONE,
+        };
+        semantics
+    }
+}
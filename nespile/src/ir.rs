@@ -0,0 +1,202 @@
+use std::collections::BTreeMap;
+
+use crate::analysis::cfg::BlockId;
+use crate::parser::disassembler::Vector;
+
+pub mod lift;
+pub mod print;
+
+pub use lift::{lift_function, lift_instruction, lift_program, Exit, Lifted};
+
+
+
+/// A CPU register, other than the status flags and program counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Reg { A, X, Y, SP }
+impl Reg {
+    pub const ALL: [Reg; 4] = [Reg::A, Reg::X, Reg::Y, Reg::SP];
+}
+
+/// A status flag, kept as a separate boolean rather than packed into P.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Flag { C, Z, I, D, V, N }
+impl Flag {
+    pub const ALL: [Flag; 6] = [Flag::C, Flag::Z, Flag::I, Flag::D, Flag::V, Flag::N];
+
+    /// The flag's bit in P.
+    pub fn mask(&self) -> u8 {
+        1 << self.bit()
+    }
+    /// The flag's bit number in P.
+    pub fn bit(&self) -> u8 {
+        match self {
+            Flag::C => 0,
+            Flag::Z => 1,
+            Flag::I => 2,
+            Flag::D => 3,
+            Flag::V => 6,
+            Flag::N => 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Type { Bool, U8, U16 }
+
+/// A value computed once within a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Temp {
+    pub id: u32,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// Bitwise complement, or logical not of a `Bool`.
+    Not,
+    /// Bit 7 of a `U8`, as a `Bool`.
+    Negative,
+    /// Whether a `U8` or `U16` is zero, as a `Bool`.
+    IsZero,
+}
+
+/// Operations on two values of one type, wrapping around at its width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    And,
+    Or,
+    Xor,
+    /// Shift left by a `U8` constant.
+    Shl,
+    /// Logical shift right by a `U8` constant.
+    Shr,
+    /// Unsigned greater than or equal, as a `Bool`.
+    Ge,
+}
+
+/// A side-effect-free value, apart from `Load`, which only appears directly in `Stmt::Let`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    Reg(Reg),
+    Flag(Flag),
+    Temp(Temp),
+    /// A byte read from a `U16` address.
+    Load(Box<Expr>),
+    /// Converts between types: `Bool` to 0 or 1, `U8` to `U16` by zero extension,
+    /// `U16` to `U8` by taking the low byte, and any number to `Bool` by comparing with zero.
+    Cast(Type, Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+impl Expr {
+    pub fn ty(&self) -> Type {
+        match self {
+            Expr::Bool(_) | Expr::Flag(_) => Type::Bool,
+            Expr::U8(_) | Expr::Reg(_) | Expr::Load(_) => Type::U8,
+            Expr::U16(_) => Type::U16,
+            Expr::Temp(temp) => temp.ty,
+            Expr::Cast(ty, _) => *ty,
+            Expr::Unary(UnaryOp::Not, value) => value.ty(),
+            Expr::Unary(UnaryOp::Negative | UnaryOp::IsZero, _) => Type::Bool,
+            Expr::Binary(BinaryOp::Ge, _, _) => Type::Bool,
+            Expr::Binary(_, lhs, _) => lhs.ty(),
+        }
+    }
+
+    /// Converts to `ty`, folding constants and leaving values already of that type alone.
+    pub fn cast(self, ty: Type) -> Expr {
+        match (self, ty) {
+            (value, ty) if value.ty() == ty => value,
+            (Expr::U8(value), Type::U16) => Expr::U16(value as u16),
+            (Expr::U16(value), Type::U8) => Expr::U8(value as u8),
+            (value, ty) => Expr::Cast(ty, Box::new(value)),
+        }
+    }
+    pub fn unary(op: UnaryOp, value: Expr) -> Expr {
+        Expr::Unary(op, Box::new(value))
+    }
+    pub fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+        Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stmt {
+    Let(Temp, Expr),
+    SetReg(Reg, Expr),
+    SetFlag(Flag, Expr),
+    /// Writes a `U8` value to a `U16` address.
+    Store(Expr, Expr),
+}
+
+/// Where a block goes next, when that is a decoded block or only a CPU address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Block(BlockId),
+    /// An address nothing was decoded at, such as an unresolved bank-switched target.
+    Address(u16),
+}
+
+/// How control leaves a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminator {
+    Goto(Target),
+    Branch { condition: Expr, taken: Target, not_taken: Target },
+    /// `JSR`, with the return address already pushed.
+    Call { callee: Target, return_to: Target },
+    /// `RTS`, to the address pulled from the stack.
+    Return(Expr),
+    /// `RTI`, with the status already pulled, to the address pulled after it.
+    ReturnFromInterrupt(Expr),
+    /// `JMP (ptr)`, or an `RTS` dispatching through a jump table, to `address`, which is one of `targets` if any were recovered.
+    JumpIndirect { address: Expr, targets: Vec<BlockId> },
+    /// `BRK`, with the return address and status pushed, through the vector at `vector`.
+    Interrupt { vector: u16 },
+    /// `STP`.
+    Halt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub id: BlockId,
+    /// CPU address of the block's first instruction.
+    pub address: u16,
+    pub bank: usize,
+    pub stmts: Vec<Stmt>,
+    pub terminator: Terminator,
+}
+
+/// A lifted function, from its entry block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub entry: BlockId,
+    pub blocks: BTreeMap<BlockId, Block>,
+}
+impl Function {
+    pub fn entry_block(&self) -> &Block {
+        &self.blocks[&self.entry]
+    }
+    /// Every temporary the function defines, in order.
+    pub fn temps(&self) -> Vec<Temp> {
+        self.blocks.values()
+            .flat_map(|block| &block.stmts)
+            .filter_map(|stmt| match stmt {
+                Stmt::Let(temp, _) => Some(*temp),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Every lifted function, keyed by entry block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub functions: BTreeMap<BlockId, Function>,
+    /// Entry function of each interrupt vector.
+    pub vectors: Vec<(Vector, BlockId)>,
+}
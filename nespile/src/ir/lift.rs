@@ -0,0 +1,544 @@
+use std::collections::BTreeMap;
+
+use crate::analysis::cfg::{self, BasicBlock, ControlFlowGraph, EdgeKind};
+use crate::cpu::UNSTABLE_MAGIC;
+use crate::parser::address_mode::AddressMode;
+use crate::parser::disassembler::IRQ_VECTOR;
+use crate::parser::instruction::Instruction;
+use crate::parser::opcodes::Opcode;
+
+use super::{BinaryOp, Block, Expr, Flag, Function, Program, Reg, Stmt, Target, Temp, Terminator, Type, UnaryOp};
+
+
+
+/// How control leaves a single lifted instruction, by CPU address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Exit {
+    Next,
+    Branch { condition: Expr, target: u16 },
+    Jump(u16),
+    JumpIndirect(Expr),
+    Call(u16),
+    Return(Expr),
+    ReturnFromInterrupt(Expr),
+    Interrupt(u16),
+    Halt,
+}
+
+/// One instruction's effects, in the order the CPU performs them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lifted {
+    pub stmts: Vec<Stmt>,
+    pub exit: Exit,
+}
+
+/// Lifts one instruction on its own, numbering temporaries from zero.
+pub fn lift_instruction(instruction: &Instruction) -> Lifted {
+    Lifter::default().instruction(instruction)
+}
+
+/// Lifts every block of a function, ending each in a terminator resolved against the graph's edges.
+pub fn lift_function(function: &cfg::Function, graph: &ControlFlowGraph) -> Function {
+    let mut lifter = Lifter::default();
+    let blocks = function.blocks.iter()
+        .map(|id| (*id, lifter.block(&graph.blocks[id], graph)))
+        .collect();
+    Function { entry: function.entry, blocks }
+}
+
+pub fn lift_program(graph: &ControlFlowGraph) -> Program {
+    let functions = graph.functions.values()
+        .map(|function| (function.entry, lift_function(function, graph)))
+        .collect::<BTreeMap<_, _>>();
+    Program { functions, vectors: graph.vectors.clone() }
+}
+
+
+
+#[derive(Default)]
+struct Lifter {
+    next_temp: u32,
+    stmts: Vec<Stmt>,
+}
+impl Lifter {
+    fn block(&mut self, block: &BasicBlock, graph: &ControlFlowGraph) -> Block {
+        let mut exit = Exit::Next;
+        for instruction in &block.instructions {
+            exit = self.opcode(instruction);
+        }
+        let stmts = std::mem::take(&mut self.stmts);
+
+        let last = block.last();
+        let edge = |kind: EdgeKind| graph.successors(block.id).find(|edge| edge.kind == kind).map(|edge| edge.to);
+        let target = |kind: EdgeKind, address: u16| edge(kind).map_or(Target::Address(address), Target::Block);
+        let next = target(EdgeKind::Fallthrough, last.next_address());
+        let indirect: Vec<_> = graph.successors(block.id)
+            .filter(|edge| edge.kind == EdgeKind::Indirect)
+            .map(|edge| edge.to)
+            .collect();
+
+        let terminator = match exit {
+            Exit::Next => Terminator::Goto(next),
+            Exit::Branch { condition, target: address } => Terminator::Branch {
+                condition,
+                taken: target(EdgeKind::Branch, address),
+                not_taken: next,
+            },
+            Exit::Jump(address) => Terminator::Goto(target(EdgeKind::Jump, address)),
+            Exit::JumpIndirect(address) => Terminator::JumpIndirect { address, targets: indirect },
+            Exit::Call(address) => Terminator::Call { callee: target(EdgeKind::Call, address), return_to: next },
+            Exit::Return(address) if !indirect.is_empty() => Terminator::JumpIndirect { address, targets: indirect },
+            Exit::Return(address) => Terminator::Return(address),
+            Exit::ReturnFromInterrupt(address) => Terminator::ReturnFromInterrupt(address),
+            Exit::Interrupt(vector) => Terminator::Interrupt { vector },
+            Exit::Halt => Terminator::Halt,
+        };
+        Block { id: block.id, address: block.address(), bank: block.bank(), stmts, terminator }
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) -> Lifted {
+        let exit = self.opcode(instruction);
+        Lifted { stmts: std::mem::take(&mut self.stmts), exit }
+    }
+
+    fn opcode(&mut self, instruction: &Instruction) -> Exit {
+        use Opcode::*;
+
+        let opcode = &instruction.opcode;
+        let mode = opcode.argument().unwrap_or(AddressMode::Implied);
+        match opcode {
+            LDA(_) => { let value = self.operand(mode); self.set_reg_nz(Reg::A, value) },
+            LDX(_) => { let value = self.operand(mode); self.set_reg_nz(Reg::X, value) },
+            LDY(_) => { let value = self.operand(mode); self.set_reg_nz(Reg::Y, value) },
+            STA(_) => { let address = self.address(mode); self.store(address, reg(Reg::A)) },
+            STX(_) => { let address = self.address(mode); self.store(address, reg(Reg::X)) },
+            STY(_) => { let address = self.address(mode); self.store(address, reg(Reg::Y)) },
+
+            TAX => self.set_reg_nz(Reg::X, reg(Reg::A)),
+            TAY => self.set_reg_nz(Reg::Y, reg(Reg::A)),
+            TXA => self.set_reg_nz(Reg::A, reg(Reg::X)),
+            TYA => self.set_reg_nz(Reg::A, reg(Reg::Y)),
+            TSX => self.set_reg_nz(Reg::X, reg(Reg::SP)),
+            TXS => self.set_reg(Reg::SP, reg(Reg::X)),
+
+            INX => self.set_reg_nz(Reg::X, add(reg(Reg::X), u8(1))),
+            INY => self.set_reg_nz(Reg::Y, add(reg(Reg::Y), u8(1))),
+            DEX => self.set_reg_nz(Reg::X, sub(reg(Reg::X), u8(1))),
+            DEY => self.set_reg_nz(Reg::Y, sub(reg(Reg::Y), u8(1))),
+
+            AND(_) => { let value = self.operand(mode); self.logic(BinaryOp::And, value) },
+            ORA(_) => { let value = self.operand(mode); self.logic(BinaryOp::Or, value) },
+            EOR(_) => { let value = self.operand(mode); self.logic(BinaryOp::Xor, value) },
+            ADC(_) => { let value = self.operand(mode); self.adc(value) },
+            SBC(_) => { let value = self.operand(mode); self.sbc(value) },
+            CMP(_) => { let value = self.operand(mode); self.compare(reg(Reg::A), value) },
+            CPX(_) => { let value = self.operand(mode); self.compare(reg(Reg::X), value) },
+            CPY(_) => { let value = self.operand(mode); self.compare(reg(Reg::Y), value) },
+            BIT(_) => {
+                let value = self.operand(mode);
+                self.set_flag(Flag::Z, is_zero(and(reg(Reg::A), value.clone())));
+                self.set_flag(Flag::V, and(value.clone(), u8(0x40)).cast(Type::Bool));
+                self.set_flag(Flag::N, negative(value));
+            },
+
+            ASL(_) => { self.modify(mode, Self::asl); },
+            LSR(_) => { self.modify(mode, Self::lsr); },
+            ROL(_) => { self.modify(mode, Self::rol); },
+            ROR(_) => { self.modify(mode, Self::ror); },
+            INC(_) => { self.modify(mode, |lifter, value| lifter.nz(add(value, u8(1)))); },
+            DEC(_) => { self.modify(mode, |lifter, value| lifter.nz(sub(value, u8(1)))); },
+
+            CLC => self.set_flag(Flag::C, Expr::Bool(false)),
+            SEC => self.set_flag(Flag::C, Expr::Bool(true)),
+            CLI => self.set_flag(Flag::I, Expr::Bool(false)),
+            SEI => self.set_flag(Flag::I, Expr::Bool(true)),
+            CLD => self.set_flag(Flag::D, Expr::Bool(false)),
+            SED => self.set_flag(Flag::D, Expr::Bool(true)),
+            CLV => self.set_flag(Flag::V, Expr::Bool(false)),
+
+            PHA => self.push(reg(Reg::A)),
+            PHP => { let status = self.pack_status(); self.push(status) },
+            PLA => { let value = self.pull(); self.set_reg_nz(Reg::A, value) },
+            PLP => { let status = self.pull(); self.unpack_status(status) },
+
+            BPL(_) => return self.branch(instruction, not(flag(Flag::N))),
+            BMI(_) => return self.branch(instruction, flag(Flag::N)),
+            BVC(_) => return self.branch(instruction, not(flag(Flag::V))),
+            BVS(_) => return self.branch(instruction, flag(Flag::V)),
+            BCC(_) => return self.branch(instruction, not(flag(Flag::C))),
+            BCS(_) => return self.branch(instruction, flag(Flag::C)),
+            BNE(_) => return self.branch(instruction, not(flag(Flag::Z))),
+            BEQ(_) => return self.branch(instruction, flag(Flag::Z)),
+
+            JMP(_) => return match mode {
+                AddressMode::Absolute(address) => Exit::Jump(address),
+                _ => Exit::JumpIndirect(self.address(mode)),
+            },
+            JSR(_) => {
+                self.push_word(instruction.address.wrapping_add(2));
+                return Exit::Call(mode.address().unwrap_or_default());
+            },
+            RTS => {
+                let address = self.pull_word();
+                return Exit::Return(add(address, Expr::U16(1)));
+            },
+            RTI => {
+                let status = self.pull();
+                self.unpack_status(status);
+                return Exit::ReturnFromInterrupt(self.pull_word());
+            },
+            BRK => {
+                self.push_word(instruction.address.wrapping_add(2));
+                let status = self.pack_status();
+                self.push(status);
+                self.set_flag(Flag::I, Expr::Bool(true));
+                return Exit::Interrupt(IRQ_VECTOR);
+            },
+            STP => return Exit::Halt,
+            NOP(_) => {
+                // Unofficial NOPs with an operand still read it, which matters for I/O registers.
+                if mode.address().is_some() {
+                    self.operand(mode);
+                }
+            },
+
+            SLO(_) => { let value = self.modify(mode, Self::asl); self.logic(BinaryOp::Or, value) },
+            RLA(_) => { let value = self.modify(mode, Self::rol); self.logic(BinaryOp::And, value) },
+            SRE(_) => { let value = self.modify(mode, Self::lsr); self.logic(BinaryOp::Xor, value) },
+            RRA(_) => { let value = self.modify(mode, Self::ror); self.adc(value) },
+            DCP(_) => {
+                let value = self.modify(mode, |lifter, value| lifter.temp(sub(value, u8(1))));
+                self.compare(reg(Reg::A), value)
+            },
+            ISC(_) => {
+                let value = self.modify(mode, |lifter, value| lifter.temp(add(value, u8(1))));
+                self.sbc(value)
+            },
+            SAX(_) => { let address = self.address(mode); self.store(address, and(reg(Reg::A), reg(Reg::X))) },
+            LAX(_) => {
+                let value = self.operand(mode);
+                self.set_reg(Reg::A, value);
+                self.set_reg_nz(Reg::X, reg(Reg::A));
+            },
+            ANC(_) => {
+                let value = self.operand(mode);
+                self.logic(BinaryOp::And, value);
+                self.set_flag(Flag::C, flag(Flag::N));
+            },
+            ALR(_) => {
+                let value = self.operand(mode);
+                self.set_reg(Reg::A, and(reg(Reg::A), value));
+                let result = self.asl_or_lsr(reg(Reg::A), false);
+                self.set_reg(Reg::A, result);
+            },
+            ARR(_) => {
+                let value = self.operand(mode);
+                let masked = self.temp(and(reg(Reg::A), value));
+                let result = self.temp(or(shr(masked, 1), shl(flag(Flag::C).cast(Type::U8), 7)));
+                self.set_reg_nz(Reg::A, result.clone());
+                self.set_flag(Flag::C, and(result.clone(), u8(0x40)).cast(Type::Bool));
+                self.set_flag(Flag::V, and(xor(result.clone(), shl(result, 1)), u8(0x40)).cast(Type::Bool));
+            },
+            AXS(_) => {
+                let value = self.operand(mode);
+                let masked = self.temp(and(reg(Reg::A), reg(Reg::X)));
+                self.set_flag(Flag::C, ge(masked.clone(), value.clone()));
+                self.set_reg_nz(Reg::X, sub(masked, value));
+            },
+            LAS(_) => {
+                let value = self.operand(mode);
+                let result = self.temp(and(value, reg(Reg::SP)));
+                self.set_reg(Reg::SP, result.clone());
+                self.set_reg(Reg::X, result.clone());
+                self.set_reg_nz(Reg::A, result);
+            },
+            XAA(_) => {
+                let value = self.operand(mode);
+                self.set_reg_nz(Reg::A, and(and(or(reg(Reg::A), u8(UNSTABLE_MAGIC)), reg(Reg::X)), value));
+            },
+            LXA(_) => {
+                let value = self.operand(mode);
+                self.set_reg(Reg::A, and(or(reg(Reg::A), u8(UNSTABLE_MAGIC)), value));
+                self.set_reg_nz(Reg::X, reg(Reg::A));
+            },
+            AHX(_) => self.store_high_masked(mode, and(reg(Reg::A), reg(Reg::X))),
+            SHX(_) => self.store_high_masked(mode, reg(Reg::X)),
+            SHY(_) => self.store_high_masked(mode, reg(Reg::Y)),
+            TAS(_) => {
+                self.set_reg(Reg::SP, and(reg(Reg::A), reg(Reg::X)));
+                self.store_high_masked(mode, reg(Reg::SP));
+            },
+        }
+        Exit::Next
+    }
+
+
+
+    fn temp(&mut self, value: Expr) -> Expr {
+        let temp = Temp { id: self.next_temp, ty: value.ty() };
+        self.next_temp += 1;
+        self.stmts.push(Stmt::Let(temp, value));
+        Expr::Temp(temp)
+    }
+    /// Gives a value a temporary unless it is already cheap to repeat. Registers count as
+    /// cheap, since the helpers below only write them after their operand's last use.
+    fn share(&mut self, value: Expr) -> Expr {
+        match value {
+            Expr::Bool(_) | Expr::U8(_) | Expr::U16(_) | Expr::Reg(_) | Expr::Temp(_) => value,
+            _ => self.temp(value),
+        }
+    }
+    fn load(&mut self, address: Expr) -> Expr {
+        self.temp(Expr::Load(Box::new(address)))
+    }
+    fn store(&mut self, address: Expr, value: Expr) {
+        self.stmts.push(Stmt::Store(address, value));
+    }
+    fn set_reg(&mut self, reg: Reg, value: Expr) {
+        self.stmts.push(Stmt::SetReg(reg, value));
+    }
+    fn set_flag(&mut self, flag: Flag, value: Expr) {
+        self.stmts.push(Stmt::SetFlag(flag, value));
+    }
+    fn set_nz(&mut self, value: Expr) {
+        self.set_flag(Flag::Z, is_zero(value.clone()));
+        self.set_flag(Flag::N, negative(value));
+    }
+    fn set_reg_nz(&mut self, reg: Reg, value: Expr) {
+        self.set_reg(reg, value);
+        self.set_nz(Expr::Reg(reg));
+    }
+    /// Computes a result into a temporary and sets N and Z from it.
+    fn nz(&mut self, value: Expr) -> Expr {
+        let result = self.temp(value);
+        self.set_nz(result.clone());
+        result
+    }
+
+    /// The effective address of a memory operand, as a `U16`.
+    fn address(&mut self, mode: AddressMode) -> Expr {
+        let x = || reg(Reg::X).cast(Type::U16);
+        let y = || reg(Reg::Y).cast(Type::U16);
+        match mode {
+            AddressMode::ZeroPage(address) => Expr::U16(address as u16),
+            AddressMode::Absolute(address) => Expr::U16(address),
+            AddressMode::ZeroPageX(address) => self.temp(add(u8(address), reg(Reg::X)).cast(Type::U16)),
+            AddressMode::ZeroPageY(address) => self.temp(add(u8(address), reg(Reg::Y)).cast(Type::U16)),
+            AddressMode::AbsoluteX(address) => self.temp(add(Expr::U16(address), x())),
+            AddressMode::AbsoluteY(address) => self.temp(add(Expr::U16(address), y())),
+            AddressMode::Indirect(pointer) => {
+                // The high byte is read without carrying into the pointer's page.
+                let hi = (pointer & 0xff00) | (pointer.wrapping_add(1) & 0x00ff);
+                self.load_word(Expr::U16(pointer), Expr::U16(hi))
+            },
+            AddressMode::IndirectX(pointer) => {
+                let pointer = self.temp(add(u8(pointer), reg(Reg::X)));
+                let lo = pointer.clone().cast(Type::U16);
+                let hi = add(pointer, u8(1)).cast(Type::U16);
+                self.load_word(lo, hi)
+            },
+            AddressMode::IndirectY(_) => {
+                let base = self.indirect_y_base(mode);
+                self.temp(add(base, y()))
+            },
+            AddressMode::Accumulator | AddressMode::Implied | AddressMode::Immediate(_) | AddressMode::Relative(_) =>
+                unreachable!("{:?} has no effective address", mode),
+        }
+    }
+    /// The pointer read from the zero page by `(zp),Y`, before Y is added.
+    fn indirect_y_base(&mut self, mode: AddressMode) -> Expr {
+        let AddressMode::IndirectY(pointer) = mode else {
+            unreachable!("{:?} is not (zp),Y", mode);
+        };
+        self.load_word(Expr::U16(pointer as u16), Expr::U16(pointer.wrapping_add(1) as u16))
+    }
+    fn load_word(&mut self, lo: Expr, hi: Expr) -> Expr {
+        let lo = self.load(lo);
+        let hi = self.load(hi);
+        self.temp(word(lo, hi))
+    }
+
+    /// The value of an operand, as a `U8`.
+    fn operand(&mut self, mode: AddressMode) -> Expr {
+        match mode {
+            AddressMode::Immediate(value) => u8(value),
+            AddressMode::Accumulator => reg(Reg::A),
+            _ => {
+                let address = self.address(mode);
+                self.load(address)
+            },
+        }
+    }
+    /// Reads, transforms and writes back an operand, returning the value written.
+    fn modify(&mut self, mode: AddressMode, f: impl FnOnce(&mut Self, Expr) -> Expr) -> Expr {
+        if mode == AddressMode::Accumulator {
+            let result = f(self, reg(Reg::A));
+            self.set_reg(Reg::A, result.clone());
+            return result;
+        }
+        let address = self.address(mode);
+        let value = self.load(address.clone());
+        let result = f(self, value);
+        self.store(address, result.clone());
+        result
+    }
+
+    fn asl(&mut self, value: Expr) -> Expr {
+        self.asl_or_lsr(value, true)
+    }
+    fn lsr(&mut self, value: Expr) -> Expr {
+        self.asl_or_lsr(value, false)
+    }
+    fn asl_or_lsr(&mut self, value: Expr, left: bool) -> Expr {
+        let value = self.share(value);
+        let (carry, result) = if left {
+            (negative(value.clone()), shl(value, 1))
+        } else {
+            (and(value.clone(), u8(1)).cast(Type::Bool), shr(value, 1))
+        };
+        let result = self.nz(result);
+        self.set_flag(Flag::C, carry);
+        result
+    }
+    fn rol(&mut self, value: Expr) -> Expr {
+        let value = self.share(value);
+        let result = self.nz(or(shl(value.clone(), 1), flag(Flag::C).cast(Type::U8)));
+        self.set_flag(Flag::C, negative(value));
+        result
+    }
+    fn ror(&mut self, value: Expr) -> Expr {
+        let value = self.share(value);
+        let result = self.nz(or(shr(value.clone(), 1), shl(flag(Flag::C).cast(Type::U8), 7)));
+        self.set_flag(Flag::C, and(value, u8(1)).cast(Type::Bool));
+        result
+    }
+
+    fn logic(&mut self, op: BinaryOp, value: Expr) {
+        self.set_reg_nz(Reg::A, Expr::binary(op, reg(Reg::A), value));
+    }
+    /// Binary add with carry; the NES CPU has no decimal mode.
+    fn adc(&mut self, value: Expr) {
+        let value = self.share(value);
+        let sum = self.temp(add(
+            add(reg(Reg::A).cast(Type::U16), value.clone().cast(Type::U16)),
+            flag(Flag::C).cast(Type::U16),
+        ));
+        let result = self.temp(sum.clone().cast(Type::U8));
+        self.set_flag(Flag::V, negative(and(xor(reg(Reg::A), result.clone()), xor(value, result.clone()))));
+        self.set_flag(Flag::C, shr(sum, 8).cast(Type::Bool));
+        self.set_reg_nz(Reg::A, result);
+    }
+    fn sbc(&mut self, value: Expr) {
+        let inverted = match value {
+            Expr::U8(value) => u8(!value),
+            value => Expr::unary(UnaryOp::Not, value),
+        };
+        self.adc(inverted);
+    }
+    fn compare(&mut self, register: Expr, value: Expr) {
+        let value = self.share(value);
+        self.nz(sub(register.clone(), value.clone()));
+        self.set_flag(Flag::C, ge(register, value));
+    }
+
+    fn branch(&mut self, instruction: &Instruction, condition: Expr) -> Exit {
+        Exit::Branch { condition, target: instruction.branch_target().unwrap_or_default() }
+    }
+
+    /// Stores `value` ANDed with one more than the high byte of the base address, as `AHX`, `SHX`, `SHY` and `TAS` do.
+    fn store_high_masked(&mut self, mode: AddressMode, value: Expr) {
+        let (address, high) = match mode {
+            AddressMode::AbsoluteX(base) | AddressMode::AbsoluteY(base) =>
+                (self.address(mode), u8((base >> 8) as u8)),
+            AddressMode::IndirectY(_) => {
+                let base = self.indirect_y_base(mode);
+                let address = self.temp(add(base.clone(), reg(Reg::Y).cast(Type::U16)));
+                (address, shr(base, 8).cast(Type::U8))
+            },
+            _ => unreachable!("{:?} is not indexed", mode),
+        };
+        self.store(address, and(value, add(high, u8(1))));
+    }
+
+    fn push(&mut self, value: Expr) {
+        self.store(stack_address(), value);
+        self.set_reg(Reg::SP, sub(reg(Reg::SP), u8(1)));
+    }
+    fn push_word(&mut self, value: u16) {
+        self.push(u8((value >> 8) as u8));
+        self.push(u8(value as u8));
+    }
+    fn pull(&mut self) -> Expr {
+        self.set_reg(Reg::SP, add(reg(Reg::SP), u8(1)));
+        self.load(stack_address())
+    }
+    fn pull_word(&mut self) -> Expr {
+        let lo = self.pull();
+        let hi = self.pull();
+        self.temp(word(lo, hi))
+    }
+    /// P as pushed by `PHP` and `BRK`, with the break and unused bits set.
+    fn pack_status(&mut self) -> Expr {
+        let status = Flag::ALL.iter().fold(u8(0x30), |status, flag| {
+            or(status, shl(Expr::Flag(*flag).cast(Type::U8), flag.bit()))
+        });
+        self.temp(status)
+    }
+    fn unpack_status(&mut self, status: Expr) {
+        for flag in Flag::ALL {
+            self.set_flag(flag, and(status.clone(), u8(flag.mask())).cast(Type::Bool));
+        }
+    }
+}
+
+
+
+fn u8(value: u8) -> Expr {
+    Expr::U8(value)
+}
+fn reg(reg: Reg) -> Expr {
+    Expr::Reg(reg)
+}
+fn flag(flag: Flag) -> Expr {
+    Expr::Flag(flag)
+}
+fn not(value: Expr) -> Expr {
+    Expr::unary(UnaryOp::Not, value)
+}
+fn negative(value: Expr) -> Expr {
+    Expr::unary(UnaryOp::Negative, value)
+}
+fn is_zero(value: Expr) -> Expr {
+    Expr::unary(UnaryOp::IsZero, value)
+}
+fn add(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::binary(BinaryOp::Add, lhs, rhs)
+}
+fn sub(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::binary(BinaryOp::Sub, lhs, rhs)
+}
+fn and(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::binary(BinaryOp::And, lhs, rhs)
+}
+fn or(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::binary(BinaryOp::Or, lhs, rhs)
+}
+fn xor(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::binary(BinaryOp::Xor, lhs, rhs)
+}
+fn shl(value: Expr, amount: u8) -> Expr {
+    Expr::binary(BinaryOp::Shl, value, u8(amount))
+}
+fn shr(value: Expr, amount: u8) -> Expr {
+    Expr::binary(BinaryOp::Shr, value, u8(amount))
+}
+fn ge(lhs: Expr, rhs: Expr) -> Expr {
+    Expr::binary(BinaryOp::Ge, lhs, rhs)
+}
+fn word(lo: Expr, hi: Expr) -> Expr {
+    or(lo.cast(Type::U16), shl(hi.cast(Type::U16), 8))
+}
+/// `$0100 + SP`.
+fn stack_address() -> Expr {
+    or(Expr::U16(0x0100), reg(Reg::SP).cast(Type::U16))
+}
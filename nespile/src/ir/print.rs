@@ -0,0 +1,157 @@
+use std::fmt;
+
+use super::{BinaryOp, Block, Expr, Flag, Function, Program, Reg, Stmt, Target, Temp, Terminator, Type, UnaryOp};
+
+
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Reg::A => "A",
+            Reg::X => "X",
+            Reg::Y => "Y",
+            Reg::SP => "SP",
+        })
+    }
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Flag::C => "C",
+            Flag::Z => "Z",
+            Flag::I => "I",
+            Flag::D => "D",
+            Flag::V => "V",
+            Flag::N => "N",
+        })
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Type::Bool => "bool",
+            Type::U8 => "u8",
+            Type::U16 => "u16",
+        })
+    }
+}
+
+impl fmt::Display for Temp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "t{}", self.id)
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::And => "&",
+            BinaryOp::Or => "|",
+            BinaryOp::Xor => "^",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::Ge => ">=",
+        })
+    }
+}
+
+/// Expressions print fully parenthesized, with loads as `[address]` and casts as `type(value)`.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Bool(value) => write!(f, "{}", value),
+            Expr::U8(value) => write!(f, "${:02x}", value),
+            Expr::U16(value) => write!(f, "${:04x}", value),
+            Expr::Reg(reg) => write!(f, "{}", reg),
+            Expr::Flag(flag) => write!(f, "{}", flag),
+            Expr::Temp(temp) => write!(f, "{}", temp),
+            Expr::Load(address) => write!(f, "[{}]", address),
+            Expr::Cast(ty, value) => write!(f, "{}({})", ty, value),
+            Expr::Unary(UnaryOp::Not, value) if value.ty() == Type::Bool => write!(f, "!{}", value),
+            Expr::Unary(UnaryOp::Not, value) => write!(f, "~{}", value),
+            Expr::Unary(UnaryOp::Negative, value) => write!(f, "neg({})", value),
+            Expr::Unary(UnaryOp::IsZero, value) => write!(f, "zero({})", value),
+            Expr::Binary(op, lhs, rhs) => write!(f, "({} {} {})", lhs, op, rhs),
+        }
+    }
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stmt::Let(temp, value) => write!(f, "{}: {} = {}", temp, temp.ty, value),
+            Stmt::SetReg(reg, value) => write!(f, "{} = {}", reg, value),
+            Stmt::SetFlag(flag, value) => write!(f, "{} = {}", flag, value),
+            Stmt::Store(address, value) => write!(f, "[{}] = {}", address, value),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Block(block) => write!(f, "b{:x}", block),
+            Target::Address(address) => write!(f, "${:04x}", address),
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Goto(target) => write!(f, "goto {}", target),
+            Terminator::Branch { condition, taken, not_taken } =>
+                write!(f, "if {} goto {} else {}", condition, taken, not_taken),
+            Terminator::Call { callee, return_to } => write!(f, "call {} then {}", callee, return_to),
+            Terminator::Return(address) => write!(f, "return {}", address),
+            Terminator::ReturnFromInterrupt(address) => write!(f, "return_interrupt {}", address),
+            Terminator::JumpIndirect { address, targets } => {
+                write!(f, "goto {}", address)?;
+                if !targets.is_empty() {
+                    let targets: Vec<_> = targets.iter().map(|target| Target::Block(*target).to_string()).collect();
+                    write!(f, " in {}", targets.join(", "))?;
+                }
+                Ok(())
+            },
+            Terminator::Interrupt { vector } => write!(f, "interrupt [${:04x}]", vector),
+            Terminator::Halt => f.write_str("halt"),
+        }
+    }
+}
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}:  ; ${:04x}, bank {}", Target::Block(self.id), self.address, self.bank)?;
+        for stmt in &self.stmts {
+            writeln!(f, "    {}", stmt)?;
+        }
+        writeln!(f, "    {}", self.terminator)
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "function {} {{", Target::Block(self.entry))?;
+        for block in self.blocks.values() {
+            write!(f, "{}", block)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (vector, entry) in &self.vectors {
+            writeln!(f, "; {:?} -> {}", vector, Target::Block(*entry))?;
+        }
+        for function in self.functions.values() {
+            writeln!(f)?;
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
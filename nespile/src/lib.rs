@@ -0,0 +1,12 @@
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod analysis;
+pub mod assembler;
+pub mod cdl;
+pub mod codegen;
+pub mod cpu;
+pub mod ir;
+pub mod mapper;
+pub mod output;
+pub mod parser;
+pub mod symbols;
@@ -1,11 +1,14 @@
-use std::{fs::File, io::Write};
+use std::{fs::{self, File}, io::Write, path::Path};
 
-use clap::{Parser, command};
-use parser::NesProgram;
-
-
-
-mod parser;
+use clap::{Parser, ValueEnum};
+use nespile::analysis::callgraph::CallGraph;
+use nespile::analysis::cfg::ControlFlowGraph;
+use nespile::cdl::CodeDataLog;
+use nespile::codegen;
+use nespile::ir;
+use nespile::output::{asm6, ca65, dot};
+use nespile::parser::{self, NesProgram};
+use nespile::symbols::{self, SymbolTable};
 
 
 #[derive(Parser, Debug)]
@@ -17,6 +20,68 @@ struct Program {
     /// Path to output the parsed program to (optional)
     #[arg(short, long)]
     output_path: Option<String>,
+
+    /// Symbol file (.nl, .mlb or .dbg) to name addresses with; may be repeated
+    #[arg(short, long = "symbols")]
+    symbol_paths: Vec<String>,
+
+    /// FCEUX or Mesen code/data log (.cdl) to seed code and data from
+    #[arg(long)]
+    cdl: Option<String>,
+
+    /// Path to write the disassembly's own code/data log to (.cdl)
+    #[arg(long)]
+    export_cdl: Option<String>,
+
+    /// Path to export the merged symbol table to (.nl, .mlb or .dbg)
+    #[arg(long)]
+    export_symbols: Option<String>,
+
+    /// Directory to write a Graphviz control-flow graph of each function to, one .dot file each
+    #[arg(long)]
+    dot_dir: Option<String>,
+
+    /// Path to write a single Graphviz control-flow graph of every function to
+    #[arg(long)]
+    dot: Option<String>,
+
+    /// Path to write a report of each function's callers, callees and interrupt vectors to
+    #[arg(long)]
+    call_graph: Option<String>,
+
+    /// Path to write every function lifted to the intermediate representation to
+    #[arg(long)]
+    ir: Option<String>,
+
+    /// Directory to write a Rust crate of every function, transpiled against nespile_runtime, to
+    #[arg(long)]
+    rust: Option<String>,
+
+    /// Path of the nespile_runtime for the Rust crate to depend on, relative to the crate, instead of the one nespile was built from
+    #[arg(long, requires = "rust")]
+    runtime_path: Option<String>,
+
+    /// Path to write C99 source of every function to, alongside a header of the same name
+    #[arg(long)]
+    c: Option<String>,
+
+    /// Path to write textual LLVM IR of every function to (.ll)
+    #[arg(long)]
+    llvm: Option<String>,
+
+    /// Path to write a WebAssembly text module of every function to (.wat)
+    #[arg(long)]
+    wat: Option<String>,
+
+    /// Write source for this assembler instead, alongside the CHR-ROM it includes (and a linker config for ca65)
+    #[arg(short, long, value_enum)]
+    assembler: Option<Assembler>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Assembler {
+    Ca65,
+    Asm6,
 }
 
 fn main() {
@@ -26,13 +91,131 @@ fn main() {
         .expect("Failed to parse ROM file");
     println!("{:?}", rom);
 
-    let program = NesProgram::try_from(&rom) 
-        .expect("Error parsing prgrom");
+    let cdl = args.cdl.map(|path| {
+        CodeDataLog::load(path, rom.prgrom().len(), rom.chrrom_data.len())
+            .expect("Failed to load code/data log")
+    });
+    let program = match &cdl {
+        Some(cdl) => NesProgram::with_code_data_log(&rom, cdl),
+        None => NesProgram::try_from(&rom),
+    }.expect("Error parsing prgrom");
+    for warning in &program.warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    if let Some(cdl) = &cdl {
+        let (logged, reached) = cdl.coverage(&program);
+        eprintln!("disassembled {} of {} bytes logged as code", reached, logged);
+    }
+    if let Some(path) = args.export_cdl {
+        CodeDataLog::from_program(&program, rom.chrrom_data.len()).save(path)
+            .expect("Failed to write code/data log");
+    }
+
+    let mut symbol_table = SymbolTable::generate(&program);
+    for path in &args.symbol_paths {
+        let user_symbols = symbols::load(path, &program)
+            .expect("Failed to load symbol file");
+        symbol_table.merge(user_symbols);
+    }
+    if let Some(path) = args.export_symbols {
+        symbols::save(&symbol_table, path, &program)
+            .expect("Failed to export symbols");
+    }
+
+    let graph_outputs = [&args.dot_dir, &args.dot, &args.call_graph, &args.ir, &args.rust, &args.c, &args.llvm, &args.wat];
+    if graph_outputs.iter().any(|path| path.is_some()) {
+        let cfg = ControlFlowGraph::build(&program);
+        if let Some(dir) = args.dot_dir {
+            fs::create_dir_all(&dir)
+                .expect("Failed to create graph directory");
+            for (file_name, graph) in dot::function_graphs(&cfg, &program, &symbol_table) {
+                fs::write(Path::new(&dir).join(file_name), graph)
+                    .expect("Failed to write graph");
+            }
+        }
+        if let Some(path) = args.dot {
+            fs::write(path, dot::program_graph(&cfg, &program, &symbol_table))
+                .expect("Failed to write graph");
+        }
+        if let Some(path) = args.call_graph {
+            fs::write(path, CallGraph::build(&cfg).report(&cfg, &symbol_table))
+                .expect("Failed to write call graph");
+        }
+        let lifted = ir::lift_program(&cfg);
+        if let Some(path) = args.ir {
+            fs::write(path, lifted.to_string())
+                .expect("Failed to write IR");
+        }
+        if let Some(dir) = args.rust {
+            let name = crate_name(&args.rom_path);
+            let output = codegen::rust::generate(&lifted, &symbol_table, &name, args.runtime_path.as_deref());
+            let dir = Path::new(&dir);
+            fs::create_dir_all(dir.join("src"))
+                .expect("Failed to create crate directory");
+            fs::write(dir.join("Cargo.toml"), output.manifest)
+                .and_then(|_| fs::write(dir.join("src/lib.rs"), output.lib))
+                .and_then(|_| fs::write(dir.join("src/functions.rs"), output.functions))
+                .expect("Failed to write Rust crate");
+        }
+        if let Some(path) = args.c {
+            let path = Path::new(&path).with_extension("c");
+            let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("nes");
+            let output = codegen::c::generate(&lifted, &symbol_table, name);
+            fs::write(path.with_extension("h"), output.header)
+                .and_then(|_| fs::write(&path, output.source))
+                .expect("Failed to write C source");
+        }
+        if let Some(path) = args.llvm {
+            fs::write(path, codegen::llvm::generate(&lifted, &symbol_table))
+                .expect("Failed to write LLVM IR");
+        }
+        if let Some(path) = args.wat {
+            fs::write(path, codegen::wasm::generate(&lifted, &symbol_table))
+                .expect("Failed to write WebAssembly");
+        }
+    }
 
-    if let Some(output_path) = args.output_path {
+    let Some(output_path) = args.output_path else {
+        return;
+    };
+    let Some(assembler) = args.assembler else {
         let mut output_file = File::create(output_path)
             .expect("Failed to create output file");
-        write!(output_file, "{}\n", program.to_source_string(rom.header.prgrom_size as usize))
+        writeln!(output_file, "{}", program.to_source_string_with(&symbol_table))
             .expect("Failed to write to output file");
+        return;
+    };
+
+    let output_path = Path::new(&output_path);
+    let chr_path = output_path.with_extension("chr");
+    let chr_name = chr_path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let rom_bytes = fs::read(&args.rom_path)
+        .expect("Failed to read ROM file");
+    let output = match assembler {
+        Assembler::Ca65 => ca65::generate(&rom_bytes, &program, &symbol_table, chr_name),
+        Assembler::Asm6 => asm6::generate(&rom_bytes, &program, &symbol_table, chr_name),
+    };
+    fs::write(output_path, output.source)
+        .expect("Failed to write to output file");
+    if !output.chr.is_empty() {
+        fs::write(&chr_path, output.chr)
+            .expect("Failed to write CHR-ROM file");
+    }
+    if let Some(linker_config) = output.linker_config {
+        fs::write(output_path.with_extension("cfg"), linker_config)
+            .expect("Failed to write linker config");
+    }
+}
+
+/// A crate name from the ROM's file name.
+fn crate_name(rom_path: &str) -> String {
+    let stem = Path::new(rom_path).file_stem().and_then(|stem| stem.to_str()).unwrap_or("rom");
+    let name: String = stem.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    match name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        true => name,
+        false => format!("rom_{}", name),
     }
 }
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::parser::rom::NesHeader;
+
+pub use axrom::AxRom;
+pub use cnrom::CnRom;
+pub use fallback::Fallback;
+pub use mmc1::Mmc1;
+pub use mmc3::Mmc3;
+pub use nrom::NRom;
+pub use uxrom::UxRom;
+
+
+
+pub mod axrom;
+pub mod cnrom;
+pub mod fallback;
+pub mod mmc1;
+pub mod mmc3;
+pub mod nrom;
+pub mod uxrom;
+
+
+
+/// First CPU address of cartridge PRG-ROM space.
+pub const PRGROM_START: u16 = 0x8000;
+
+
+
+#[derive(Error, Debug)]
+pub enum MapperError {
+    #[error("Unsupported mapper {0}")]
+    Unsupported(u16),
+}
+
+/// A byte of PRG-ROM, identified by its bank and its offset into PRG-ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PrgLocation {
+    pub bank: usize,
+    pub prg_offset: usize,
+}
+
+/// Which PRG-ROM bank is currently selected in each switchable CPU address window.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BankContext {
+    selected: BTreeMap<u16, usize>,
+}
+impl BankContext {
+    pub fn select(&mut self, window: u16, bank: usize) {
+        self.selected.insert(window, bank);
+    }
+    /// Forgets the bank in a window, e.g. after a write of an unknown value.
+    pub fn deselect(&mut self, window: u16) {
+        self.selected.remove(&window);
+    }
+    pub fn selected(&self, window: u16) -> Option<usize> {
+        self.selected.get(&window).copied()
+    }
+}
+
+/// Shadow copy of write-only mapper state that changes how later register writes are interpreted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MapperRegisters {
+    /// Serial shift register contents (MMC1), `None` once an unknown bit was shifted in.
+    pub shift: Option<u8>,
+    pub shift_count: u8,
+    /// Last value written to the bank select register (MMC3).
+    pub bank_select: Option<u8>,
+}
+
+/// The effect of a CPU write on the mapper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterWrite {
+    /// The address is not a mapper register.
+    NotRegister,
+    /// A register write that does not change PRG-ROM banking.
+    Other,
+    /// Selects a PRG-ROM bank into a CPU address window; the bank is unknown if the written value is.
+    Prg { window: u16, bank: Option<usize> },
+}
+
+
+
+/// Maps PRG-ROM banks into the CPU address space, per https://www.nesdev.org/wiki/Mapper
+pub trait Mapper: std::fmt::Debug {
+    /// iNES mapper number.
+    fn number(&self) -> u16;
+    fn name(&self) -> &'static str;
+
+    /// Total PRG-ROM size in bytes.
+    fn prg_size(&self) -> usize;
+    /// Size in bytes of the smallest independently switchable PRG-ROM bank.
+    fn prg_bank_size(&self) -> usize;
+    /// CPU address at which a bank is placed when it is switched in.
+    fn bank_window(&self, bank: usize) -> u16;
+    /// The bank that is always mapped at a CPU address, regardless of bank switching.
+    fn fixed_bank(&self, addr: u16) -> Option<usize>;
+
+    /// Interprets a write of `value` (if known) to `addr`.
+    fn register_write(&self, addr: u16, value: Option<u8>, registers: &mut MapperRegisters) -> RegisterWrite;
+
+    /// Banks selected at power-on, beyond the fixed ones.
+    fn power_on_context(&self) -> BankContext {
+        BankContext::default()
+    }
+
+    fn prg_bank_count(&self) -> usize {
+        (self.prg_size() / self.prg_bank_size()).max(1)
+    }
+    /// First CPU address of the bank-sized window containing `addr`.
+    fn window_start(&self, addr: u16) -> u16 {
+        let bank_size = self.prg_bank_size().min(0x8000) as u16;
+        addr & !(bank_size - 1)
+    }
+    /// Resolves a CPU address to a PRG-ROM location, given the currently selected banks.
+    fn resolve(&self, addr: u16, banks: &BankContext) -> Option<PrgLocation> {
+        if addr < PRGROM_START {
+            return None;
+        }
+
+        let bank = self.fixed_bank(addr)
+            .or_else(|| banks.selected(self.window_start(addr)))?;
+        let prg_offset = bank * self.prg_bank_size() + (addr as usize & (self.prg_bank_size() - 1));
+        (prg_offset < self.prg_size()).then_some(PrgLocation { bank, prg_offset })
+    }
+    /// The bank containing a PRG-ROM offset.
+    fn location(&self, prg_offset: usize) -> PrgLocation {
+        PrgLocation { bank: prg_offset / self.prg_bank_size(), prg_offset }
+    }
+    /// The CPU address a PRG-ROM offset is placed at when its bank is in its default window.
+    fn cpu_address(&self, prg_offset: usize) -> u16 {
+        let bank = prg_offset / self.prg_bank_size();
+        self.bank_window(bank) + (prg_offset % self.prg_bank_size()) as u16
+    }
+}
+
+/// Creates the mapper declared by a ROM header.
+pub fn for_header(header: &NesHeader) -> Result<Box<dyn Mapper>, MapperError> {
+    let prg_size = header.prgrom_size;
+    match header.mapper_number() {
+        0 => Ok(Box::new(NRom::new(prg_size))),
+        1 => Ok(Box::new(Mmc1::new(prg_size))),
+        2 => Ok(Box::new(UxRom::new(prg_size))),
+        3 => Ok(Box::new(CnRom::new(prg_size))),
+        4 => Ok(Box::new(Mmc3::new(prg_size))),
+        7 => Ok(Box::new(AxRom::new(prg_size))),
+        n => Err(MapperError::Unsupported(n)),
+    }
+}
@@ -0,0 +1,54 @@
+use crate::mapper::{BankContext, Mapper, MapperRegisters, RegisterWrite, PRGROM_START};
+
+
+
+const BANK_SIZE: usize = 0x8000;
+const WINDOW: u16 = 0x8000;
+
+/// Mapper 7: a single switchable 32KB bank at $8000.
+///
+/// No bank is fixed; the last bank is assumed selected at power-on,
+/// since most games place a reset stub in every bank.
+#[derive(Debug, Clone)]
+pub struct AxRom {
+    prg_size: usize,
+}
+impl AxRom {
+    pub fn new(prg_size: usize) -> Self {
+        AxRom { prg_size }
+    }
+}
+impl Mapper for AxRom {
+    fn number(&self) -> u16 { 7 }
+    fn name(&self) -> &'static str { "AxROM" }
+
+    fn prg_size(&self) -> usize {
+        self.prg_size
+    }
+    fn prg_bank_size(&self) -> usize {
+        BANK_SIZE
+    }
+    fn bank_window(&self, _bank: usize) -> u16 {
+        WINDOW
+    }
+    fn fixed_bank(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    /// Any write to $8000-$FFFF selects the 32KB bank with bits 0-2.
+    fn register_write(&self, addr: u16, value: Option<u8>, _registers: &mut MapperRegisters) -> RegisterWrite {
+        if addr < PRGROM_START {
+            return RegisterWrite::NotRegister;
+        }
+        RegisterWrite::Prg {
+            window: WINDOW,
+            bank: value.map(|v| (v & 0x07) as usize % self.prg_bank_count()),
+        }
+    }
+
+    fn power_on_context(&self) -> BankContext {
+        let mut banks = BankContext::default();
+        banks.select(WINDOW, self.prg_bank_count() - 1);
+        banks
+    }
+}
@@ -0,0 +1,36 @@
+use crate::mapper::{Mapper, MapperRegisters, NRom, RegisterWrite, PRGROM_START};
+
+
+
+/// Mapper 3: NROM-like PRG-ROM with switchable CHR-ROM.
+#[derive(Debug, Clone)]
+pub struct CnRom {
+    prg: NRom,
+}
+impl CnRom {
+    pub fn new(prg_size: usize) -> Self {
+        CnRom { prg: NRom::new(prg_size) }
+    }
+}
+impl Mapper for CnRom {
+    fn number(&self) -> u16 { 3 }
+    fn name(&self) -> &'static str { "CNROM" }
+
+    fn prg_size(&self) -> usize {
+        self.prg.prg_size()
+    }
+    fn prg_bank_size(&self) -> usize {
+        self.prg.prg_bank_size()
+    }
+    fn bank_window(&self, bank: usize) -> u16 {
+        self.prg.bank_window(bank)
+    }
+    fn fixed_bank(&self, addr: u16) -> Option<usize> {
+        self.prg.fixed_bank(addr)
+    }
+
+    /// Any write to $8000-$FFFF selects an 8KB CHR-ROM bank.
+    fn register_write(&self, addr: u16, _value: Option<u8>, _registers: &mut MapperRegisters) -> RegisterWrite {
+        if addr >= PRGROM_START { RegisterWrite::Other } else { RegisterWrite::NotRegister }
+    }
+}
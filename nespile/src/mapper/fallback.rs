@@ -0,0 +1,39 @@
+use crate::mapper::{Mapper, MapperRegisters, RegisterWrite, PRGROM_START};
+
+
+
+/// Stand-in for a mapper nespile doesn't support: the last 32KB of PRG-ROM fixed at $8000.
+///
+/// Most mappers power on with the reset vector's bank at the top of the address space, so
+/// this reaches at least the startup code. Bank switches aren't followed.
+#[derive(Debug, Clone)]
+pub struct Fallback {
+    number: u16,
+    prg_size: usize,
+}
+impl Fallback {
+    pub fn new(number: u16, prg_size: usize) -> Self {
+        Fallback { number, prg_size }
+    }
+}
+impl Mapper for Fallback {
+    fn number(&self) -> u16 { self.number }
+    fn name(&self) -> &'static str { "unsupported" }
+
+    fn prg_size(&self) -> usize {
+        self.prg_size
+    }
+    fn prg_bank_size(&self) -> usize {
+        self.prg_size.next_power_of_two().clamp(0x2000, 0x8000)
+    }
+    fn bank_window(&self, _bank: usize) -> u16 {
+        (0x10000 - self.prg_bank_size()) as u16
+    }
+    fn fixed_bank(&self, addr: u16) -> Option<usize> {
+        (addr >= PRGROM_START).then(|| self.prg_bank_count() - 1)
+    }
+
+    fn register_write(&self, _addr: u16, _value: Option<u8>, _registers: &mut MapperRegisters) -> RegisterWrite {
+        RegisterWrite::NotRegister
+    }
+}
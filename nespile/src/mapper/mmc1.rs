@@ -0,0 +1,74 @@
+use crate::mapper::{Mapper, MapperRegisters, RegisterWrite, PRGROM_START};
+
+
+
+const BANK_SIZE: usize = 0x4000;
+const SWITCHABLE_WINDOW: u16 = 0x8000;
+const FIXED_WINDOW: u16 = 0xc000;
+const PRG_BANK_REGISTER: u16 = 0xe000;
+const SHIFT_REGISTER_BITS: u8 = 5;
+
+/// Mapper 1: MMC1 (SxROM).
+///
+/// Assumes PRG mode 3 (the power-on mode): switchable 16KB bank at $8000, last bank fixed at $C000.
+#[derive(Debug, Clone)]
+pub struct Mmc1 {
+    prg_size: usize,
+}
+impl Mmc1 {
+    pub fn new(prg_size: usize) -> Self {
+        Mmc1 { prg_size }
+    }
+}
+impl Mapper for Mmc1 {
+    fn number(&self) -> u16 { 1 }
+    fn name(&self) -> &'static str { "MMC1" }
+
+    fn prg_size(&self) -> usize {
+        self.prg_size
+    }
+    fn prg_bank_size(&self) -> usize {
+        BANK_SIZE
+    }
+    fn bank_window(&self, bank: usize) -> u16 {
+        if bank + 1 == self.prg_bank_count() { FIXED_WINDOW } else { SWITCHABLE_WINDOW }
+    }
+    fn fixed_bank(&self, addr: u16) -> Option<usize> {
+        (addr >= FIXED_WINDOW).then(|| self.prg_bank_count() - 1)
+    }
+
+    /// Registers are loaded serially, one bit per write; the fifth write commits the
+    /// value to the register selected by bits 13-14 of its address.
+    fn register_write(&self, addr: u16, value: Option<u8>, registers: &mut MapperRegisters) -> RegisterWrite {
+        if addr < PRGROM_START {
+            return RegisterWrite::NotRegister;
+        }
+        if value.is_some_and(|v| v & 0x80 != 0) {
+            registers.shift = Some(0);
+            registers.shift_count = 0;
+            return RegisterWrite::Other;
+        }
+
+        if registers.shift_count == 0 {
+            registers.shift = Some(0);
+        }
+        registers.shift = registers.shift
+            .zip(value)
+            .map(|(shift, v)| shift | ((v & 0x01) << registers.shift_count));
+        registers.shift_count += 1;
+        if registers.shift_count < SHIFT_REGISTER_BITS {
+            return RegisterWrite::Other;
+        }
+
+        let committed = registers.shift;
+        registers.shift_count = 0;
+        if addr >= PRG_BANK_REGISTER {
+            RegisterWrite::Prg {
+                window: SWITCHABLE_WINDOW,
+                bank: committed.map(|v| (v & 0x0f) as usize % self.prg_bank_count()),
+            }
+        } else {
+            RegisterWrite::Other
+        }
+    }
+}
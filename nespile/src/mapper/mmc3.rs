@@ -0,0 +1,76 @@
+use crate::mapper::{Mapper, MapperRegisters, RegisterWrite, PRGROM_START};
+
+
+
+const BANK_SIZE: usize = 0x2000;
+/// Registers past $A000 control mirroring, PRG-RAM and IRQs.
+const BANKING_REGISTERS_END: u16 = 0xa000;
+const R6_WINDOW: u16 = 0x8000;
+const R7_WINDOW: u16 = 0xa000;
+
+/// Mapper 4: MMC3 (TxROM).
+///
+/// Assumes PRG mode 0: switchable 8KB banks at $8000 (R6) and $A000 (R7),
+/// second-last bank fixed at $C000 and last bank fixed at $E000.
+/// Switchable banks are placed at $8000 unless analysis shows otherwise.
+#[derive(Debug, Clone)]
+pub struct Mmc3 {
+    prg_size: usize,
+}
+impl Mmc3 {
+    pub fn new(prg_size: usize) -> Self {
+        Mmc3 { prg_size }
+    }
+}
+impl Mapper for Mmc3 {
+    fn number(&self) -> u16 { 4 }
+    fn name(&self) -> &'static str { "MMC3" }
+
+    fn prg_size(&self) -> usize {
+        self.prg_size
+    }
+    fn prg_bank_size(&self) -> usize {
+        BANK_SIZE
+    }
+    fn bank_window(&self, bank: usize) -> u16 {
+        let count = self.prg_bank_count();
+        match bank {
+            b if b + 1 == count => 0xe000,
+            b if b + 2 == count => 0xc000,
+            _ => R6_WINDOW,
+        }
+    }
+    fn fixed_bank(&self, addr: u16) -> Option<usize> {
+        let count = self.prg_bank_count();
+        match addr {
+            0xe000..=0xffff => Some(count - 1),
+            0xc000..=0xdfff => Some(count.saturating_sub(2)),
+            _ => None,
+        }
+    }
+
+    /// Even addresses select which bank register the following odd-address write loads.
+    fn register_write(&self, addr: u16, value: Option<u8>, registers: &mut MapperRegisters) -> RegisterWrite {
+        if addr < PRGROM_START {
+            return RegisterWrite::NotRegister;
+        }
+        if addr >= BANKING_REGISTERS_END {
+            return RegisterWrite::Other;
+        }
+        if addr & 0x01 == 0 {
+            registers.bank_select = value;
+            return RegisterWrite::Other;
+        }
+
+        let window = match registers.bank_select.map(|select| select & 0x07) {
+            Some(6) => R6_WINDOW,
+            Some(7) => R7_WINDOW,
+            Some(_) => return RegisterWrite::Other,
+            None => return RegisterWrite::Prg { window: R6_WINDOW, bank: None },
+        };
+        RegisterWrite::Prg {
+            window,
+            bank: value.map(|v| v as usize % self.prg_bank_count()),
+        }
+    }
+}
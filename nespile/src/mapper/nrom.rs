@@ -0,0 +1,37 @@
+use crate::mapper::{Mapper, MapperRegisters, RegisterWrite, PRGROM_START};
+
+
+
+/// Mapper 0: 16KB or 32KB of PRG-ROM with no bank switching.
+///
+/// A 16KB PRG-ROM is mirrored into both $8000 and $C000.
+#[derive(Debug, Clone)]
+pub struct NRom {
+    prg_size: usize,
+}
+impl NRom {
+    pub fn new(prg_size: usize) -> Self {
+        NRom { prg_size }
+    }
+}
+impl Mapper for NRom {
+    fn number(&self) -> u16 { 0 }
+    fn name(&self) -> &'static str { "NROM" }
+
+    fn prg_size(&self) -> usize {
+        self.prg_size
+    }
+    fn prg_bank_size(&self) -> usize {
+        self.prg_size.next_power_of_two().clamp(0x2000, 0x8000)
+    }
+    fn bank_window(&self, _bank: usize) -> u16 {
+        (0x10000 - self.prg_bank_size()) as u16
+    }
+    fn fixed_bank(&self, addr: u16) -> Option<usize> {
+        (addr >= PRGROM_START).then_some(0)
+    }
+
+    fn register_write(&self, _addr: u16, _value: Option<u8>, _registers: &mut MapperRegisters) -> RegisterWrite {
+        RegisterWrite::NotRegister
+    }
+}
@@ -0,0 +1,46 @@
+use crate::mapper::{Mapper, MapperRegisters, RegisterWrite, PRGROM_START};
+
+
+
+const BANK_SIZE: usize = 0x4000;
+const SWITCHABLE_WINDOW: u16 = 0x8000;
+const FIXED_WINDOW: u16 = 0xc000;
+
+/// Mapper 2: switchable 16KB bank at $8000, last 16KB bank fixed at $C000.
+#[derive(Debug, Clone)]
+pub struct UxRom {
+    prg_size: usize,
+}
+impl UxRom {
+    pub fn new(prg_size: usize) -> Self {
+        UxRom { prg_size }
+    }
+}
+impl Mapper for UxRom {
+    fn number(&self) -> u16 { 2 }
+    fn name(&self) -> &'static str { "UxROM" }
+
+    fn prg_size(&self) -> usize {
+        self.prg_size
+    }
+    fn prg_bank_size(&self) -> usize {
+        BANK_SIZE
+    }
+    fn bank_window(&self, bank: usize) -> u16 {
+        if bank + 1 == self.prg_bank_count() { FIXED_WINDOW } else { SWITCHABLE_WINDOW }
+    }
+    fn fixed_bank(&self, addr: u16) -> Option<usize> {
+        (addr >= FIXED_WINDOW).then(|| self.prg_bank_count() - 1)
+    }
+
+    /// Any write to $8000-$FFFF selects the 16KB bank at $8000.
+    fn register_write(&self, addr: u16, value: Option<u8>, _registers: &mut MapperRegisters) -> RegisterWrite {
+        if addr < PRGROM_START {
+            return RegisterWrite::NotRegister;
+        }
+        RegisterWrite::Prg {
+            window: SWITCHABLE_WINDOW,
+            bank: value.map(|v| v as usize % self.prg_bank_count()),
+        }
+    }
+}
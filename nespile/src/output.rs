@@ -0,0 +1,329 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use crate::mapper::PRGROM_START;
+use crate::parser::address_mode::AddressMode;
+use crate::parser::disassembler::{ByteKind, NMI_VECTOR};
+use crate::parser::instruction::Instruction;
+use crate::parser::opcodes::is_official_opcode;
+use crate::parser::NesProgram;
+use crate::symbols::SymbolTable;
+
+pub mod asm6;
+pub mod ca65;
+pub mod dot;
+
+
+
+const DATA_BYTES_PER_LINE: usize = 8;
+/// Size of the NMI, reset and IRQ vector table.
+const VECTOR_TABLE_SIZE: usize = 6;
+
+
+
+/// Source for an assembler which reassembles to the original ROM file.
+#[derive(Debug, Clone)]
+pub struct AssemblyOutput {
+    pub source: String,
+    /// Linker configuration, for assemblers that need one.
+    pub linker_config: Option<String>,
+    /// CHR-ROM and anything else following PRG-ROM, included by the source with `.incbin`.
+    pub chr: Vec<u8>,
+}
+
+/// Where the dialects of the supported assemblers differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Ca65,
+    Asm6,
+}
+impl Syntax {
+    fn byte_directive(&self) -> &'static str {
+        match self {
+            Syntax::Ca65 => ".byte",
+            Syntax::Asm6 => ".db",
+        }
+    }
+    fn word_directive(&self) -> &'static str {
+        match self {
+            Syntax::Ca65 => ".word",
+            Syntax::Asm6 => ".dw",
+        }
+    }
+    /// Prefix forcing absolute addressing of a zeropage address, if the assembler has one.
+    fn absolute_prefix(&self) -> Option<&'static str> {
+        match self {
+            Syntax::Ca65 => Some("a:"),
+            Syntax::Asm6 => None,
+        }
+    }
+}
+
+/// A line-sized piece of PRG-ROM.
+#[derive(Debug, Clone)]
+enum Unit<'a> {
+    Instruction(&'a Instruction),
+    Bytes(Range<usize>),
+    /// The NMI, reset and IRQ vectors.
+    Vectors(usize),
+}
+impl Unit<'_> {
+    fn start(&self) -> usize {
+        match self {
+            Unit::Instruction(instruction) => instruction.prg_offset,
+            Unit::Bytes(range) => range.start,
+            Unit::Vectors(offset) => *offset,
+        }
+    }
+}
+
+/// The program split into banks of units, with the labels and equates that can be defined for it.
+struct Layout<'a> {
+    program: &'a NesProgram,
+    syntax: Syntax,
+    /// CPU address each bank is assembled at.
+    bank_addresses: Vec<u16>,
+    banks: Vec<Vec<Unit<'a>>>,
+    labels: HashMap<usize, String>,
+    equates: Vec<(u16, String)>,
+}
+impl<'a> Layout<'a> {
+    fn new(program: &'a NesProgram, symbols: &SymbolTable, syntax: Syntax) -> Self {
+        let mut layout = Layout {
+            program,
+            syntax,
+            bank_addresses: Self::bank_addresses(program),
+            banks: vec![],
+            labels: HashMap::new(),
+            equates: vec![],
+        };
+        layout.banks = (0..layout.bank_addresses.len())
+            .map(|bank| layout.bank_units(bank, symbols))
+            .collect();
+
+        // Assemblers need names to be unique, which generated names from different banks may not be.
+        let mut used = HashSet::new();
+        let mut unique_name = |name: &str, bank: Option<usize>| {
+            let mut unique = name.to_string();
+            let mut suffix = 1;
+            while used.contains(&unique) {
+                unique = match bank {
+                    Some(bank) if suffix == 1 => format!("{}_b{}", name, bank),
+                    _ => format!("{}_{}", name, suffix),
+                };
+                suffix += 1;
+            }
+            used.insert(unique.clone());
+            unique
+        };
+        for symbol in symbols.iter().filter(|symbol| symbol.location.is_none() && symbol.address < PRGROM_START) {
+            layout.equates.push((symbol.address, unique_name(&symbol.name, None)));
+        }
+        let unit_starts: Vec<usize> = layout.banks.iter().flatten().map(Unit::start).collect();
+        for offset in unit_starts {
+            if let Some(symbol) = symbols.rom_symbol(offset) {
+                let bank = offset / program.mapper.prg_bank_size();
+                layout.labels.insert(offset, unique_name(&symbol.name, Some(bank)));
+            }
+        }
+        layout
+    }
+
+    /// Assembles each bank where most of its code was found to run, or else in its default window.
+    fn bank_addresses(program: &NesProgram) -> Vec<u16> {
+        let bank_size = program.mapper.prg_bank_size();
+        let bank_count = program.prgrom.len().div_ceil(bank_size);
+        (0..bank_count)
+            .map(|bank| {
+                let mut counts = HashMap::<u16, usize>::new();
+                for instruction in program.instructions.range(bank * bank_size..(bank + 1) * bank_size).map(|(_, i)| i) {
+                    let start = instruction.address.wrapping_sub((instruction.prg_offset % bank_size) as u16);
+                    *counts.entry(start).or_default() += 1;
+                }
+                counts.into_iter()
+                    .max_by_key(|(start, count)| (*count, std::cmp::Reverse(*start)))
+                    .map_or_else(|| program.mapper.cpu_address(bank * bank_size), |(start, _)| start)
+            })
+            .collect()
+    }
+    fn bank_range(&self, bank: usize) -> Range<usize> {
+        let bank_size = self.program.mapper.prg_bank_size();
+        bank * bank_size..((bank + 1) * bank_size).min(self.program.prgrom.len())
+    }
+    /// CPU address a PRG-ROM offset is assembled at.
+    fn address_of(&self, prg_offset: usize) -> u16 {
+        let bank_size = self.program.mapper.prg_bank_size();
+        self.bank_addresses[prg_offset / bank_size].wrapping_add((prg_offset % bank_size) as u16)
+    }
+
+    fn bank_units(&self, bank: usize, symbols: &SymbolTable) -> Vec<Unit<'a>> {
+        let program = self.program;
+        let range = self.bank_range(bank);
+        let vectors = program.mapper.resolve(NMI_VECTOR, &program.mapper.power_on_context())
+            .map(|location| location.prg_offset)
+            .filter(|offset| *offset + VECTOR_TABLE_SIZE <= range.end && range.contains(offset))
+            .filter(|offset| program.byte_kinds[*offset..*offset + VECTOR_TABLE_SIZE].iter().all(|kind| *kind == ByteKind::Data));
+
+        let mut units = vec![];
+        let mut run: Option<Range<usize>> = None;
+        let mut offset = range.start;
+        while offset < range.end {
+            let instruction = program.instruction_at_offset(offset)
+                .filter(|instruction| instruction.prg_offset + instruction.size() <= range.end && self.encodable(instruction));
+            let unit = if let Some(instruction) = instruction {
+                Some(Unit::Instruction(instruction))
+            } else if vectors == Some(offset) {
+                Some(Unit::Vectors(offset))
+            } else {
+                None
+            };
+
+            match unit {
+                Some(unit) => {
+                    units.extend(run.take().map(Unit::Bytes));
+                    offset += match &unit {
+                        Unit::Instruction(instruction) => instruction.size(),
+                        _ => VECTOR_TABLE_SIZE,
+                    };
+                    units.push(unit);
+                },
+                None => {
+                    let breaks = run.as_ref().is_some_and(|run| {
+                        run.len() == DATA_BYTES_PER_LINE || symbols.rom_symbol(offset).is_some()
+                    });
+                    if breaks {
+                        units.extend(run.take().map(Unit::Bytes));
+                    }
+                    run.get_or_insert(offset..offset).end = offset + 1;
+                    offset += 1;
+                },
+            }
+        }
+        units.extend(run.take().map(Unit::Bytes));
+        units
+    }
+
+    /// Whether the assembler is certain to encode the instruction back to the same bytes.
+    fn encodable(&self, instruction: &Instruction) -> bool {
+        let opcode_byte = instruction.bytes[0];
+        // Some assemblers add a signature byte to BRK.
+        if !is_official_opcode(opcode_byte) || opcode_byte == 0x00 || instruction.size() != instruction.opcode.size() {
+            return false;
+        }
+        match instruction.opcode.argument() {
+            Some(AddressMode::Relative(offset)) => {
+                let next = self.address_of(instruction.prg_offset) as i32 + instruction.size() as i32;
+                (0..=u16::MAX as i32).contains(&(next + offset as i8 as i32))
+            },
+            Some(AddressMode::Absolute(address) | AddressMode::AbsoluteX(address) | AddressMode::AbsoluteY(address)) =>
+                address >= 0x100 || self.syntax.absolute_prefix().is_some(),
+            _ => true,
+        }
+    }
+
+    /// The label defined at a PRG-ROM location, if it's assembled at `address`.
+    fn label_at(&self, prg_offset: usize, address: u16) -> Option<&str> {
+        self.labels.get(&prg_offset)
+            .filter(|_| self.address_of(prg_offset) == address)
+            .map(String::as_str)
+    }
+    fn name_for(&self, instruction: &Instruction, address: u16) -> Option<&str> {
+        if address < PRGROM_START {
+            return self.equates.iter()
+                .find(|(equate, _)| *equate == address)
+                .map(|(_, name)| name.as_str());
+        }
+        let location = self.program.resolve_operand(instruction, address)?;
+        self.label_at(location.prg_offset, address)
+    }
+
+    fn format_instruction(&self, instruction: &Instruction) -> String {
+        let mnemonic = instruction.opcode.variant_name();
+        let operand = match instruction.opcode.argument() {
+            None | Some(AddressMode::Implied) => return mnemonic.to_string(),
+            Some(AddressMode::Relative(offset)) => {
+                let next = self.address_of(instruction.prg_offset).wrapping_add(instruction.size() as u16);
+                let target = next.wrapping_add_signed(offset as i8 as i16);
+                instruction.target
+                    .and_then(|location| self.label_at(location.prg_offset, target))
+                    .map_or_else(|| format!("${:04x}", target), str::to_string)
+            },
+            Some(mode) => match mode.address() {
+                Some(address) => {
+                    let name = self.name_for(instruction, address).map_or_else(
+                        || match mode.size() {
+                            1 => format!("${:02x}", address),
+                            _ => format!("${:04x}", address),
+                        },
+                        str::to_string,
+                    );
+                    let name = match (mode.size(), self.syntax.absolute_prefix()) {
+                        (2, Some(prefix)) if address < 0x100 => format!("{}{}", prefix, name),
+                        _ => name,
+                    };
+                    mode.format_with(&name)
+                },
+                None => mode.to_string(),
+            },
+        };
+        format!("{}   {}", mnemonic, operand)
+    }
+
+    fn format_vectors(&self, prg_offset: usize) -> String {
+        let context = self.program.mapper.power_on_context();
+        let words: Vec<String> = (0..3)
+            .map(|i| {
+                let offset = prg_offset + i * 2;
+                let address = u16::from_le_bytes([self.program.prgrom[offset], self.program.prgrom[offset + 1]]);
+                self.program.mapper.resolve(address, &context)
+                    .and_then(|location| self.label_at(location.prg_offset, address))
+                    .map_or_else(|| format!("${:04x}", address), str::to_string)
+            })
+            .collect();
+        format!("{} {}", self.syntax.word_directive(), words.join(", "))
+    }
+
+    /// Equates for the RAM and hardware register names used by the program.
+    fn equate_lines(&self) -> Vec<String> {
+        self.equates.iter()
+            .map(|(address, name)| match address {
+                0..=0xff => format!("{} = ${:02x}", name, address),
+                _ => format!("{} = ${:04x}", name, address),
+            })
+            .collect()
+    }
+    /// The labelled instructions and data of one bank.
+    fn bank_lines(&self, bank: usize) -> Vec<String> {
+        let mut lines = vec![];
+        for unit in &self.banks[bank] {
+            if let Some(label) = self.labels.get(&unit.start()) {
+                lines.push(format!("{}:", label));
+            }
+            lines.push(format!("    {}", match unit {
+                Unit::Instruction(instruction) => self.format_instruction(instruction),
+                Unit::Bytes(range) => byte_line(self.syntax, &self.program.prgrom[range.clone()]),
+                Unit::Vectors(offset) => self.format_vectors(*offset),
+            }));
+        }
+        lines
+    }
+}
+
+/// Splits a ROM file into its header (and trainer) and what follows PRG-ROM.
+fn split_rom<'r>(rom: &'r [u8], program: &NesProgram) -> (&'r [u8], &'r [u8]) {
+    let prg_end = (program.prg_file_offset + program.prgrom.len()).min(rom.len());
+    (&rom[..program.prg_file_offset.min(rom.len())], &rom[prg_end..])
+}
+
+fn byte_line(syntax: Syntax, bytes: &[u8]) -> String {
+    format!("{} {}", syntax.byte_directive(), bytes.iter()
+        .map(|b| format!("${:02x}", b))
+        .collect::<Vec<String>>()
+        .join(", "))
+}
+/// Formats raw bytes as data lines of the given syntax.
+fn data_lines(syntax: Syntax, bytes: &[u8]) -> Vec<String> {
+    bytes.chunks(DATA_BYTES_PER_LINE)
+        .map(|chunk| format!("    {}", byte_line(syntax, chunk)))
+        .collect()
+}
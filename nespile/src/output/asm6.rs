@@ -0,0 +1,36 @@
+use crate::output::{data_lines, split_rom, AssemblyOutput, Layout, Syntax};
+use crate::parser::NesProgram;
+use crate::symbols::SymbolTable;
+
+
+
+/// Generates asm6 source for the whole ROM file.
+///
+/// The header is assembled from `.org $0000`; each bank then uses `.base` to set its CPU
+/// address without padding the output, as `.org` would.
+///
+/// `chr_path` is the path the source includes [`AssemblyOutput::chr`] from.
+pub fn generate(rom: &[u8], program: &NesProgram, symbols: &SymbolTable, chr_path: &str) -> AssemblyOutput {
+    let layout = Layout::new(program, symbols, Syntax::Asm6);
+    let (header, chr) = split_rom(rom, program);
+
+    let mut lines = vec![format!("; {} (mapper {})", program.mapper.name(), program.mapper.number())];
+    lines.extend(layout.equate_lines());
+    lines.push(String::new());
+    lines.push(".org $0000".to_string());
+    lines.extend(data_lines(Syntax::Asm6, header));
+
+    for (bank, address) in layout.bank_addresses.iter().enumerate() {
+        lines.push(String::new());
+        lines.push(format!("; bank {}", bank));
+        lines.push(format!(".base ${:04x}", address));
+        lines.extend(layout.bank_lines(bank));
+    }
+    if !chr.is_empty() {
+        lines.push(String::new());
+        lines.push(format!(".incbin \"{}\"", chr_path));
+    }
+    lines.push(String::new());
+
+    AssemblyOutput { source: lines.join("\n"), linker_config: None, chr: chr.to_vec() }
+}
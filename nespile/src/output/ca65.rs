@@ -0,0 +1,49 @@
+use crate::output::{data_lines, split_rom, AssemblyOutput, Layout, Syntax};
+use crate::parser::NesProgram;
+use crate::symbols::SymbolTable;
+
+
+
+/// Generates ca65 source with a segment per PRG-ROM bank, and the ld65 configuration laying
+/// them out after the iNES header as in the original file.
+///
+/// `chr_path` is the path the source includes [`AssemblyOutput::chr`] from.
+pub fn generate(rom: &[u8], program: &NesProgram, symbols: &SymbolTable, chr_path: &str) -> AssemblyOutput {
+    let layout = Layout::new(program, symbols, Syntax::Ca65);
+    let (header, chr) = split_rom(rom, program);
+
+    let mut lines = vec![format!("; {} (mapper {})", program.mapper.name(), program.mapper.number())];
+    lines.extend(layout.equate_lines());
+    lines.push(String::new());
+    lines.push(".segment \"HEADER\"".to_string());
+    lines.extend(data_lines(Syntax::Ca65, header));
+
+    let mut memory = vec![format!("    HEADER: start = $0000, size = ${:04x}, fill = yes, file = %O;", header.len())];
+    let mut segments = vec!["    HEADER: load = HEADER, type = ro;".to_string()];
+    for (bank, address) in layout.bank_addresses.iter().enumerate() {
+        lines.push(String::new());
+        lines.push(format!(".segment \"BANK{}\"", bank));
+        lines.extend(layout.bank_lines(bank));
+
+        memory.push(format!(
+            "    PRG{}: start = ${:04x}, size = ${:04x}, fill = yes, file = %O;",
+            bank, address, layout.bank_range(bank).len(),
+        ));
+        segments.push(format!("    BANK{}: load = PRG{}, type = ro;", bank, bank));
+    }
+    if !chr.is_empty() {
+        lines.push(String::new());
+        lines.push(".segment \"CHR\"".to_string());
+        lines.push(format!("    .incbin \"{}\"", chr_path));
+
+        memory.push(format!("    CHR: start = $0000, size = ${:04x}, fill = yes, file = %O;", chr.len()));
+        segments.push("    CHR: load = CHR, type = ro;".to_string());
+    }
+    lines.push(String::new());
+
+    let linker_config = format!(
+        "MEMORY {{\n{}\n}}\n\nSEGMENTS {{\n{}\n}}\n",
+        memory.join("\n"), segments.join("\n"),
+    );
+    AssemblyOutput { source: lines.join("\n"), linker_config: Some(linker_config), chr: chr.to_vec() }
+}
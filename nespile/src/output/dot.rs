@@ -0,0 +1,147 @@
+use std::collections::{BTreeSet, HashSet};
+
+use crate::analysis::cfg::{BasicBlock, BlockId, ControlFlowGraph, EdgeKind, Function};
+use crate::cpu::ControlFlow;
+use crate::parser::NesProgram;
+use crate::symbols::SymbolTable;
+
+
+
+/// Graphviz attributes for each kind of edge.
+fn edge_style(kind: EdgeKind) -> &'static str {
+    match kind {
+        EdgeKind::Fallthrough => "color=black",
+        EdgeKind::Branch => "color=darkgreen",
+        EdgeKind::Jump => "color=blue",
+        EdgeKind::Indirect => "color=red",
+        EdgeKind::Call => "color=orange, style=dashed",
+        EdgeKind::Return => "color=gray, style=dotted",
+    }
+}
+/// Attributes for the edge from a block ending in `JMP (ptr)` to its targets, if none were recovered.
+const INDIRECT_STYLE: &str = "color=red, style=dashed";
+
+fn node_id(block: BlockId) -> String {
+    format!("b{:x}", block)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Name of a block, from its symbol or else its address.
+fn block_name(block: &BasicBlock, symbols: &SymbolTable) -> String {
+    symbols.rom_symbol(block.id)
+        .map(|symbol| symbol.name.clone())
+        .unwrap_or_else(|| format!("${:04x}", block.address()))
+}
+
+/// A box listing the block's disassembly, left-aligned.
+fn block_node(block: &BasicBlock, program: &NesProgram, symbols: &SymbolTable) -> String {
+    let mut label = String::new();
+    if let Some(symbol) = symbols.rom_symbol(block.id) {
+        label.push_str(&format!("{}:\\l", escape(&symbol.name)));
+    }
+    for instruction in &block.instructions {
+        let source = instruction.format_with(|address| symbols.name_for(program, instruction, address).map(str::to_string));
+        label.push_str(&format!("${:04x}  {}\\l", instruction.address, escape(&source)));
+    }
+    format!("    {} [label=\"{}\"];", node_id(block.id), label)
+}
+
+/// Nodes for `blocks`, with an edge from each unrecovered indirect jump to a node for its unknown targets.
+fn graph_body(cfg: &ControlFlowGraph, blocks: &BTreeSet<BlockId>, program: &NesProgram, symbols: &SymbolTable) -> (Vec<String>, Vec<String>) {
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    for block in blocks.iter().map(|id| &cfg.blocks[id]) {
+        nodes.push(block_node(block, program, symbols));
+        let recovered = cfg.successors(block.id).any(|edge| edge.kind == EdgeKind::Indirect);
+        if block.exit() == ControlFlow::IndirectJump && !recovered {
+            let unknown = format!("{}_indirect", node_id(block.id));
+            nodes.push(format!("    {} [label=\"?\", shape=circle];", unknown));
+            edges.push(format!("    {} -> {} [{}];", node_id(block.id), unknown, INDIRECT_STYLE));
+        }
+    }
+    (nodes, edges)
+}
+
+fn edge_line(from: BlockId, to: &str, kind: EdgeKind) -> String {
+    format!("    {} -> {} [{}];", node_id(from), to, edge_style(kind))
+}
+
+const HEADER: &str = "    node [shape=box, fontname=monospace];";
+
+/// One function's blocks and the edges between them, with its calls drawn to the callees' names.
+pub fn function_graph(cfg: &ControlFlowGraph, function: &Function, program: &NesProgram, symbols: &SymbolTable) -> String {
+    let (mut nodes, mut edges) = graph_body(cfg, &function.blocks, program, symbols);
+    let mut callees = HashSet::new();
+    for edge in function.blocks.iter().flat_map(|block| cfg.successors(*block)) {
+        match edge.kind {
+            EdgeKind::Return => {},
+            _ if edge.kind != EdgeKind::Call && function.blocks.contains(&edge.to) =>
+                edges.push(edge_line(edge.from, &node_id(edge.to), edge.kind)),
+            // Calls, and jumps into other functions, go to a node named after the target.
+            _ => {
+                let callee = format!("{}_call", node_id(edge.to));
+                if callees.insert(edge.to) {
+                    nodes.push(format!("    {} [label=\"{}\", shape=ellipse];", callee, escape(&block_name(&cfg.blocks[&edge.to], symbols))));
+                }
+                edges.push(edge_line(edge.from, &callee, edge.kind));
+            },
+        }
+    }
+
+    let name = block_name(&cfg.blocks[&function.entry], symbols);
+    let mut lines = vec![format!("digraph \"{}\" {{", escape(&name)), HEADER.to_string()];
+    lines.extend(nodes);
+    lines.extend(edges);
+    lines.push("}".to_string());
+    lines.join("\n") + "\n"
+}
+
+/// Every function as a cluster in a single graph, with every edge between blocks.
+pub fn program_graph(cfg: &ControlFlowGraph, program: &NesProgram, symbols: &SymbolTable) -> String {
+    let mut lines = vec!["digraph program {".to_string(), HEADER.to_string()];
+    let mut drawn: BTreeSet<BlockId> = cfg.functions.keys().copied().collect();
+    for function in cfg.functions.values() {
+        // Blocks shared between functions are drawn in the first one only, unless they are another's entry.
+        let mut blocks: BTreeSet<BlockId> = function.blocks.difference(&drawn).copied().collect();
+        drawn.extend(blocks.iter().copied());
+        blocks.insert(function.entry);
+        let (nodes, edges) = graph_body(cfg, &blocks, program, symbols);
+
+        lines.push(format!("  subgraph cluster_{} {{", node_id(function.entry)));
+        lines.push(format!("    label=\"{}\";", escape(&block_name(&cfg.blocks[&function.entry], symbols))));
+        lines.extend(nodes);
+        lines.extend(edges);
+        lines.push("  }".to_string());
+    }
+
+    // Blocks not reached from any function entry.
+    let orphans: BTreeSet<BlockId> = cfg.blocks.keys().filter(|block| !drawn.contains(*block)).copied().collect();
+    let (nodes, edges) = graph_body(cfg, &orphans, program, symbols);
+    lines.extend(nodes);
+    lines.extend(edges);
+
+    lines.extend(cfg.edges.iter().map(|edge| edge_line(edge.from, &node_id(edge.to), edge.kind)));
+    lines.push("}".to_string());
+    lines.join("\n") + "\n"
+}
+
+/// A graph per function, each with a file name unique across banks.
+pub fn function_graphs(cfg: &ControlFlowGraph, program: &NesProgram, symbols: &SymbolTable) -> Vec<(String, String)> {
+    let mut names = HashSet::new();
+    cfg.functions.values()
+        .map(|function| {
+            let entry = &cfg.blocks[&function.entry];
+            let mut name = symbols.rom_symbol(entry.id)
+                .map(|symbol| symbol.name.clone())
+                .unwrap_or_else(|| format!("sub_{:04X}", entry.address()));
+            if !names.insert(name.clone()) {
+                name = format!("{}_b{}", name, entry.bank());
+                names.insert(name.clone());
+            }
+            (format!("{}.dot", name), function_graph(cfg, function, program, symbols))
+        })
+        .collect()
+}
@@ -1,14 +1,24 @@
-use std::io::Cursor;
+use std::collections::BTreeMap;
 
-use binrw::{BinRead, Error as BinError};
+use binrw::Error as BinError;
 use thiserror::Error;
 
+use crate::cdl::CodeDataLog;
+use crate::mapper::{self, BankContext, Fallback, Mapper, MapperError, PrgLocation};
+use crate::symbols::SymbolTable;
+use disassembler::{ByteKind, Disassembler, DisassemblyWarning, UnresolvedTarget, Vector};
+use disassembler::bank_switch::BankSwitch;
+use disassembler::inline_data::InlineData;
+use disassembler::jump_table::JumpTable;
+use instruction::Instruction;
 use opcodes::Opcode;
 use rom::NesFile;
 
 
 
 pub mod address_mode;
+pub mod disassembler;
+pub mod instruction;
 pub mod rom;
 pub mod opcodes;
 
@@ -18,45 +28,212 @@ pub mod opcodes;
 pub enum ProgramParseError {
     #[error(transparent)]
     BinRead(#[from] BinError),
+
+    #[error(transparent)]
+    Mapper(#[from] MapperError),
+}
+
+const DATA_BYTES_PER_LINE: usize = 8;
+
+pub enum ProgramItem<'a> {
+    Code(&'a Instruction),
+    /// A run of bytes that was never reached by control flow.
+    Data {
+        address: u16,
+        prg_offset: usize,
+        bytes: &'a [u8],
+    },
 }
 
 pub struct NesProgram {
-    pub operations: Vec<Opcode>
+    pub mapper: Box<dyn Mapper>,
+    pub prgrom: Vec<u8>,
+    /// Offset of PRG-ROM into the ROM file.
+    pub prg_file_offset: usize,
+    /// Decoded instructions, keyed by PRG-ROM offset.
+    pub instructions: BTreeMap<usize, Instruction>,
+    pub byte_kinds: Vec<ByteKind>,
+    pub vectors: Vec<(Vector, u16)>,
+    pub unresolved: Vec<UnresolvedTarget>,
+    pub bank_switches: Vec<BankSwitch>,
+    pub jump_tables: Vec<JumpTable>,
+    pub inline_data: Vec<InlineData>,
+    pub warnings: Vec<DisassemblyWarning>,
+    /// PRG-ROM offsets of the instructions at each CPU address, across all banks.
+    address_index: BTreeMap<u16, Vec<usize>>,
 }
 impl TryFrom<&NesFile> for NesProgram {
     type Error = ProgramParseError;
 
     fn try_from(file: &NesFile) -> Result<Self, Self::Error> {
-        let size = file.header.prgrom_size as u64;
-        let data = file.prgrom_data.clone();
-        let mut cursor = Cursor::new(data);
+        NesProgram::disassemble(file, None)
+    }
+}
 
-        let mut operations = Vec::new();
-        while cursor.position() < size {
-            operations.push(Opcode::read_options(&mut cursor, binrw::Endian::Little, ())?)
+impl NesProgram {
+    /// Disassembles from the interrupt vectors, then from whatever else a code/data log saw run.
+    pub fn with_code_data_log(file: &NesFile, cdl: &CodeDataLog) -> Result<Self, ProgramParseError> {
+        NesProgram::disassemble(file, Some(cdl))
+    }
+    fn disassemble(file: &NesFile, cdl: Option<&CodeDataLog>) -> Result<Self, ProgramParseError> {
+        // An unsupported mapper still gets its startup code disassembled, rather than nothing.
+        let (mapper, unsupported) = match mapper::for_header(&file.header) {
+            Ok(mapper) => (mapper, None),
+            Err(MapperError::Unsupported(number)) =>
+                (Box::new(Fallback::new(number, file.header.prgrom_size)) as Box<dyn Mapper>, Some(number)),
+        };
+        let mut disassembler = Disassembler::for_file(file, mapper.as_ref());
+        disassembler.add_vector_entry_points();
+        if let Some(cdl) = cdl {
+            disassembler.add_code_data_log(cdl);
+        }
+        let mut disassembly = disassembler.run();
+        if let Some(number) = unsupported {
+            disassembly.warnings.insert(0, DisassemblyWarning::UnsupportedMapper { number });
+        }
+
+        let mut address_index = BTreeMap::<u16, Vec<usize>>::new();
+        for instruction in disassembly.instructions.values() {
+            address_index.entry(instruction.address).or_default().push(instruction.prg_offset);
         }
 
-        Ok(NesProgram{ operations })
+        Ok(NesProgram{
+            mapper,
+            prgrom: file.prgrom().to_vec(),
+            prg_file_offset: file.prgrom_file_offset(),
+            instructions: disassembly.instructions,
+            byte_kinds: disassembly.byte_kinds,
+            vectors: disassembly.vectors,
+            unresolved: disassembly.unresolved,
+            bank_switches: disassembly.bank_switches,
+            jump_tables: disassembly.jump_tables,
+            inline_data: disassembly.inline_data,
+            warnings: disassembly.warnings,
+            address_index,
+        })
     }
-}
 
-impl NesProgram {
-    pub fn to_source_string(&self, total_size: usize) -> String {
-        let mut addr = 0x8000usize;
-        let address_width = (((total_size + addr) as f32).log2() / 4f32).ceil() as usize;
-
-        self.operations.iter()
-            .map(|op| {
-                let line = format!("${:0width$x}    {}", addr, op.to_source_string(), width = address_width);
-
-                addr += if let Some(addr_mode) = op.argument() {
-                    1 + addr_mode.size()
-                } else {
-                    1
-                };
-                return line;
-            })
-            .collect::<Vec<String>>()
-            .join("\n")
+    /// The instruction at a CPU address, preferring the lowest bank if several banks have one there.
+    pub fn instruction_at(&self, addr: u16) -> Option<&Instruction> {
+        self.instructions_at(addr).next()
+    }
+    /// Every instruction at a CPU address, one per bank that has code there.
+    pub fn instructions_at(&self, addr: u16) -> impl Iterator<Item = &Instruction> {
+        self.address_index.get(&addr)
+            .into_iter()
+            .flatten()
+            .filter_map(|offset| self.instructions.get(offset))
+    }
+    pub fn instruction_at_offset(&self, prg_offset: usize) -> Option<&Instruction> {
+        self.instructions.get(&prg_offset)
+    }
+    /// The parameters stored inline after the `JSR` at a PRG-ROM offset, if its callee reads any.
+    pub fn inline_data_after(&self, call: usize) -> Option<&InlineData> {
+        self.inline_data.iter().find(|data| data.call == call)
+    }
+
+    /// Resolves an address used by `instruction` to PRG-ROM, using its resolved jump target
+    /// or else assuming the instruction's own bank is still selected.
+    pub fn resolve_operand(&self, instruction: &Instruction, address: u16) -> Option<PrgLocation> {
+        let direct_target = instruction.branch_target().or(match instruction.opcode {
+            Opcode::JMP(_) | Opcode::JSR(_) => instruction.opcode.argument().and_then(|mode| mode.address()),
+            _ => None,
+        });
+        if direct_target == Some(address) && instruction.target.is_some() {
+            return instruction.target;
+        }
+
+        let mut banks = BankContext::default();
+        banks.select(self.mapper.window_start(instruction.address), instruction.bank);
+        self.mapper.resolve(address, &banks)
+    }
+
+    /// Instructions and unreached data in PRG-ROM order.
+    pub fn items(&self) -> Vec<ProgramItem<'_>> {
+        self.items_breaking_at(|_| false)
+    }
+    /// Like [`NesProgram::items`], but also ending runs of data at offsets where `breaks_at` is true.
+    pub fn items_breaking_at(&self, breaks_at: impl Fn(usize) -> bool) -> Vec<ProgramItem<'_>> {
+        let mut items = Vec::new();
+        let mut offset = 0;
+        while offset < self.prgrom.len() {
+            if let Some(instruction) = self.instructions.get(&offset) {
+                items.push(ProgramItem::Code(instruction));
+                offset += instruction.size();
+            } else {
+                let bank_end = (offset / self.mapper.prg_bank_size() + 1) * self.mapper.prg_bank_size();
+                let len = self.byte_kinds[offset..bank_end.min(self.prgrom.len())].iter()
+                    .take(DATA_BYTES_PER_LINE)
+                    .enumerate()
+                    .take_while(|(i, kind)| **kind == ByteKind::Data && (*i == 0 || !breaks_at(offset + i)))
+                    .count()
+                    .max(1);
+                items.push(ProgramItem::Data {
+                    address: self.mapper.cpu_address(offset),
+                    prg_offset: offset,
+                    bytes: &self.prgrom[offset..offset + len],
+                });
+                offset += len;
+            }
+        }
+        items
+    }
+
+    pub fn to_source_string(&self) -> String {
+        self.to_source_string_with(&SymbolTable::generate(self))
+    }
+    /// Renders the program with labels and operands named from `symbols`.
+    pub fn to_source_string_with(&self, symbols: &SymbolTable) -> String {
+        let banked = self.mapper.prg_bank_count() > 1;
+
+        let mut current_bank = None;
+        let mut lines = Vec::new();
+        for item in self.items_breaking_at(|offset| symbols.rom_symbol(offset).is_some()) {
+            let prg_offset = match item {
+                ProgramItem::Code(instruction) => instruction.prg_offset,
+                ProgramItem::Data { prg_offset, .. } => prg_offset,
+            };
+            let bank = prg_offset / self.mapper.prg_bank_size();
+            if banked && current_bank != Some(bank) {
+                lines.push(format!("\n; bank {}", bank));
+                current_bank = Some(bank);
+            }
+            if let Some(symbol) = symbols.rom_symbol(prg_offset) {
+                if let Some(comment) = &symbol.comment {
+                    lines.extend(comment.lines().map(|line| format!("; {}", line)));
+                }
+                lines.push(format!("{}:", symbol.name));
+            }
+
+            match item {
+                ProgramItem::Code(instruction) => {
+                    let source = instruction.format_with(|address| symbols.name_for(self, instruction, address).map(str::to_string));
+
+                    let mut comments = Vec::new();
+                    if let Some(target) = instruction.target.filter(|target| target.bank != instruction.bank) {
+                        comments.push(format!("bank {}", target.bank));
+                    }
+                    if let Some(data) = self.inline_data_after(instruction.prg_offset) {
+                        comments.push(format!("{} bytes of inline data follow", data.len));
+                    }
+                    let location = instruction.location();
+                    comments.extend(self.warnings.iter()
+                        .filter(|warning| warning.location() == Some(location))
+                        .map(|warning| format!("warning: {}", warning.message())));
+
+                    if comments.is_empty() {
+                        lines.push(format!("${:04x}    {}", instruction.address, source));
+                    } else {
+                        lines.push(format!("${:04x}    {:<16}; {}", instruction.address, source, comments.join("; ")));
+                    }
+                },
+                ProgramItem::Data { address, bytes, .. } =>
+                    lines.push(format!("${:04x}    .byte {}", address, bytes.iter()
+                        .map(|b| format!("${:02x}", b))
+                        .collect::<Vec<String>>()
+                        .join(", "))),
+            }
+        }
+        lines.join("\n")
     }
 }
@@ -1,9 +1,15 @@
-use binrw::{io, BinRead};
-use nespile_macros::BinReadAddressMode;
+use binrw::{io, BinRead, BinWrite};
+use nespile_macros::{BinReadAddressMode, BinWriteAddressMode};
 use subenum::subenum;
 
 
 
+/// An operand of an instruction, which `parse_byte_with(write; ..)` picks the opcode byte of.
+pub trait MatchesOpcode {
+    /// Whether `opcode` selects this address mode, the inverse of reading it.
+    fn matches_opcode(&self, opcode: u8) -> bool;
+}
+
 /// Address Modes per https://www.masswerk.at/6502/6502_instruction_set.html
 #[subenum(
     AddrModeImmediate,
@@ -11,9 +17,9 @@ use subenum::subenum;
     AddrModeSimpleOrImm, AddrModeSimpleX, AddrModeAbsInd, AddrModeAbs,
     AddrModeSimpleYImm, AddrModeSimpleXImm, AddrModeNoZeropageYNoImm,
     AddrModeSAX, AddrModeSTX, AddrModeSTY, AddrModeAHX, AddrModeLAX,
-    AddrModeAbsY, AddrModeAbsX
+    AddrModeAbsY, AddrModeAbsX, AddrModeNOP
 )]
-#[derive(Debug, Clone, Copy, BinReadAddressMode)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BinReadAddressMode, BinWriteAddressMode)]
 pub enum AddressMode {
     /// Operand is implied Accumulator register.
     #[subenum(AddrModeSimpleXAcc)]
@@ -24,12 +30,12 @@ pub enum AddressMode {
         AddrModeNoZeropageY, AddrModeNoZeropageYNoImm, AddrModeSimpleXAcc, AddrModeSimple,
         AddrModeSimpleOrImm, AddrModeSimpleX, AddrModeAbsInd, AddrModeAbs,
         AddrModeSimpleYImm, AddrModeSimpleXImm, AddrModeSAX, AddrModeSTX, AddrModeSTY,
-        AddrModeLAX
+        AddrModeLAX, AddrModeNOP
     )]
     Absolute(u16),
 
     /// Absolute (16-bit) address, incremented by X with carry.
-    #[subenum(AddrModeNoZeropageY, AddrModeNoZeropageYNoImm, AddrModeSimpleXAcc, AddrModeSimpleX, AddrModeSimpleXImm, AddrModeAbsX)]
+    #[subenum(AddrModeNoZeropageY, AddrModeNoZeropageYNoImm, AddrModeSimpleXAcc, AddrModeSimpleX, AddrModeSimpleXImm, AddrModeAbsX, AddrModeNOP)]
     AbsoluteX(u16),
 
     /// Absolute (16-bit) address, incremented by Y with carry.
@@ -37,10 +43,11 @@ pub enum AddressMode {
     AbsoluteY(u16),
 
     /// Immediate (8-bit) value.
-    #[subenum(AddrModeNoZeropageY, AddrModeSimpleOrImm, AddrModeSimpleYImm, AddrModeSimpleXImm, AddrModeImmediate)]
+    #[subenum(AddrModeNoZeropageY, AddrModeSimpleOrImm, AddrModeSimpleYImm, AddrModeSimpleXImm, AddrModeImmediate, AddrModeNOP)]
     Immediate(u8),
 
     /// Implied (empty) value.
+    #[subenum(AddrModeNOP)]
     Implied,
 
     /// Absolute address, the value in memory at the given absolute address.
@@ -60,11 +67,11 @@ pub enum AddressMode {
     Relative(u8),
 
     /// Zeropage (8-bit) address.
-    #[subenum(AddrModeNoZeropageY, AddrModeNoZeropageYNoImm, AddrModeSimpleXAcc, AddrModeSimple, AddrModeSimpleOrImm, AddrModeSimpleX, AddrModeSimpleYImm, AddrModeSimpleXImm, AddrModeSAX, AddrModeSTX, AddrModeSTY, AddrModeLAX)]
+    #[subenum(AddrModeNoZeropageY, AddrModeNoZeropageYNoImm, AddrModeSimpleXAcc, AddrModeSimple, AddrModeSimpleOrImm, AddrModeSimpleX, AddrModeSimpleYImm, AddrModeSimpleXImm, AddrModeSAX, AddrModeSTX, AddrModeSTY, AddrModeLAX, AddrModeNOP)]
     ZeroPage(u8),
 
     /// Zeropage (8-bit) address, incremented by X without carry.
-    #[subenum(AddrModeNoZeropageY, AddrModeNoZeropageYNoImm, AddrModeSimpleXAcc, AddrModeSimpleX, AddrModeSimpleXImm, AddrModeSTY)]
+    #[subenum(AddrModeNoZeropageY, AddrModeNoZeropageYNoImm, AddrModeSimpleXAcc, AddrModeSimpleX, AddrModeSimpleXImm, AddrModeSTY, AddrModeNOP)]
     ZeroPageX(u8),
 
     /// Zeropage (8-bit) address, incremented by Y without carry.
@@ -72,17 +79,52 @@ pub enum AddressMode {
     ZeroPageY(u8),
 }
 impl AddressMode {
+    /// Signed offset of a relative branch.
+    pub fn relative_offset(&self) -> Option<i8> {
+        match self {
+            AddressMode::Relative(offset) => Some(*offset as i8),
+            _ => None,
+        }
+    }
+
+    /// The memory address the operand refers to, for modes that refer to one.
+    pub fn address(&self) -> Option<u16> {
+        match self {
+            AddressMode::Absolute(addr) | AddressMode::AbsoluteX(addr) |
+            AddressMode::AbsoluteY(addr) | AddressMode::Indirect(addr) =>
+                Some(*addr),
+            AddressMode::IndirectX(addr) | AddressMode::IndirectY(addr) |
+            AddressMode::ZeroPage(addr) | AddressMode::ZeroPageX(addr) |
+            AddressMode::ZeroPageY(addr) =>
+                Some(*addr as u16),
+            AddressMode::Accumulator | AddressMode::Implied |
+            AddressMode::Immediate(_) | AddressMode::Relative(_) =>
+                None,
+        }
+    }
+    /// Renders the operand with `name` in place of its address.
+    pub fn format_with(&self, name: &str) -> String {
+        match self {
+            AddressMode::AbsoluteX(_) | AddressMode::ZeroPageX(_) => format!("{},X", name),
+            AddressMode::AbsoluteY(_) | AddressMode::ZeroPageY(_) => format!("{},Y", name),
+            AddressMode::Indirect(_) => format!("({})", name),
+            AddressMode::IndirectX(_) => format!("({},X)", name),
+            AddressMode::IndirectY(_) => format!("({}),Y", name),
+            _ => name.to_string(),
+        }
+    }
+
     pub fn size(&self) -> usize {
         match self {
-            &AddressMode::Accumulator | &AddressMode::Implied =>
+            AddressMode::Accumulator | AddressMode::Implied =>
                 0,
-            &AddressMode::Immediate(_) | &AddressMode::IndirectX(_) |
-            &AddressMode::IndirectY(_) | &AddressMode::Relative(_) |
-            &AddressMode::ZeroPage(_) | &AddressMode::ZeroPageX(_) |
-            &AddressMode::ZeroPageY(_) =>
+            AddressMode::Immediate(_) | AddressMode::IndirectX(_) |
+            AddressMode::IndirectY(_) | AddressMode::Relative(_) |
+            AddressMode::ZeroPage(_) | AddressMode::ZeroPageX(_) |
+            AddressMode::ZeroPageY(_) =>
                 1,
-            &AddressMode::Absolute(_) | &AddressMode::AbsoluteX(_) |
-            &AddressMode::AbsoluteY(_) | &AddressMode::Indirect(_) => 
+            AddressMode::Absolute(_) | AddressMode::AbsoluteX(_) |
+            AddressMode::AbsoluteY(_) | AddressMode::Indirect(_) => 
                 2,
         }
     }
@@ -90,19 +132,23 @@ impl AddressMode {
 impl std::fmt::Display for AddressMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            &AddressMode::Accumulator => write!(f, "A"),
-            &AddressMode::Absolute(addr) => write!(f, "${:04x}", addr),
-            &AddressMode::AbsoluteX(addr) => write!(f, "${:04x},X", addr),
-            &AddressMode::AbsoluteY(addr) => write!(f, "${:04x},Y", addr),
-            &AddressMode::Immediate(addr) => write!(f, "#${:02x}", addr),
-            &AddressMode::Implied => Ok(()),
-            &AddressMode::Indirect(addr) => write!(f, "(${:04x})", addr),
-            &AddressMode::IndirectX(addr) => write!(f, "(${:02x},X)", addr),
-            &AddressMode::IndirectY(addr) => write!(f, "(${:02x}),Y", addr),
-            &AddressMode::Relative(addr) => write!(f, "${:02x}", addr),
-            &AddressMode::ZeroPage(addr) => write!(f, "${:02x}", addr),
-            &AddressMode::ZeroPageX(addr) => write!(f, "${:02x},X", addr),
-            &AddressMode::ZeroPageY(addr) => write!(f, "${:02x},Y", addr),
+            AddressMode::Accumulator => write!(f, "A"),
+            AddressMode::Absolute(addr) => write!(f, "${:04x}", addr),
+            AddressMode::AbsoluteX(addr) => write!(f, "${:04x},X", addr),
+            AddressMode::AbsoluteY(addr) => write!(f, "${:04x},Y", addr),
+            AddressMode::Immediate(addr) => write!(f, "#${:02x}", addr),
+            AddressMode::Implied => Ok(()),
+            AddressMode::Indirect(addr) => write!(f, "(${:04x})", addr),
+            AddressMode::IndirectX(addr) => write!(f, "(${:02x},X)", addr),
+            AddressMode::IndirectY(addr) => write!(f, "(${:02x}),Y", addr),
+            // Relative to the branch itself, so assemblers read it as a target rather than an offset.
+            AddressMode::Relative(offset) => match *offset as i8 as i16 + 2 {
+                distance if distance < 0 => write!(f, "*-${:02x}", -distance),
+                distance => write!(f, "*+${:02x}", distance),
+            },
+            AddressMode::ZeroPage(addr) => write!(f, "${:02x}", addr),
+            AddressMode::ZeroPageX(addr) => write!(f, "${:02x},X", addr),
+            AddressMode::ZeroPageY(addr) => write!(f, "${:02x},Y", addr),
         }
     }
 }
@@ -0,0 +1,612 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Cursor;
+
+use binrw::{BinRead, Error as BinError};
+
+use crate::mapper::{BankContext, Mapper, MapperRegisters, RegisterWrite, PRGROM_START};
+use crate::parser::address_mode::{AddrModeAbs, AddressMode};
+use crate::parser::instruction::{Instruction, Location};
+use crate::cdl::CodeDataLog;
+use crate::cpu::ControlFlow;
+use crate::parser::opcodes::Opcode;
+use crate::parser::rom::NesFile;
+
+use bank_switch::{BankSwitch, PathState, RegisterValues};
+use inline_data::{InlineData, INLINE_DATA_SCAN_LENGTH};
+use jump_table::{JumpTable, TableOperands, JUMP_TABLE_WINDOW};
+
+
+
+pub mod bank_switch;
+pub mod inline_data;
+pub mod jump_table;
+
+
+
+pub const NMI_VECTOR: u16 = 0xfffa;
+pub const RESET_VECTOR: u16 = 0xfffc;
+pub const IRQ_VECTOR: u16 = 0xfffe;
+
+/// How far into a subroutine to look for bank switches before giving up.
+const MAX_SIMULATED_CALL_LENGTH: usize = 32;
+/// How many instructions of a subroutine too long to simulate to search for mapper writes.
+const MAX_MAPPER_WRITE_SCAN_LENGTH: usize = 512;
+/// Most entries read from a jump table without a bounds check to size it.
+const MAX_JUMP_TABLE_ENTRIES: usize = 128;
+
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Vector { NMI, Reset, IRQ }
+impl Vector {
+    pub const ALL: [Vector; 3] = [Vector::NMI, Vector::Reset, Vector::IRQ];
+
+    /// CPU address of the vector in the interrupt vector table.
+    pub fn address(&self) -> u16 {
+        match self {
+            Vector::NMI => NMI_VECTOR,
+            Vector::Reset => RESET_VECTOR,
+            Vector::IRQ => IRQ_VECTOR,
+        }
+    }
+}
+
+/// Classification of a single PRG-ROM byte after disassembly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteKind {
+    /// Never reached by control flow.
+    Data,
+    /// First byte of a reached instruction.
+    Opcode,
+    /// Operand byte of a reached instruction.
+    Operand,
+}
+
+/// Where control may go after executing an instruction.
+struct Flow {
+    targets: Vec<u16>,
+    falls_through: bool,
+}
+impl Flow {
+    fn of(op: &Opcode, next: u16) -> Flow {
+        match op {
+            Opcode::JMP(_) => Flow {
+                targets: op.argument().and_then(|mode| absolute_target(&mode)).into_iter().collect(),
+                falls_through: false,
+            },
+            Opcode::JSR(_) => Flow {
+                targets: op.argument().and_then(|mode| absolute_target(&mode)).into_iter().collect(),
+                falls_through: true,
+            },
+            Opcode::RTS | Opcode::RTI | Opcode::BRK | Opcode::STP => Flow {
+                targets: Vec::new(),
+                falls_through: false,
+            },
+            _ => match op.argument() {
+                Some(AddressMode::Relative(offset)) => Flow {
+                    targets: vec![ next.wrapping_add_signed(offset as i8 as i16) ],
+                    falls_through: true,
+                },
+                _ => Flow { targets: Vec::new(), falls_through: true },
+            },
+        }
+    }
+}
+
+/// Only direct jumps can be followed statically; `JMP ($xxxx)` is not.
+fn absolute_target(mode: &AddressMode) -> Option<u16> {
+    match mode {
+        AddressMode::Absolute(addr) => Some(*addr),
+        _ => None,
+    }
+}
+
+
+
+/// The result of a recursive-descent disassembly of PRG-ROM.
+pub struct Disassembly {
+    /// Reached instructions, keyed by PRG-ROM offset.
+    pub instructions: BTreeMap<usize, Instruction>,
+    pub byte_kinds: Vec<ByteKind>,
+    /// Interrupt vectors and the CPU address each one points to.
+    pub vectors: Vec<(Vector, u16)>,
+    /// Jump, call and branch targets whose PRG-ROM bank could not be determined.
+    pub unresolved: Vec<UnresolvedTarget>,
+    pub bank_switches: Vec<BankSwitch>,
+    /// Jump tables recovered from `JMP (ptr)` and `RTS` dispatches, whose targets were traced as code.
+    pub jump_tables: Vec<JumpTable>,
+    /// Parameters stored after calls, skipped over as data.
+    pub inline_data: Vec<InlineData>,
+    pub warnings: Vec<DisassemblyWarning>,
+}
+
+/// Something suspicious found while disassembling, which may indicate misidentified code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisassemblyWarning {
+    /// A relative branch into a different PRG-ROM bank than the branch itself.
+    BranchOutOfBank { from: Location, target: u16 },
+    /// A relative branch that wraps around the top or bottom of the address space.
+    BranchWraparound { from: Location, target: u16 },
+    /// The header's mapper isn't supported, so only the last 32KB of PRG-ROM was mapped, at $8000.
+    UnsupportedMapper { number: u16 },
+}
+impl DisassemblyWarning {
+    /// The instruction the warning is about, if it is about one.
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            DisassemblyWarning::BranchOutOfBank { from, .. } |
+            DisassemblyWarning::BranchWraparound { from, .. } => Some(*from),
+            DisassemblyWarning::UnsupportedMapper { .. } => None,
+        }
+    }
+    /// Describes the warning, without its location.
+    pub fn message(&self) -> String {
+        match self {
+            DisassemblyWarning::BranchOutOfBank { target, .. } =>
+                format!("branch to ${:04x} leaves its bank", target),
+            DisassemblyWarning::BranchWraparound { target, .. } =>
+                format!("branch to ${:04x} wraps around the address space", target),
+            DisassemblyWarning::UnsupportedMapper { number } =>
+                format!("mapper {} is unsupported, so only the last 32KB of PRG-ROM was disassembled, at $8000", number),
+        }
+    }
+}
+impl std::fmt::Display for DisassemblyWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location() {
+            Some(location) => write!(f, "{}: {}", location, self.message()),
+            None => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+/// A control flow target that could not be mapped to PRG-ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnresolvedTarget {
+    /// PRG-ROM offset of the jumping instruction.
+    pub from: usize,
+    pub target: u16,
+}
+
+/// Recursive-descent disassembler that only decodes bytes reachable from its entry points.
+///
+/// Each traced path remembers which banks it has seen selected, so that targets in a
+/// switchable window resolve to the bank the path is running from. Writes to mapper
+/// registers are tracked with constant propagation of A, X and Y, including through
+/// short branchless subroutines, so that calls following a bank switch resolve to the
+/// newly selected bank. Values are forgotten where another path's branch or jump joins.
+pub struct Disassembler<'a> {
+    prgrom: &'a [u8],
+    mapper: &'a dyn Mapper,
+    /// Offset of PRG-ROM into the ROM file.
+    file_offset: usize,
+    pending: Vec<(u16, BankContext)>,
+    vectors: Vec<(Vector, u16)>,
+    byte_kinds: Vec<ByteKind>,
+    instructions: BTreeMap<usize, Instruction>,
+    unresolved: Vec<UnresolvedTarget>,
+    bank_switches: Vec<BankSwitch>,
+    /// Resolved bank switches, and the PRG-ROM offsets their value passed through after being loaded.
+    switch_paths: Vec<(BankSwitch, Vec<usize>)>,
+    /// PRG-ROM offsets reached by more than one path, where register values are unknown.
+    join_points: HashSet<usize>,
+    jump_tables: Vec<JumpTable>,
+    inline_data: Vec<InlineData>,
+    /// Inline parameter bytes expected by each subroutine called so far, keyed by PRG-ROM offset.
+    inline_lengths: HashMap<usize, Option<usize>>,
+    /// Addresses of bytes a code/data log saw run, keyed by PRG-ROM offset.
+    code_hints: BTreeMap<usize, u16>,
+    /// Bytes a code/data log saw read but never run, which are never decoded.
+    known_data: Vec<bool>,
+    warnings: Vec<DisassemblyWarning>,
+}
+impl<'a> Disassembler<'a> {
+    pub fn new(prgrom: &'a [u8], mapper: &'a dyn Mapper) -> Self {
+        Disassembler {
+            prgrom,
+            mapper,
+            file_offset: 0,
+            pending: Vec::new(),
+            vectors: Vec::new(),
+            byte_kinds: vec![ByteKind::Data; prgrom.len()],
+            instructions: BTreeMap::new(),
+            unresolved: Vec::new(),
+            bank_switches: Vec::new(),
+            switch_paths: Vec::new(),
+            join_points: HashSet::new(),
+            jump_tables: Vec::new(),
+            inline_data: Vec::new(),
+            inline_lengths: HashMap::new(),
+            code_hints: BTreeMap::new(),
+            known_data: vec![false; prgrom.len()],
+            warnings: Vec::new(),
+        }
+    }
+    pub fn for_file(file: &'a NesFile, mapper: &'a dyn Mapper) -> Self {
+        Disassembler {
+            file_offset: file.prgrom_file_offset(),
+            ..Disassembler::new(file.prgrom(), mapper)
+        }
+    }
+
+    /// Adds an entry point, resolved with the banks selected at power-on.
+    pub fn add_entry_point(&mut self, addr: u16) {
+        self.pending.push((addr, self.mapper.power_on_context()));
+    }
+    /// Reads the NMI, reset and IRQ vectors from the end of PRG-ROM and adds them as entry points.
+    pub fn add_vector_entry_points(&mut self) {
+        for vector in Vector::ALL {
+            if let Some(addr) = self.read_word(vector.address()) {
+                self.vectors.push((vector, addr));
+                self.add_entry_point(addr);
+            }
+        }
+    }
+
+    /// Seeds the disassembly from a code/data log: bytes it saw run are traced as code once everything
+    /// reachable from the entry points has been, and bytes it only saw read are kept as data.
+    pub fn add_code_data_log(&mut self, cdl: &CodeDataLog) {
+        for prg_offset in 0..self.prgrom.len().min(cdl.prg.len()) {
+            if cdl.is_code(prg_offset) {
+                self.code_hints.insert(prg_offset, cdl.cpu_address(prg_offset, self.mapper));
+            } else if cdl.is_data(prg_offset) {
+                self.known_data[prg_offset] = true;
+            }
+        }
+    }
+
+    pub fn run(mut self) -> Disassembly {
+        loop {
+            while let Some((addr, banks)) = self.pending.pop() {
+                self.trace(addr, banks);
+            }
+            // Code only seen running, in address order so each run of it is decoded from its start.
+            let Some((prg_offset, addr)) = self.code_hints.pop_first() else {
+                break;
+            };
+            if self.byte_kinds[prg_offset] != ByteKind::Data {
+                continue;
+            }
+            let [logged, usual] = [addr, self.mapper.cpu_address(prg_offset)].map(|addr| {
+                let mut banks = self.mapper.power_on_context();
+                banks.select(self.mapper.window_start(addr), self.mapper.location(prg_offset).bank);
+                (addr, banks)
+            });
+            let resolved = [logged, usual].into_iter()
+                .find(|(addr, banks)| self.mapper.resolve(*addr, banks).is_some_and(|location| location.prg_offset == prg_offset));
+            if let Some((addr, banks)) = resolved {
+                self.trace(addr, banks);
+            }
+        }
+
+        Disassembly {
+            instructions: self.instructions,
+            byte_kinds: self.byte_kinds,
+            vectors: self.vectors,
+            unresolved: self.unresolved,
+            bank_switches: self.bank_switches,
+            jump_tables: self.jump_tables,
+            inline_data: self.inline_data,
+            warnings: self.warnings,
+        }
+    }
+
+    /// Decodes instructions linearly from `addr` until control flow stops,
+    /// queueing every branch, jump, call and jump table target along the way.
+    fn trace(&mut self, mut addr: u16, banks: BankContext) {
+        let entry = addr;
+        let mut state = PathState::new(banks);
+        let mut recent = Vec::new();
+        while let Some(location) = self.mapper.resolve(addr, &state.banks) {
+            // Code keeps running from whichever bank it was found in.
+            state.banks.select(self.mapper.window_start(addr), location.bank);
+
+            let offset = location.prg_offset;
+            if self.byte_kinds[offset] == ByteKind::Opcode {
+                // Falling into code another path already traced joins it.
+                if addr != entry {
+                    self.add_join_point(offset);
+                }
+                break;
+            }
+            let Ok(op) = self.decode_at(offset) else {
+                break;
+            };
+            let size = op.size();
+            if !self.claim(offset, size) {
+                break;
+            }
+
+            let mut next = addr.wrapping_add(size as u16);
+            let flow = Flow::of(&op, next);
+            let mut target = None;
+            for target_addr in flow.targets {
+                if let Some(target_location) = self.mapper.resolve(target_addr, &state.banks) {
+                    // Calls are simulated with the caller's registers instead.
+                    if !matches!(op, Opcode::JSR(_)) {
+                        self.add_join_point(target_location.prg_offset);
+                    }
+                    self.pending.push((target_addr, state.banks.clone()));
+                    target = Some(target_location);
+                } else {
+                    self.unresolved.push(UnresolvedTarget { from: offset, target: target_addr });
+                }
+            }
+            let instruction = Instruction {
+                address: addr,
+                bank: location.bank,
+                prg_offset: offset,
+                file_offset: self.file_offset + offset,
+                bytes: self.prgrom[offset..offset + size].to_vec(),
+                opcode: op,
+                target,
+            };
+            self.check_branch(&instruction);
+            self.instructions.insert(offset, instruction);
+
+            recent.push(op);
+            if recent.len() > JUMP_TABLE_WINDOW {
+                recent.remove(0);
+            }
+            if let Some(operands) = TableOperands::find(&recent) {
+                self.recover_jump_table(offset, operands, &state.banks);
+            }
+
+            if !flow.falls_through {
+                break;
+            }
+            match (op, target) {
+                (Opcode::JSR(AddrModeAbs::Absolute(callee)), Some(callee_location)) => {
+                    if let Some(len) = self.inline_length(callee, callee_location.prg_offset, &state.banks) {
+                        self.inline_data.push(InlineData { call: offset, prg_offset: offset + size, len });
+                        next = next.wrapping_add(len as u16);
+                    }
+                    // The callee may have changed any register.
+                    recent.clear();
+                    self.simulate_call(addr, callee, &mut state);
+                },
+                _ => self.step(offset, &op, &mut state),
+            }
+            addr = next;
+        }
+    }
+    fn check_branch(&mut self, instruction: &Instruction) {
+        let Some(target) = instruction.branch_target() else {
+            return;
+        };
+
+        if instruction.branch_wraps() {
+            self.warnings.push(DisassemblyWarning::BranchWraparound { from: instruction.location(), target });
+        } else if instruction.target.is_some_and(|location| location.bank != instruction.bank) {
+            self.warnings.push(DisassemblyWarning::BranchOutOfBank { from: instruction.location(), target });
+        }
+    }
+    /// Applies an instruction to the path state, recording any bank switch it performs.
+    fn step(&mut self, offset: usize, op: &Opcode, state: &mut PathState) {
+        if self.join_points.contains(&offset) {
+            state.forget_registers();
+        }
+        if let Some((register, value)) = state.registers.store(op) {
+            let write = self.mapper.register_write(register, value, &mut state.mapper_registers);
+            if let RegisterWrite::Prg { window, bank } = write {
+                match bank {
+                    Some(bank) => state.banks.select(window, bank),
+                    None => state.banks.deselect(window),
+                }
+
+                let switch = BankSwitch { prg_offset: offset, register, value, window, bank };
+                if bank.is_some() {
+                    let path = state.since_load(op).iter().copied().chain([offset]).collect();
+                    self.switch_paths.push((switch.clone(), path));
+                }
+                self.record_bank_switch(switch);
+            }
+        }
+        state.step(offset, op);
+    }
+    /// Marks where another path joins, and forgets the bank of switches whose stored value a path
+    /// joining after its load may have changed, unless another path still selects it.
+    fn add_join_point(&mut self, prg_offset: usize) {
+        if !self.join_points.insert(prg_offset) {
+            return;
+        }
+        let (joined, rest) = std::mem::take(&mut self.switch_paths).into_iter()
+            .partition(|(_, path)| path.contains(&prg_offset));
+        self.switch_paths = rest;
+        for (switch, _) in joined {
+            if self.switch_paths.iter().any(|(other, _)| *other == switch) {
+                continue;
+            }
+            self.bank_switches.retain(|other| *other != switch);
+            self.record_bank_switch(BankSwitch { value: None, bank: None, ..switch });
+        }
+    }
+    /// Records a bank switch, preferring a resolved bank over an unknown one at the same store.
+    fn record_bank_switch(&mut self, switch: BankSwitch) {
+        let same_store = |other: &BankSwitch| other.prg_offset == switch.prg_offset;
+        if switch.bank.is_none() && self.bank_switches.iter().any(same_store) {
+            return;
+        }
+        if switch.bank.is_some() {
+            self.bank_switches.retain(|other| !same_store(other) || other.bank.is_some());
+        }
+        if !self.bank_switches.contains(&switch) {
+            self.bank_switches.push(switch);
+        }
+    }
+    /// Follows a called subroutine up to its `RTS` and applies its effects to the caller's state,
+    /// if it is short and branchless. Otherwise the caller's registers become unknown, and so do
+    /// its banks if the callee might write a mapper register, except for the caller's own window.
+    fn simulate_call(&mut self, caller: u16, mut addr: u16, state: &mut PathState) {
+        let callee_addr = addr;
+        let mut callee = state.clone();
+        for _ in 0..MAX_SIMULATED_CALL_LENGTH {
+            let Some(location) = self.mapper.resolve(addr, &callee.banks) else { break };
+            let Ok(op) = self.decode_at(location.prg_offset) else { break };
+            if matches!(op, Opcode::RTS) {
+                *state = callee;
+                return;
+            }
+
+            let next = addr.wrapping_add(op.size() as u16);
+            let flow = Flow::of(&op, next);
+            if !flow.targets.is_empty() || !flow.falls_through {
+                break;
+            }
+            self.step(location.prg_offset, &op, &mut callee);
+            addr = next;
+        }
+        state.forget_registers();
+
+        if self.may_write_mapper(callee_addr, &state.banks) {
+            let window = self.mapper.window_start(caller);
+            let caller_bank = state.banks.selected(window);
+            state.banks = BankContext::default();
+            if let Some(bank) = caller_bank {
+                state.banks.select(window, bank);
+            }
+            state.mapper_registers = MapperRegisters::default();
+        }
+    }
+    /// Whether anything reachable from a subroutine stores to a mapper register. Code that can't
+    /// be followed, such as an indirect jump or a switchable bank, counts as a possible write.
+    fn may_write_mapper(&self, addr: u16, banks: &BankContext) -> bool {
+        let mut pending = vec![addr];
+        let mut seen = HashSet::new();
+        while let Some(addr) = pending.pop() {
+            if !seen.insert(addr) {
+                continue;
+            }
+            if seen.len() > MAX_MAPPER_WRITE_SCAN_LENGTH {
+                return true;
+            }
+            let Some(location) = self.mapper.resolve(addr, banks) else { return true };
+            let Ok(op) = self.decode_at(location.prg_offset) else { return true };
+
+            let write = RegisterValues::default().store(&op)
+                .map(|(register, _)| self.mapper.register_write(register, None, &mut MapperRegisters::default()));
+            if write.is_some_and(|write| write != RegisterWrite::NotRegister) {
+                return true;
+            }
+
+            let next = addr.wrapping_add(op.size() as u16);
+            let flow = Flow::of(&op, next);
+            if matches!(op, Opcode::JMP(_)) && flow.targets.is_empty() {
+                return true;
+            }
+            pending.extend(flow.targets);
+            if flow.falls_through {
+                pending.push(next);
+            }
+        }
+        false
+    }
+    /// The number of inline parameter bytes a subroutine skips, from its instructions in address order.
+    fn inline_length(&mut self, mut addr: u16, prg_offset: usize, banks: &BankContext) -> Option<usize> {
+        if let Some(len) = self.inline_lengths.get(&prg_offset) {
+            return *len;
+        }
+
+        let mut ops = Vec::new();
+        while ops.len() < INLINE_DATA_SCAN_LENGTH {
+            let Some(location) = self.mapper.resolve(addr, banks) else { break };
+            let Ok(op) = self.decode_at(location.prg_offset) else { break };
+            ops.push((addr, op));
+            if !matches!(op.semantics().control_flow, ControlFlow::Next | ControlFlow::Branch) {
+                break;
+            }
+            addr = addr.wrapping_add(op.size() as u16);
+        }
+
+        let len = inline_data::inline_length(&ops).filter(|len| *len > 0);
+        self.inline_lengths.insert(prg_offset, len);
+        len
+    }
+    /// Reads the entries of a jump table and queues each as an entry point, stopping at the first
+    /// that doesn't look like code or whose bytes something else refers to, such as another
+    /// instruction's operand or another table. Without a bounds check to size the table, only the
+    /// first entry is taken on trust: later ones must point at code already reached some other way.
+    fn recover_jump_table(&mut self, prg_offset: usize, operands: TableOperands, banks: &BankContext) {
+        let read_data = |addr: u16| {
+            let location = self.mapper.resolve(addr, banks)?;
+            (self.byte_kinds.get(location.prg_offset)? == &ByteKind::Data).then(|| self.prgrom[location.prg_offset])
+        };
+        let referenced = self.referenced_addresses();
+
+        let mut targets = Vec::new();
+        for entry in 0..operands.limit.unwrap_or(MAX_JUMP_TABLE_ENTRIES) {
+            let step = entry as u16 * operands.stride;
+            let (lo_addr, hi_addr) = (operands.lo_table.wrapping_add(step), operands.hi_table.wrapping_add(step));
+            // The dispatch's own loads refer to the first entry.
+            if entry > 0 && (referenced.contains(&lo_addr) || referenced.contains(&hi_addr)) {
+                break;
+            }
+            let Some((lo, hi)) = read_data(lo_addr).zip(read_data(hi_addr)) else {
+                break;
+            };
+
+            let target = u16::from_le_bytes([lo, hi]).wrapping_add(operands.target_offset());
+            let Some(location) = self.mapper.resolve(target, banks).filter(|_| target >= PRGROM_START) else {
+                break;
+            };
+            if self.byte_kinds[location.prg_offset] == ByteKind::Operand || self.decode_at(location.prg_offset).is_err() {
+                break;
+            }
+            if operands.limit.is_none() && entry > 0 && self.byte_kinds[location.prg_offset] != ByteKind::Opcode {
+                break;
+            }
+            targets.push((target, location));
+        }
+
+        if targets.is_empty() {
+            return;
+        }
+        for (target, location) in &targets {
+            self.add_join_point(location.prg_offset);
+            self.pending.push((*target, banks.clone()));
+        }
+        self.jump_tables.push(JumpTable {
+            prg_offset,
+            dispatch: operands.dispatch,
+            lo_table: operands.lo_table,
+            hi_table: operands.hi_table,
+            stride: operands.stride,
+            limit: operands.limit,
+            targets,
+        });
+    }
+    /// CPU addresses that decoded instructions use as operands, and the bytes of recovered jump tables.
+    fn referenced_addresses(&self) -> HashSet<u16> {
+        let operands = self.instructions.values()
+            .filter_map(|instruction| instruction.opcode.argument()?.address());
+        let tables = self.jump_tables.iter()
+            .flat_map(|table| table.entry_bytes())
+            .flat_map(|(lo, hi)| [lo, hi]);
+        operands.chain(tables).collect()
+    }
+    /// Marks the bytes of an instruction as reached, unless they overlap another instruction.
+    fn claim(&mut self, offset: usize, size: usize) -> bool {
+        let Some(bytes) = self.byte_kinds.get_mut(offset..offset + size) else {
+            return false;
+        };
+        if bytes.iter().any(|kind| *kind != ByteKind::Data) || self.known_data[offset..offset + size].contains(&true) {
+            return false;
+        }
+
+        bytes[0] = ByteKind::Opcode;
+        bytes[1..].fill(ByteKind::Operand);
+        true
+    }
+
+    fn decode_at(&self, offset: usize) -> Result<Opcode, BinError> {
+        let mut cursor = Cursor::new(&self.prgrom[offset..]);
+        Opcode::read_options(&mut cursor, binrw::Endian::Little, ())
+    }
+    fn read_word(&self, addr: u16) -> Option<u16> {
+        let banks = self.mapper.power_on_context();
+        let lo = *self.prgrom.get(self.mapper.resolve(addr, &banks)?.prg_offset)?;
+        let hi = *self.prgrom.get(self.mapper.resolve(addr.wrapping_add(1), &banks)?.prg_offset)?;
+        Some(u16::from_le_bytes([lo, hi]))
+    }
+}
@@ -0,0 +1,147 @@
+use crate::mapper::{BankContext, MapperRegisters};
+use crate::parser::address_mode::AddressMode;
+use crate::parser::opcodes::Opcode;
+
+
+
+/// A write to a mapper register that changes which PRG-ROM bank is mapped into a window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BankSwitch {
+    /// PRG-ROM offset of the store instruction.
+    pub prg_offset: usize,
+    /// CPU address written to.
+    pub register: u16,
+    /// Value written, if constant propagation could determine it.
+    pub value: Option<u8>,
+    pub window: u16,
+    pub bank: Option<usize>,
+}
+
+/// Known constant values of A, X and Y along a single path of execution.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RegisterValues {
+    pub a: Option<u8>,
+    pub x: Option<u8>,
+    pub y: Option<u8>,
+}
+impl RegisterValues {
+    /// Updates the known values after executing `op`.
+    ///
+    /// Instructions that are not modeled forget every value.
+    pub fn step(&mut self, op: &Opcode) {
+        let imm = op.argument().and_then(|mode| match mode {
+            AddressMode::Immediate(v) => Some(v),
+            _ => None,
+        });
+
+        match op {
+            Opcode::LDA(_) => self.a = imm,
+            Opcode::LDX(_) => self.x = imm,
+            Opcode::LDY(_) => self.y = imm,
+            Opcode::TAX => self.x = self.a,
+            Opcode::TAY => self.y = self.a,
+            Opcode::TXA => self.a = self.x,
+            Opcode::TYA => self.a = self.y,
+            Opcode::TSX => self.x = None,
+            Opcode::INX => self.x = self.x.map(|x| x.wrapping_add(1)),
+            Opcode::DEX => self.x = self.x.map(|x| x.wrapping_sub(1)),
+            Opcode::INY => self.y = self.y.map(|y| y.wrapping_add(1)),
+            Opcode::DEY => self.y = self.y.map(|y| y.wrapping_sub(1)),
+            Opcode::AND(_) => self.a = self.a.zip(imm).map(|(a, v)| a & v),
+            Opcode::ORA(_) => self.a = self.a.zip(imm).map(|(a, v)| a | v),
+            Opcode::EOR(_) => self.a = self.a.zip(imm).map(|(a, v)| a ^ v),
+            Opcode::ASL(_) if matches!(op.argument(), Some(AddressMode::Accumulator)) =>
+                self.a = self.a.map(|a| a << 1),
+            Opcode::LSR(_) if matches!(op.argument(), Some(AddressMode::Accumulator)) =>
+                self.a = self.a.map(|a| a >> 1),
+            Opcode::ROL(_) | Opcode::ROR(_) if matches!(op.argument(), Some(AddressMode::Accumulator)) =>
+                self.a = None,
+
+            Opcode::STA(_) | Opcode::STX(_) | Opcode::STY(_) | Opcode::SAX(_) |
+            Opcode::CMP(_) | Opcode::CPX(_) | Opcode::CPY(_) | Opcode::BIT(_) |
+            Opcode::ASL(_) | Opcode::LSR(_) | Opcode::ROL(_) | Opcode::ROR(_) |
+            Opcode::INC(_) | Opcode::DEC(_) |
+            Opcode::CLC | Opcode::SEC | Opcode::CLI | Opcode::SEI |
+            Opcode::CLD | Opcode::SED | Opcode::CLV | Opcode::NOP(_) |
+            Opcode::PHA | Opcode::PHP | Opcode::PLP | Opcode::TXS |
+            Opcode::BCC(_) | Opcode::BCS(_) | Opcode::BEQ(_) | Opcode::BMI(_) |
+            Opcode::BNE(_) | Opcode::BPL(_) | Opcode::BVC(_) | Opcode::BVS(_) |
+            Opcode::JMP(_) => {},
+
+            _ => *self = RegisterValues::default(),
+        }
+    }
+
+    /// The CPU address written by a store instruction, and the value if it is known.
+    ///
+    /// An unknown index is treated as zero, which is enough to recognize the register range.
+    pub fn store(&self, op: &Opcode) -> Option<(u16, Option<u8>)> {
+        let value = match op {
+            Opcode::STA(_) => self.a,
+            Opcode::STX(_) => self.x,
+            Opcode::STY(_) => self.y,
+            Opcode::SAX(_) => self.a.zip(self.x).map(|(a, x)| a & x),
+            _ => return None,
+        };
+        let addr = match op.argument()? {
+            AddressMode::Absolute(addr) => addr,
+            AddressMode::AbsoluteX(addr) => addr.wrapping_add(self.x.unwrap_or(0) as u16),
+            AddressMode::AbsoluteY(addr) => addr.wrapping_add(self.y.unwrap_or(0) as u16),
+            _ => return None,
+        };
+        Some((addr, value))
+    }
+}
+
+/// Everything a single traced path knows about the machine state.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathState {
+    pub banks: BankContext,
+    pub registers: RegisterValues,
+    pub mapper_registers: MapperRegisters,
+    /// PRG-ROM offsets of the instructions applied since the registers were last forgotten.
+    pub path: Vec<usize>,
+    /// Index into `path` of the load that A, X and Y got their values from.
+    sources: [usize; 3],
+}
+impl PathState {
+    pub fn new(banks: BankContext) -> Self {
+        PathState { banks, ..PathState::default() }
+    }
+
+    /// Applies the instruction at `prg_offset` to the known register values.
+    pub fn step(&mut self, prg_offset: usize, op: &Opcode) {
+        let here = self.path.len();
+        self.path.push(prg_offset);
+        let [a, x, y] = &mut self.sources;
+        match op {
+            Opcode::LDA(_) => *a = here,
+            Opcode::LDX(_) => *x = here,
+            Opcode::LDY(_) => *y = here,
+            Opcode::TAX => *x = *a,
+            Opcode::TAY => *y = *a,
+            Opcode::TXA => *a = *x,
+            Opcode::TYA => *a = *y,
+            _ => {},
+        }
+        self.registers.step(op);
+    }
+    /// Forgets every register value, as where paths traced with different values join.
+    pub fn forget_registers(&mut self) {
+        self.registers = RegisterValues::default();
+        self.path.clear();
+        self.sources = [0; 3];
+    }
+    /// PRG-ROM offsets of the instructions after the load a stored value came from, up to the store.
+    pub fn since_load(&self, op: &Opcode) -> &[usize] {
+        let [a, x, y] = self.sources;
+        let source = match op {
+            Opcode::STA(_) => a,
+            Opcode::STX(_) => x,
+            Opcode::STY(_) => y,
+            Opcode::SAX(_) => a.min(x),
+            _ => return &[],
+        };
+        self.path.get(source + 1..).unwrap_or_default()
+    }
+}
@@ -0,0 +1,132 @@
+use std::ops::RangeInclusive;
+
+use crate::cpu::{ControlFlow, Registers};
+use crate::parser::address_mode::AddressMode;
+use crate::parser::opcodes::Opcode;
+
+
+
+/// How many instructions into a subroutine to look for it adjusting its return address.
+pub const INLINE_DATA_SCAN_LENGTH: usize = 48;
+
+/// Parameter bytes stored after a `JSR` to a routine that reads them through its return address and skips them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InlineData {
+    /// PRG-ROM offset of the `JSR`.
+    pub call: usize,
+    /// PRG-ROM offset of the first byte of data, just after the `JSR`.
+    pub prg_offset: usize,
+    pub len: usize,
+}
+
+/// Where a routine keeps the low byte of its return address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    /// Copied to memory after `PLA`.
+    Memory(u16),
+    /// In place on the stack, addressed as `$0101,X` after `TSX`, offset by bytes pushed before the `TSX`.
+    Stack,
+}
+
+/// The number of inline bytes a subroutine skips past, from its first instructions in address order.
+///
+/// Recognizes the low byte of the return address being pulled (`PLA`) or read in place
+/// (`TSX` then `$0101,X`), then either having a constant added with `ADC #n` or being
+/// incremented with `INC` once per byte. Branches are not followed, and the scan ends
+/// at the first call, jump or return. Routines that adjust the address inside a loop, or
+/// call out while it is pulled off the stack, skip a number of bytes that can't be known
+/// from here, like the usual print-inline-string routine, so they give `None`.
+pub fn inline_length(ops: &[(u16, Opcode)]) -> Option<usize> {
+    let loops = backward_jumps(ops);
+    let in_loop = |address: u16| loops.iter().any(|range| range.contains(&address));
+
+    let mut pushed = 0i32;
+    let mut pulled = 0;
+    // Bytes pushed when X was loaded from SP, which places the return address at $0101 plus that.
+    let mut x_is_sp_after = None;
+    let mut a_is_return_lo = false;
+    let mut slots = Vec::new();
+    let mut carry = None;
+    let mut increments = 0;
+
+    for &(address, op) in ops {
+        let mode = op.argument();
+        let semantics = op.semantics();
+        let is_slot = |mode: Option<AddressMode>, slots: &[Slot]| match mode {
+            Some(AddressMode::ZeroPage(address)) => slots.contains(&Slot::Memory(address as u16)),
+            Some(AddressMode::Absolute(address)) => slots.contains(&Slot::Memory(address)),
+            Some(AddressMode::AbsoluteX(address)) => x_is_sp_after.is_some_and(|pushed: i32| address == 0x0101 + pushed as u16),
+            _ => false,
+        };
+
+        match op {
+            Opcode::PLA if pushed == 0 => {
+                pulled += 1;
+                a_is_return_lo = pulled == 1;
+                continue;
+            },
+            Opcode::TSX => {
+                x_is_sp_after = Some(pushed);
+                slots.push(Slot::Stack);
+                continue;
+            },
+            Opcode::STA(_) if a_is_return_lo => {
+                match mode {
+                    Some(AddressMode::ZeroPage(address)) => slots.push(Slot::Memory(address as u16)),
+                    Some(AddressMode::Absolute(address)) => slots.push(Slot::Memory(address)),
+                    _ => {},
+                }
+                continue;
+            },
+            Opcode::LDA(_) if is_slot(mode, &slots) => {
+                a_is_return_lo = true;
+                continue;
+            },
+            Opcode::ADC(_) if a_is_return_lo => {
+                let Some(AddressMode::Immediate(n)) = mode.filter(|_| !in_loop(address)) else {
+                    return None;
+                };
+                return Some(n as usize + usize::from(carry == Some(true)));
+            },
+            Opcode::INC(_) if is_slot(mode, &slots) => {
+                if in_loop(address) {
+                    return None;
+                }
+                increments += 1;
+            },
+            Opcode::CLC => carry = Some(false),
+            Opcode::SEC => carry = Some(true),
+            _ => {},
+        }
+
+        // Whatever runs after the call could still move the return address before pushing it back.
+        if semantics.control_flow == ControlFlow::Call && pushed < pulled {
+            return None;
+        }
+        pushed += semantics.stack as i32;
+        if semantics.registers_written.contains(Registers::A) {
+            a_is_return_lo = false;
+        }
+        if semantics.registers_written.contains(Registers::X) {
+            x_is_sp_after = None;
+        }
+        if !matches!(semantics.control_flow, ControlFlow::Next | ControlFlow::Branch) {
+            break;
+        }
+    }
+    (increments > 0).then_some(increments)
+}
+
+/// The address ranges that branches and jumps back to an earlier instruction can repeat.
+fn backward_jumps(ops: &[(u16, Opcode)]) -> Vec<RangeInclusive<u16>> {
+    ops.iter()
+        .filter_map(|&(address, op)| {
+            let target = match op.argument()? {
+                AddressMode::Relative(offset) => address.wrapping_add(op.size() as u16).wrapping_add_signed(offset as i8 as i16),
+                AddressMode::Absolute(target) if matches!(op, Opcode::JMP(_)) => target,
+                _ => return None,
+            };
+            (target <= address).then_some(target..=address)
+        })
+        .collect()
+}
@@ -0,0 +1,182 @@
+use crate::cpu::Registers;
+use crate::mapper::PrgLocation;
+use crate::parser::address_mode::AddressMode;
+use crate::parser::opcodes::Opcode;
+
+
+
+/// How many instructions before a dispatch are searched for the loads that build its address.
+pub const JUMP_TABLE_WINDOW: usize = 16;
+
+/// How code jumps through a table of addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dispatch {
+    /// The address is stored to a pointer, then `JMP (ptr)`.
+    IndirectJump,
+    /// The address minus one is pushed high byte first, then `RTS`.
+    PushedReturn,
+}
+
+/// A table of code addresses that a `JMP (ptr)` or `RTS` dispatches through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpTable {
+    /// PRG-ROM offset of the dispatching instruction.
+    pub prg_offset: usize,
+    pub dispatch: Dispatch,
+    /// CPU address of the table of low bytes.
+    pub lo_table: u16,
+    /// CPU address of the table of high bytes, one past `lo_table` for a table of words.
+    pub hi_table: u16,
+    /// Bytes between consecutive entries: 2 for a table of words, 1 for split tables.
+    pub stride: u16,
+    /// Entry count from the index's bounds check, if the dispatch has one.
+    pub limit: Option<usize>,
+    /// Each entry's code address and where it was found, one per entry read from the table.
+    pub targets: Vec<(u16, PrgLocation)>,
+}
+impl JumpTable {
+    /// CPU addresses of the low and high byte of each entry that was read.
+    pub fn entry_bytes(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        (0..self.targets.len() as u16).map(|entry| {
+            let step = entry * self.stride;
+            (self.lo_table.wrapping_add(step), self.hi_table.wrapping_add(step))
+        })
+    }
+}
+
+/// Where a dispatch's table is, as recognized from the instructions leading up to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableOperands {
+    pub dispatch: Dispatch,
+    pub lo_table: u16,
+    pub hi_table: u16,
+    pub stride: u16,
+    /// Entry count, from an index bounds check like `CMP #n` then `BCS`.
+    pub limit: Option<usize>,
+}
+impl TableOperands {
+    /// Added to each table entry to give the code address.
+    pub fn target_offset(&self) -> u16 {
+        match self.dispatch {
+            Dispatch::IndirectJump => 0,
+            Dispatch::PushedReturn => 1,
+        }
+    }
+
+    /// Recognizes a jump table idiom ending at the last of `recent`, the straight-line
+    /// instructions leading up to it:
+    ///
+    /// ```text
+    /// LDA lo,X / STA ptr / LDA hi,X / STA ptr+1 / JMP (ptr)
+    /// LDA hi,X / PHA / LDA lo,X / PHA / RTS
+    /// ```
+    ///
+    /// in any register and either order, including tables of words indexed at `table` and `table+1`.
+    pub fn find(recent: &[Opcode]) -> Option<TableOperands> {
+        let (dispatch, before) = recent.split_last()?;
+        let (dispatch, (lo, hi)) = match (dispatch, dispatch.argument()) {
+            (Opcode::JMP(_), Some(AddressMode::Indirect(pointer))) => {
+                let lo = indexed_load_stored(before, pointer)?;
+                let hi = indexed_load_stored(before, pointer.wrapping_add(1))?;
+                (Dispatch::IndirectJump, (lo, hi))
+            },
+            (Opcode::RTS, _) => (Dispatch::PushedReturn, pushed_address(before)?),
+            _ => return None,
+        };
+        if lo.1 != hi.1 {
+            return None;
+        }
+
+        let (lo_table, hi_table) = (lo.0, hi.0);
+        let stride = if hi_table == lo_table.wrapping_add(1) { 2 } else { 1 };
+        Some(TableOperands { dispatch, lo_table, hi_table, stride, limit: bounds_check(before, stride, lo.1) })
+    }
+}
+
+/// The table address and index register of an indexed load from an absolute table.
+fn indexed_load(op: &Opcode) -> Option<(u16, Registers)> {
+    if !matches!(op, Opcode::LDA(_) | Opcode::LDX(_) | Opcode::LDY(_)) {
+        return None;
+    }
+    match op.argument()? {
+        AddressMode::AbsoluteX(table) => Some((table, Registers::X)),
+        AddressMode::AbsoluteY(table) => Some((table, Registers::Y)),
+        _ => None,
+    }
+}
+
+/// The table and index register of the load that gave `register` its value at the end of `recent`.
+fn loaded_from_table(recent: &[Opcode], register: Registers) -> Option<(u16, Registers)> {
+    let load = recent.iter().rev().find(|op| op.semantics().registers_written.contains(register))?;
+    indexed_load(load)
+}
+
+/// The table load whose value was last stored to `address`.
+fn indexed_load_stored(recent: &[Opcode], address: u16) -> Option<(u16, Registers)> {
+    let position = recent.iter().rposition(|op| {
+        let stored = matches!(op.argument(), Some(AddressMode::ZeroPage(a)) if a as u16 == address) ||
+            matches!(op.argument(), Some(AddressMode::Absolute(a)) if a == address);
+        stored && matches!(op, Opcode::STA(_) | Opcode::STX(_) | Opcode::STY(_))
+    })?;
+    let register = recent[position].semantics().registers_read;
+    loaded_from_table(&recent[..position], register)
+}
+
+/// The table loads of the high then low byte pushed by the last two `PHA`s, with nothing else touching the stack since.
+fn pushed_address(recent: &[Opcode]) -> Option<((u16, Registers), (u16, Registers))> {
+    let mut pushes = recent.iter().enumerate().rev()
+        .filter(|(_, op)| op.semantics().stack != 0);
+    let (lo_push, lo_op) = pushes.next()?;
+    let (hi_push, hi_op) = pushes.next()?;
+    if !matches!((lo_op, hi_op), (Opcode::PHA, Opcode::PHA)) {
+        return None;
+    }
+    let lo = loaded_from_table(&recent[..lo_push], Registers::A)?;
+    let hi = loaded_from_table(&recent[..hi_push], Registers::A)?;
+    Some((lo, hi))
+}
+
+/// The entry count implied by a `CMP`, `CPX` or `CPY` immediate followed by `BCS` or `BCC`, of the
+/// register that still holds the table's `index` or is transferred into it, halved for a table of
+/// words if the index was already doubled when compared.
+fn bounds_check(recent: &[Opcode], stride: u16, index: Registers) -> Option<usize> {
+    let position = (0..recent.len().saturating_sub(1)).rev().find(|&position| {
+        let compared = match recent[position] {
+            Opcode::CMP(_) => Registers::A,
+            Opcode::CPX(_) => Registers::X,
+            Opcode::CPY(_) => Registers::Y,
+            _ => return false,
+        };
+        matches!(recent[position + 1], Opcode::BCS(_) | Opcode::BCC(_)) &&
+            holds_compared(&recent[position + 1..], compared, index)
+    })?;
+    let Some(AddressMode::Immediate(count)) = recent[position].argument() else {
+        return None;
+    };
+
+    let doubled = stride == 2 && recent[..position].iter()
+        .any(|op| matches!(op, Opcode::ASL(_)) && op.argument() == Some(AddressMode::Accumulator));
+    Some(if doubled { (count as usize).div_ceil(2) } else { count as usize })
+}
+
+/// Whether `index` holds the value compared in `compared` after `ops`, following transfers between
+/// registers. Doubling A keeps it, since a table of words is indexed by twice the entry number.
+fn holds_compared(ops: &[Opcode], compared: Registers, index: Registers) -> bool {
+    let mut holders = vec![compared];
+    for op in ops {
+        let transfer = match op {
+            Opcode::TAX => Some((Registers::A, Registers::X)),
+            Opcode::TAY => Some((Registers::A, Registers::Y)),
+            Opcode::TXA => Some((Registers::X, Registers::A)),
+            Opcode::TYA => Some((Registers::Y, Registers::A)),
+            _ => None,
+        };
+        let transferred = transfer.filter(|(from, _)| holders.contains(from)).map(|(_, to)| to);
+        let doubles = matches!(op, Opcode::ASL(_)) && op.argument() == Some(AddressMode::Accumulator);
+        if !doubles {
+            holders.retain(|register| !op.semantics().registers_written.contains(*register));
+        }
+        holders.extend(transferred);
+    }
+    holders.contains(&index)
+}
@@ -0,0 +1,86 @@
+use crate::mapper::PrgLocation;
+use crate::parser::opcodes::Opcode;
+
+
+
+/// A decoded instruction along with where it was found.
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    /// CPU address the instruction executes from.
+    pub address: u16,
+    /// PRG-ROM bank containing the instruction.
+    pub bank: usize,
+    /// Offset of the opcode byte into PRG-ROM.
+    pub prg_offset: usize,
+    /// Offset of the opcode byte into the ROM file, including the header and trainer.
+    pub file_offset: usize,
+    /// Raw bytes of the opcode and its operand.
+    pub bytes: Vec<u8>,
+    pub opcode: Opcode,
+    /// Resolved location of a direct jump, call or branch target, given the banks
+    /// known to be selected when the instruction executes.
+    pub target: Option<PrgLocation>,
+}
+impl Instruction {
+    pub fn size(&self) -> usize {
+        self.bytes.len()
+    }
+    /// CPU address of the instruction that follows this one.
+    pub fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.bytes.len() as u16)
+    }
+    /// Absolute destination of a relative branch, wrapping around the address space.
+    pub fn branch_target(&self) -> Option<u16> {
+        let offset = self.opcode.argument()?.relative_offset()?;
+        Some(self.next_address().wrapping_add_signed(offset as i16))
+    }
+    /// Whether a relative branch crosses the top or bottom of the address space.
+    pub fn branch_wraps(&self) -> bool {
+        self.opcode.argument()
+            .and_then(|mode| mode.relative_offset())
+            .is_some_and(|offset| self.next_address().checked_add_signed(offset as i16).is_none())
+    }
+
+    pub fn location(&self) -> Location {
+        Location {
+            address: self.address,
+            bank: self.bank,
+            file_offset: self.file_offset,
+        }
+    }
+
+    pub fn to_source_string(&self) -> String {
+        self.format_with(|_| None)
+    }
+    /// Renders the instruction, substituting `label_for` an operand address where it gives a name.
+    ///
+    /// Relative branches are rendered with their absolute destination.
+    pub fn format_with(&self, label_for: impl Fn(u16) -> Option<String>) -> String {
+        let Some(mode) = self.opcode.argument() else {
+            return self.opcode.to_source_string();
+        };
+
+        let operand = if let Some(branch_target) = self.branch_target() {
+            label_for(branch_target)
+                .unwrap_or_else(|| format!("${:04x}", branch_target))
+        } else if let Some(name) = mode.address().and_then(&label_for) {
+            mode.format_with(&name)
+        } else {
+            mode.to_string()
+        };
+        format!("{}   {}", self.opcode.variant_name(), operand)
+    }
+}
+
+/// A printable location in the ROM, for diagnostics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub address: u16,
+    pub bank: usize,
+    pub file_offset: usize,
+}
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "${:04x} (bank {}, file offset ${:x})", self.address, self.bank, self.file_offset)
+    }
+}
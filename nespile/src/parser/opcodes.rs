@@ -1,4 +1,4 @@
-use binrw::BinRead;
+use binrw::{BinRead, BinWrite};
 
 use nespile_macros::{parse_byte_with, OpcodeArgs, VariantNames};
 
@@ -6,8 +6,29 @@ use crate::parser::address_mode::*;
 
 
 
-#[derive(Debug, VariantNames, OpcodeArgs)]
-#[parse_byte_with(
+/// Opcode bytes of the documented NMOS 6502 instruction set, which every assembler encodes
+/// the same way; everything else is an unofficial opcode or a duplicate encoding.
+pub const OFFICIAL_OPCODES: [u8; 151] = [
+    0x00, 0x01, 0x05, 0x06, 0x08, 0x09, 0x0a, 0x0d, 0x0e, 0x10, 0x11, 0x15, 0x16, 0x18, 0x19, 0x1d,
+    0x1e, 0x20, 0x21, 0x24, 0x25, 0x26, 0x28, 0x29, 0x2a, 0x2c, 0x2d, 0x2e, 0x30, 0x31, 0x35, 0x36,
+    0x38, 0x39, 0x3d, 0x3e, 0x40, 0x41, 0x45, 0x46, 0x48, 0x49, 0x4a, 0x4c, 0x4d, 0x4e, 0x50, 0x51,
+    0x55, 0x56, 0x58, 0x59, 0x5d, 0x5e, 0x60, 0x61, 0x65, 0x66, 0x68, 0x69, 0x6a, 0x6c, 0x6d, 0x6e,
+    0x70, 0x71, 0x75, 0x76, 0x78, 0x79, 0x7d, 0x7e, 0x81, 0x84, 0x85, 0x86, 0x88, 0x8a, 0x8c, 0x8d,
+    0x8e, 0x90, 0x91, 0x94, 0x95, 0x96, 0x98, 0x99, 0x9a, 0x9d, 0xa0, 0xa1, 0xa2, 0xa4, 0xa5, 0xa6,
+    0xa8, 0xa9, 0xaa, 0xac, 0xad, 0xae, 0xb0, 0xb1, 0xb4, 0xb5, 0xb6, 0xb8, 0xb9, 0xba, 0xbc, 0xbd,
+    0xbe, 0xc0, 0xc1, 0xc4, 0xc5, 0xc6, 0xc8, 0xc9, 0xca, 0xcc, 0xcd, 0xce, 0xd0, 0xd1, 0xd5, 0xd6,
+    0xd8, 0xd9, 0xdd, 0xde, 0xe0, 0xe1, 0xe4, 0xe5, 0xe6, 0xe8, 0xe9, 0xea, 0xec, 0xed, 0xee, 0xf0,
+    0xf1, 0xf5, 0xf6, 0xf8, 0xf9, 0xfd, 0xfe,
+];
+
+pub fn is_official_opcode(byte: u8) -> bool {
+    OFFICIAL_OPCODES.binary_search(&byte).is_ok()
+}
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, VariantNames, OpcodeArgs)]
+#[parse_byte_with(write;
     BRK, ORA, STP, SLO, NOP, ORA, ASL, SLO, PHP, ORA, ASL, ANC, NOP, ORA, ASL, SLO, 
     BPL, ORA, STP, SLO, NOP, ORA, ASL, SLO, CLC, ORA, NOP, SLO, NOP, ORA, ASL, SLO, 
     JSR, AND, STP, RLA, BIT, AND, ROL, RLA, PLP, AND, ROL, ANC, BIT, AND, ROL, RLA, 
@@ -93,8 +114,9 @@ pub enum Opcode {
     LDY(AddrModeSimpleXImm),
     /// logical shift right
     LSR(AddrModeSimpleXAcc),
-    /// no operation
-    NOP,
+    /// no operation (with operands, unofficially)
+    #[prefer_byte(0xea)]
+    NOP(AddrModeNOP),
     /// or with accumulator
     ORA(AddrModeNoZeropageY),
     /// push accumulator
@@ -205,11 +227,23 @@ pub enum Opcode {
 
 
 impl Opcode {
+    /// Size of the instruction in bytes, including the opcode byte.
+    pub fn size(&self) -> usize {
+        1 + self.argument().map_or(0, |mode| mode.size())
+    }
+
+    /// Encodes the instruction with its canonical opcode byte.
+    pub fn encode(&self) -> binrw::BinResult<Vec<u8>> {
+        let mut writer = std::io::Cursor::new(Vec::with_capacity(self.size()));
+        self.write_options(&mut writer, binrw::Endian::Little, ())?;
+        Ok(writer.into_inner())
+    }
+
     pub fn to_source_string(&self) -> String {
         if let Some(addr_mode) = self.argument() {
-            format!("{:?}   {}", self.variant_name(), addr_mode)
+            format!("{}   {}", self.variant_name(), addr_mode)
         } else {
-            format!("{:?}", self.variant_name())
+            self.variant_name().to_string()
         }
     }
 }
@@ -1,19 +1,27 @@
+// `#[bitfield]` expands to parenthesized field types.
+#![allow(unused_parens)]
+
 use std::{fs, io};
 use std::path::Path;
 
 use binrw::{binread, BinRead, Error as BinError};
 use modular_bitfield::{bitfield, BitfieldSpecifier};
-use modular_bitfield::specifiers::{B2, B4, B7};
+use modular_bitfield::specifiers::{B2, B4, B6, B7};
 use thiserror::Error;
 
 
 
+pub const NES_HEADER_SIZE: usize = 16;
+
 const PRGROM_SIZE_SHIFT: usize = 14;
 const CHRROM_SIZE_SHIFT: usize = 13;
+const PRGRAM_SIZE_SHIFT: usize = 13;
+/// NES 2.0 RAM sizes are given as a shift count of 64 bytes.
+const NES2_RAM_SIZE_BASE: usize = 64;
 
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NesFormatVersion {
     ArchaicINes,
     INES,
@@ -21,15 +29,147 @@ pub enum NesFormatVersion {
     Unknown,
 }
 
-#[derive(BitfieldSpecifier, Debug)]
+#[derive(BitfieldSpecifier, Debug, Clone, Copy, PartialEq, Eq)]
 #[bits = 1]
 pub enum NametableArrangement { Vertical, Horizontal }
-#[derive(BitfieldSpecifier, Debug)]
+#[derive(BitfieldSpecifier, Debug, Clone, Copy, PartialEq, Eq)]
 #[bits = 1]
 pub enum TVSystem { NTSC, PAL }
 
+/// Console type per NES 2.0 byte 7, bits 0-1.
+#[derive(BitfieldSpecifier, Debug, Clone, Copy, PartialEq, Eq)]
+#[bits = 2]
+pub enum ConsoleType { NES, VsSystem, Playchoice10, Extended }
+
+/// CPU/PPU timing mode per NES 2.0 byte 12.
+#[derive(BitfieldSpecifier, Debug, Clone, Copy, PartialEq, Eq)]
+#[bits = 2]
+pub enum CpuPpuTiming {
+    /// RP2C02 ("NTSC NES")
+    NTSC,
+    /// RP2C07 ("Licensed PAL NES")
+    PAL,
+    /// Multiple-region
+    MultiRegion,
+    /// UA6538 ("Dendy")
+    Dendy,
+}
+
+/// Vs. System PPU type per NES 2.0 byte 13, bits 0-3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VsPpuType {
+    RP2C03B,
+    RP2C03G,
+    RP2C04_0001,
+    RP2C04_0002,
+    RP2C04_0003,
+    RP2C04_0004,
+    RC2C03B,
+    RC2C03C,
+    RC2C05_01,
+    RC2C05_02,
+    RC2C05_03,
+    RC2C05_04,
+    RC2C05_05,
+    Reserved(u8),
+}
+impl From<u8> for VsPpuType {
+    fn from(value: u8) -> Self {
+        match value {
+            0x0 => VsPpuType::RP2C03B,
+            0x1 => VsPpuType::RP2C03G,
+            0x2 => VsPpuType::RP2C04_0001,
+            0x3 => VsPpuType::RP2C04_0002,
+            0x4 => VsPpuType::RP2C04_0003,
+            0x5 => VsPpuType::RP2C04_0004,
+            0x6 => VsPpuType::RC2C03B,
+            0x7 => VsPpuType::RC2C03C,
+            0x8 => VsPpuType::RC2C05_01,
+            0x9 => VsPpuType::RC2C05_02,
+            0xa => VsPpuType::RC2C05_03,
+            0xb => VsPpuType::RC2C05_04,
+            0xc => VsPpuType::RC2C05_05,
+            v => VsPpuType::Reserved(v),
+        }
+    }
+}
+
+/// Vs. System hardware type per NES 2.0 byte 13, bits 4-7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VsHardwareType {
+    UnisystemNormal,
+    UnisystemRbiBaseball,
+    UnisystemTkoBoxing,
+    UnisystemSuperXevious,
+    UnisystemIceClimberJapan,
+    DualSystemNormal,
+    DualSystemRaidOnBungelingBay,
+    Reserved(u8),
+}
+impl From<u8> for VsHardwareType {
+    fn from(value: u8) -> Self {
+        match value {
+            0x0 => VsHardwareType::UnisystemNormal,
+            0x1 => VsHardwareType::UnisystemRbiBaseball,
+            0x2 => VsHardwareType::UnisystemTkoBoxing,
+            0x3 => VsHardwareType::UnisystemSuperXevious,
+            0x4 => VsHardwareType::UnisystemIceClimberJapan,
+            0x5 => VsHardwareType::DualSystemNormal,
+            0x6 => VsHardwareType::DualSystemRaidOnBungelingBay,
+            v => VsHardwareType::Reserved(v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VsSystemType {
+    pub ppu: VsPpuType,
+    pub hardware: VsHardwareType,
+}
+
+/// Extended console type per NES 2.0 byte 13, bits 0-3.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtendedConsoleType {
+    NES,
+    VsSystem,
+    Playchoice10,
+    FamicloneDecimalMode,
+    EPSM,
+    VT01,
+    VT02,
+    VT03,
+    VT09,
+    VT32,
+    VT369,
+    UM6578,
+    FamicomNetworkSystem,
+    Reserved(u8),
+}
+impl From<u8> for ExtendedConsoleType {
+    fn from(value: u8) -> Self {
+        match value {
+            0x0 => ExtendedConsoleType::NES,
+            0x1 => ExtendedConsoleType::VsSystem,
+            0x2 => ExtendedConsoleType::Playchoice10,
+            0x3 => ExtendedConsoleType::FamicloneDecimalMode,
+            0x4 => ExtendedConsoleType::EPSM,
+            0x5 => ExtendedConsoleType::VT01,
+            0x6 => ExtendedConsoleType::VT02,
+            0x7 => ExtendedConsoleType::VT03,
+            0x8 => ExtendedConsoleType::VT09,
+            0x9 => ExtendedConsoleType::VT32,
+            0xa => ExtendedConsoleType::VT369,
+            0xb => ExtendedConsoleType::UM6578,
+            0xc => ExtendedConsoleType::FamicomNetworkSystem,
+            v => ExtendedConsoleType::Reserved(v),
+        }
+    }
+}
+
+
+
 #[bitfield]
-#[derive(BinRead, Debug)]
+#[derive(BinRead, Clone, Debug, Default)]
 #[br(map = Self::from_bytes)]
 pub struct INesHeaderFlags6 {
     pub nametable_arrangement: NametableArrangement,
@@ -39,7 +179,7 @@ pub struct INesHeaderFlags6 {
     pub mapper_lower: B4
 }
 #[bitfield]
-#[derive(BinRead, Clone, Debug)]
+#[derive(BinRead, Clone, Debug, Default)]
 #[br(map = Self::from_bytes)]
 pub struct INesHeaderFlags7 {
     pub vs_unisystem: bool,
@@ -48,7 +188,7 @@ pub struct INesHeaderFlags7 {
     pub mapper_upper: B4
 }
 #[bitfield]
-#[derive(BinRead, Clone, Debug)]
+#[derive(BinRead, Clone, Debug, Default)]
 #[br(map = Self::from_bytes)]
 pub struct INesHeaderFlags9 {
     pub tv_system: TVSystem,
@@ -56,27 +196,109 @@ pub struct INesHeaderFlags9 {
     reserved: B7
 }
 
+/// Bytes 8-15 of an iNES 1.0 header.
+#[binread]
+#[derive(Clone, Debug)]
+#[br(little)]
+pub struct INesHeaderExtension {
+    #[br(map = |x: u8| (x as usize) << PRGRAM_SIZE_SHIFT)]
+    pub prgram_size: usize,
+
+    pub flags9: INesHeaderFlags9,
+
+    #[br(temp)]
+    unused: [u8; 6],
+}
+
+
+#[bitfield]
+#[derive(BinRead, Clone, Debug, Default)]
+#[br(map = Self::from_bytes)]
+pub struct Nes2HeaderFlags8 {
+    pub mapper_msb: B4,
+    pub submapper: B4,
+}
+#[bitfield]
+#[derive(BinRead, Clone, Debug, Default)]
+#[br(map = Self::from_bytes)]
+pub struct Nes2HeaderFlags9 {
+    pub prgrom_size_msb: B4,
+    pub chrrom_size_msb: B4,
+}
+#[bitfield]
+#[derive(BinRead, Clone, Debug, Default)]
+#[br(map = Self::from_bytes)]
+pub struct Nes2HeaderFlags10 {
+    pub prgram_shift: B4,
+    pub prgnvram_shift: B4,
+}
+#[bitfield]
+#[derive(BinRead, Clone, Debug, Default)]
+#[br(map = Self::from_bytes)]
+pub struct Nes2HeaderFlags11 {
+    pub chrram_shift: B4,
+    pub chrnvram_shift: B4,
+}
+#[bitfield]
+#[derive(BinRead, Clone, Debug, Default)]
+#[br(map = Self::from_bytes)]
+pub struct Nes2HeaderFlags12 {
+    pub timing: CpuPpuTiming,
+    #[skip]
+    reserved: B6
+}
+/// Vs. System type or extended console type, depending on the console type.
+#[bitfield]
+#[derive(BinRead, Clone, Debug, Default)]
+#[br(map = Self::from_bytes)]
+pub struct Nes2HeaderFlags13 {
+    pub system_type_lower: B4,
+    pub system_type_upper: B4,
+}
+#[bitfield]
+#[derive(BinRead, Clone, Debug, Default)]
+#[br(map = Self::from_bytes)]
+pub struct Nes2HeaderFlags14 {
+    pub misc_rom_count: B2,
+    #[skip]
+    reserved: B6
+}
+#[bitfield]
+#[derive(BinRead, Clone, Debug, Default)]
+#[br(map = Self::from_bytes)]
+pub struct Nes2HeaderFlags15 {
+    pub default_expansion_device: B6,
+    #[skip]
+    reserved: B2
+}
+
+/// Bytes 8-15 of a NES 2.0 header, per https://www.nesdev.org/wiki/NES_2.0
+#[binread]
+#[derive(Clone, Debug)]
+#[br(little)]
+pub struct Nes2HeaderExtension {
+    pub flags8: Nes2HeaderFlags8,
+    pub flags9: Nes2HeaderFlags9,
+    pub flags10: Nes2HeaderFlags10,
+    pub flags11: Nes2HeaderFlags11,
+    pub flags12: Nes2HeaderFlags12,
+    pub flags13: Nes2HeaderFlags13,
+    pub flags14: Nes2HeaderFlags14,
+    pub flags15: Nes2HeaderFlags15,
+}
+
 
 #[binread]
 #[derive(Debug)]
 #[br(little, magic = b"NES\x1a")]
 pub struct NesHeader {
-    #[br(map = |x: u8| (x as usize) << PRGROM_SIZE_SHIFT)]
-    pub prgrom_size: usize,
-    #[br(map = |x: u8| (x as usize) << CHRROM_SIZE_SHIFT)]
-    pub chrrom_size: usize,
+    #[br(temp)]
+    prgrom_size_lsb: u8,
+    #[br(temp)]
+    chrrom_size_lsb: u8,
 
     pub flags6: INesHeaderFlags6,
     pub flags7: INesHeaderFlags7,
-    
-    #[br(map = |x: u8| (x as usize) << CHRROM_SIZE_SHIFT)]
-    prgram_size: usize,
-    
-    pub flags9: INesHeaderFlags9,
-
-    unused: [u8; 6],
-
-
 
     #[br(calc = match flags7.clone().into_bytes()[0] {
         f if f & 0x0c == 0x04 => NesFormatVersion::ArchaicINes,
@@ -84,7 +306,151 @@ pub struct NesHeader {
         f if f & 0x0c == 0x08 => NesFormatVersion::NES2_0,
         _ => NesFormatVersion::Unknown
     })]
-    pub version: NesFormatVersion
+    pub version: NesFormatVersion,
+
+    #[br(if(version == NesFormatVersion::NES2_0))]
+    pub nes2: Option<Nes2HeaderExtension>,
+    #[br(if(version != NesFormatVersion::NES2_0))]
+    pub ines: Option<INesHeaderExtension>,
+
+    #[br(calc = rom_size(
+        prgrom_size_lsb,
+        nes2.as_ref().map_or(0, |ext| ext.flags9.prgrom_size_msb()),
+        PRGROM_SIZE_SHIFT
+    ))]
+    pub prgrom_size: usize,
+    #[br(calc = rom_size(
+        chrrom_size_lsb,
+        nes2.as_ref().map_or(0, |ext| ext.flags9.chrrom_size_msb()),
+        CHRROM_SIZE_SHIFT
+    ))]
+    pub chrrom_size: usize,
+}
+
+/// Decodes a ROM size from its LSB byte and MSB nibble.
+///
+/// An MSB nibble of `$F` selects the NES 2.0 exponent-multiplier notation,
+/// where the LSB byte is `EEEEEEMM` and the size is `2^E * (MM * 2 + 1)` bytes.
+fn rom_size(lsb: u8, msb: u8, unit_shift: usize) -> usize {
+    if msb == 0x0f {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0x03) as usize * 2 + 1;
+        1usize.checked_shl(exponent)
+            .map_or(usize::MAX, |size| size.saturating_mul(multiplier))
+    } else {
+        (((msb as usize) << 8) | lsb as usize) << unit_shift
+    }
+}
+/// Decodes a NES 2.0 RAM shift count, where zero means no RAM is present.
+fn ram_size(shift: u8) -> usize {
+    if shift == 0 { 0 } else { NES2_RAM_SIZE_BASE << shift }
+}
+
+impl NesHeader {
+    /// Full mapper number; 12 bits for NES 2.0 and 8 bits for iNES.
+    pub fn mapper_number(&self) -> u16 {
+        let lower = self.flags6.mapper_lower() as u16;
+        match (&self.version, &self.nes2) {
+            // Archaic iNES files often contain garbage ("DiskDude!") in bytes 7-15.
+            (NesFormatVersion::ArchaicINes, _) => lower,
+            (_, Some(ext)) =>
+                ((ext.flags8.mapper_msb() as u16) << 8) | ((self.flags7.mapper_upper() as u16) << 4) | lower,
+            (_, None) =>
+                ((self.flags7.mapper_upper() as u16) << 4) | lower,
+        }
+    }
+    /// NES 2.0 submapper number, or zero for iNES headers.
+    pub fn submapper(&self) -> u8 {
+        self.nes2.as_ref().map_or(0, |ext| ext.flags8.submapper())
+    }
+
+    pub fn console_type(&self) -> ConsoleType {
+        match (self.flags7.vs_unisystem(), self.flags7.playchoice_10()) {
+            (false, false) => ConsoleType::NES,
+            (true, false) => ConsoleType::VsSystem,
+            (false, true) => ConsoleType::Playchoice10,
+            (true, true) if self.nes2.is_some() => ConsoleType::Extended,
+            (true, true) => ConsoleType::VsSystem,
+        }
+    }
+    pub fn timing(&self) -> CpuPpuTiming {
+        match (&self.nes2, &self.ines) {
+            (Some(ext), _) => ext.flags12.timing(),
+            (None, Some(ext)) if ext.flags9.tv_system() == TVSystem::PAL => CpuPpuTiming::PAL,
+            _ => CpuPpuTiming::NTSC,
+        }
+    }
+    /// Vs. System PPU and hardware type, if this is a NES 2.0 Vs. System ROM.
+    pub fn vs_system_type(&self) -> Option<VsSystemType> {
+        match (&self.nes2, self.console_type()) {
+            (Some(ext), ConsoleType::VsSystem) => Some(VsSystemType {
+                ppu: ext.flags13.system_type_lower().into(),
+                hardware: ext.flags13.system_type_upper().into(),
+            }),
+            _ => None,
+        }
+    }
+    /// Extended console type, if this is a NES 2.0 ROM with an extended console type.
+    pub fn extended_console_type(&self) -> Option<ExtendedConsoleType> {
+        match (&self.nes2, self.console_type()) {
+            (Some(ext), ConsoleType::Extended) => Some(ext.flags13.system_type_lower().into()),
+            _ => None,
+        }
+    }
+
+    /// iNES byte 9, as the header used to expose it; NES 2.0 headers report their timing in it.
+    #[deprecated(note = "use `timing()`, or `ines` for the raw iNES 1.0 bytes")]
+    pub fn flags9(&self) -> INesHeaderFlags9 {
+        match &self.ines {
+            Some(ext) => ext.flags9.clone(),
+            None => INesHeaderFlags9::new().with_tv_system(match self.timing() {
+                CpuPpuTiming::PAL => TVSystem::PAL,
+                _ => TVSystem::NTSC,
+            }),
+        }
+    }
+
+    /// Volatile PRG-RAM size in bytes.
+    pub fn prgram_size(&self) -> usize {
+        match (&self.nes2, &self.ines) {
+            (Some(ext), _) => ram_size(ext.flags10.prgram_shift()),
+            (None, _) if self.flags6.persistent_memory() => 0,
+            // A value of zero implies 8KB for compatibility.
+            (None, Some(ext)) if ext.prgram_size > 0 => ext.prgram_size,
+            (None, _) => 1 << PRGRAM_SIZE_SHIFT,
+        }
+    }
+    /// Non-volatile (battery-backed) PRG-RAM size in bytes.
+    pub fn prgnvram_size(&self) -> usize {
+        match (&self.nes2, &self.ines) {
+            (Some(ext), _) => ram_size(ext.flags10.prgnvram_shift()),
+            (None, _) if !self.flags6.persistent_memory() => 0,
+            (None, Some(ext)) if ext.prgram_size > 0 => ext.prgram_size,
+            (None, _) => 1 << PRGRAM_SIZE_SHIFT,
+        }
+    }
+    /// Volatile CHR-RAM size in bytes.
+    pub fn chrram_size(&self) -> usize {
+        match &self.nes2 {
+            Some(ext) => ram_size(ext.flags11.chrram_shift()),
+            None if self.chrrom_size == 0 => 1 << CHRROM_SIZE_SHIFT,
+            None => 0,
+        }
+    }
+    /// Non-volatile CHR-RAM size in bytes.
+    pub fn chrnvram_size(&self) -> usize {
+        self.nes2.as_ref().map_or(0, |ext| ram_size(ext.flags11.chrnvram_shift()))
+    }
+
+    /// Number of miscellaneous ROMs following CHR-ROM (NES 2.0 only).
+    pub fn misc_rom_count(&self) -> u8 {
+        self.nes2.as_ref().map_or(0, |ext| ext.flags14.misc_rom_count())
+    }
+    /// Default expansion device per https://www.nesdev.org/wiki/NES_2.0#Default_Expansion_Device,
+    /// or zero (unspecified) for iNES headers.
+    pub fn default_expansion_device(&self) -> u8 {
+        self.nes2.as_ref().map_or(0, |ext| ext.flags15.default_expansion_device())
+    }
 }
 
 #[binread]
@@ -101,6 +467,17 @@ pub struct NesFile {
     pub chrrom_data: Vec<u8>,
 }
 
+impl NesFile {
+    /// PRG-ROM, truncated to the size declared in the header.
+    pub fn prgrom(&self) -> &[u8] {
+        &self.prgrom_data[..self.header.prgrom_size.min(self.prgrom_data.len())]
+    }
+    /// Offset of PRG-ROM into the ROM file.
+    pub fn prgrom_file_offset(&self) -> usize {
+        NES_HEADER_SIZE + self.trainer.as_ref().map_or(0, |t| t.len())
+    }
+}
+
 impl std::fmt::Debug for NesFile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f,
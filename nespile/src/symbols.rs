@@ -0,0 +1,292 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::mapper::{PrgLocation, PRGROM_START};
+use crate::parser::address_mode::AddressMode;
+use crate::parser::instruction::Instruction;
+use crate::parser::opcodes::Opcode;
+use crate::parser::NesProgram;
+use crate::parser::disassembler::{ByteKind, Vector};
+
+pub mod ca65;
+pub mod fceux;
+pub mod mesen;
+
+
+
+/// Memory-mapped PPU, APU and I/O registers, per https://www.nesdev.org/wiki/2A03
+/// and https://www.nesdev.org/wiki/PPU_registers
+pub const HARDWARE_REGISTERS: [(u16, &str); 30] = [
+    (0x2000, "PPUCTRL"),
+    (0x2001, "PPUMASK"),
+    (0x2002, "PPUSTATUS"),
+    (0x2003, "OAMADDR"),
+    (0x2004, "OAMDATA"),
+    (0x2005, "PPUSCROLL"),
+    (0x2006, "PPUADDR"),
+    (0x2007, "PPUDATA"),
+    (0x4000, "SQ1_VOL"),
+    (0x4001, "SQ1_SWEEP"),
+    (0x4002, "SQ1_LO"),
+    (0x4003, "SQ1_HI"),
+    (0x4004, "SQ2_VOL"),
+    (0x4005, "SQ2_SWEEP"),
+    (0x4006, "SQ2_LO"),
+    (0x4007, "SQ2_HI"),
+    (0x4008, "TRI_LINEAR"),
+    (0x400a, "TRI_LO"),
+    (0x400b, "TRI_HI"),
+    (0x400c, "NOISE_VOL"),
+    (0x400e, "NOISE_LO"),
+    (0x400f, "NOISE_HI"),
+    (0x4010, "DMC_FREQ"),
+    (0x4011, "DMC_RAW"),
+    (0x4012, "DMC_START"),
+    (0x4013, "DMC_LEN"),
+    (0x4014, "OAMDMA"),
+    (0x4015, "SND_CHN"),
+    (0x4016, "JOY1"),
+    (0x4017, "JOY2"),
+];
+
+
+
+/// What a symbol names, in increasing order of precedence when several apply to one address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SymbolKind {
+    /// Target of a branch or jump (`loc_XXXX`).
+    Branch,
+    /// Indexed data table (`tbl_XXXX`).
+    Data,
+    /// Target of a `JSR` (`sub_XXXX`).
+    Subroutine,
+    /// Interrupt vector handler (`reset`, `nmi`, `irq`).
+    Vector,
+    /// Memory-mapped hardware register.
+    Hardware,
+    /// Supplied by the user.
+    User,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// CPU address the symbol names.
+    pub address: u16,
+    /// Location in PRG-ROM, for symbols in cartridge space.
+    pub location: Option<PrgLocation>,
+    pub comment: Option<String>,
+}
+impl Symbol {
+    pub fn new(name: impl Into<String>, kind: SymbolKind, address: u16, location: Option<PrgLocation>) -> Self {
+        Symbol { name: name.into(), kind, address, location, comment: None }
+    }
+}
+
+/// Names for addresses, split into PRG-ROM locations and everything else (RAM and registers).
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    rom: BTreeMap<usize, Symbol>,
+    ram: BTreeMap<u16, Symbol>,
+}
+impl SymbolTable {
+    /// Symbols for the NES hardware registers only.
+    pub fn hardware() -> Self {
+        let mut symbols = SymbolTable::default();
+        for (address, name) in HARDWARE_REGISTERS {
+            symbols.insert(Symbol::new(name, SymbolKind::Hardware, address, None));
+        }
+        symbols
+    }
+    /// Generates names for vector handlers, subroutines, jump targets and data tables.
+    pub fn generate(program: &NesProgram) -> Self {
+        let mut symbols = SymbolTable::hardware();
+
+        // Several vectors often share one handler, which is named for the most important one.
+        let mut vectors = program.vectors.clone();
+        vectors.sort_by_key(|(vector, _)| match vector {
+            Vector::Reset => 0,
+            Vector::NMI => 1,
+            Vector::IRQ => 2,
+        });
+        for (vector, address) in &vectors {
+            let location = program.mapper.resolve(*address, &program.mapper.power_on_context());
+            let name = match vector {
+                Vector::NMI => "nmi",
+                Vector::Reset => "reset",
+                Vector::IRQ => "irq",
+            };
+            let named = location.and_then(|location| symbols.rom_symbol(location.prg_offset));
+            if location.is_some() && named.is_none_or(|symbol| symbol.kind != SymbolKind::Vector) {
+                symbols.insert(Symbol::new(name, SymbolKind::Vector, *address, location));
+            }
+        }
+
+        for instruction in program.instructions.values() {
+            if let Some(target) = instruction.target {
+                let Some(address) = program.instruction_at_offset(target.prg_offset).map(|t| t.address) else {
+                    continue;
+                };
+                let (prefix, kind) = match instruction.opcode {
+                    Opcode::JSR(_) => ("sub", SymbolKind::Subroutine),
+                    _ => ("loc", SymbolKind::Branch),
+                };
+                symbols.insert(Symbol::new(format!("{}_{:04X}", prefix, address), kind, address, Some(target)));
+            }
+
+            if let Some(AddressMode::AbsoluteX(address) | AddressMode::AbsoluteY(address)) = instruction.opcode.argument() {
+                let data = program.resolve_operand(instruction, address)
+                    .filter(|location| program.byte_kinds.get(location.prg_offset) == Some(&ByteKind::Data));
+                if let Some(location) = data {
+                    symbols.insert(Symbol::new(format!("tbl_{:04X}", address), SymbolKind::Data, address, Some(location)));
+                }
+            }
+        }
+
+        for (address, location) in program.jump_tables.iter().flat_map(|table| &table.targets) {
+            symbols.insert(Symbol::new(format!("loc_{:04X}", address), SymbolKind::Branch, *address, Some(*location)));
+        }
+
+        symbols
+    }
+
+    /// Adds a symbol, unless one of higher precedence already names its address.
+    pub fn insert(&mut self, symbol: Symbol) {
+        let existing = match symbol.location {
+            Some(location) => self.rom.get(&location.prg_offset),
+            None => self.ram.get(&symbol.address),
+        };
+        if existing.is_some_and(|existing| existing.kind > symbol.kind) {
+            return;
+        }
+
+        match symbol.location {
+            Some(location) => self.rom.insert(location.prg_offset, symbol),
+            None => self.ram.insert(symbol.address, symbol),
+        };
+    }
+    /// Adds every symbol of `other`, subject to the usual precedence.
+    pub fn merge(&mut self, other: SymbolTable) {
+        for symbol in other.rom.into_values().chain(other.ram.into_values()) {
+            self.insert(symbol);
+        }
+    }
+
+    pub fn rom_symbol(&self, prg_offset: usize) -> Option<&Symbol> {
+        self.rom.get(&prg_offset)
+    }
+    /// The symbol for a CPU address outside PRG-ROM.
+    pub fn ram_symbol(&self, address: u16) -> Option<&Symbol> {
+        self.ram.get(&address)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &Symbol> {
+        self.ram.values().chain(self.rom.values())
+    }
+    pub fn len(&self) -> usize {
+        self.rom.len() + self.ram.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The name for an operand address of `instruction`, resolving cartridge addresses
+    /// through the instruction's own bank and the mapper's fixed banks.
+    pub fn name_for(&self, program: &NesProgram, instruction: &Instruction, address: u16) -> Option<&str> {
+        if address < PRGROM_START {
+            return self.ram_symbol(address).map(|symbol| symbol.name.as_str());
+        }
+        let location = program.resolve_operand(instruction, address)?;
+        self.rom_symbol(location.prg_offset).map(|symbol| symbol.name.as_str())
+    }
+}
+
+
+
+#[derive(Error, Debug)]
+pub enum SymbolFileError {
+    #[error("Failed to access symbol file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid symbol file at line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error("Unrecognised symbol file format: {0}")]
+    UnknownFormat(PathBuf),
+}
+
+/// Symbol file formats of the emulators and assemblers nespile exchanges labels with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolFileFormat {
+    /// FCEUX name lists (`.nl`), one file per 16KB bank.
+    Fceux,
+    /// Mesen label files (`.mlb`).
+    Mesen,
+    /// ca65/ld65 debug info (`.dbg`).
+    Ca65,
+}
+impl SymbolFileFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "nl" => Some(SymbolFileFormat::Fceux),
+            "mlb" => Some(SymbolFileFormat::Mesen),
+            "dbg" => Some(SymbolFileFormat::Ca65),
+            _ => None,
+        }
+    }
+}
+
+/// Reads a symbol file, choosing its format by extension.
+///
+/// FCEUX files cover a single bank (`game.nes.3.nl`) or RAM (`game.nes.ram.nl`), per their name.
+pub fn load(path: impl AsRef<Path>, program: &NesProgram) -> Result<SymbolTable, SymbolFileError> {
+    let path = path.as_ref();
+    let unknown = || SymbolFileError::UnknownFormat(path.to_path_buf());
+    let format = SymbolFileFormat::from_path(path).ok_or_else(unknown)?;
+    let text = fs::read_to_string(path)?;
+    match format {
+        SymbolFileFormat::Fceux => {
+            let file = path.file_name().and_then(|name| name.to_str())
+                .and_then(fceux::NlFile::from_file_name)
+                .ok_or_else(unknown)?;
+            fceux::import(&text, file, program.mapper.as_ref())
+        },
+        SymbolFileFormat::Mesen => mesen::import(&text, program.mapper.as_ref()),
+        SymbolFileFormat::Ca65 => ca65::import(&text, program.mapper.as_ref(), program.prg_file_offset),
+    }
+}
+
+/// Writes a symbol file, choosing its format by extension, and returns the paths written.
+///
+/// For FCEUX, `game.nes.nl` is expanded to `game.nes.ram.nl`, `game.nes.0.nl`, ... as the emulator expects.
+pub fn save(symbols: &SymbolTable, path: impl AsRef<Path>, program: &NesProgram) -> Result<Vec<PathBuf>, SymbolFileError> {
+    let path = path.as_ref();
+    let format = SymbolFileFormat::from_path(path)
+        .ok_or_else(|| SymbolFileError::UnknownFormat(path.to_path_buf()))?;
+    match format {
+        SymbolFileFormat::Fceux => {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            let base = name.strip_suffix(".nl").unwrap_or(name);
+            let base = match fceux::NlFile::from_file_name(name) {
+                Some(_) => base.rsplit_once('.').map_or(base, |(base, _)| base),
+                None => base,
+            };
+            let mut written = vec![];
+            for (file, text) in fceux::export(symbols) {
+                let file_path = path.with_file_name(format!("{}.{}", base, file.file_suffix()));
+                fs::write(&file_path, text)?;
+                written.push(file_path);
+            }
+            Ok(written)
+        },
+        SymbolFileFormat::Mesen => {
+            fs::write(path, mesen::export(symbols))?;
+            Ok(vec![path.to_path_buf()])
+        },
+        SymbolFileFormat::Ca65 => {
+            fs::write(path, ca65::export(symbols, program.mapper.as_ref(), program.prg_file_offset))?;
+            Ok(vec![path.to_path_buf()])
+        },
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+use crate::mapper::{Mapper, PRGROM_START};
+use crate::symbols::{Symbol, SymbolFileError, SymbolKind, SymbolTable};
+
+
+
+/// A segment of a ca65 debug file; only what's needed to place its symbols.
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    start: u32,
+    /// Offset of the segment into the output file, absent for segments not written out (e.g. BSS).
+    file_offset: Option<usize>,
+}
+
+/// Splits the attributes of a debug file line (`id=0,name="CODE",start=0x8000`) into pairs.
+fn attributes(text: &str) -> HashMap<&str, &str> {
+    let mut attributes = HashMap::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let Some((key, value_start)) = rest.split_once('=') else {
+            break;
+        };
+        let (value, remainder) = match value_start.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+            },
+            None => value_start.split_once(',').map_or((value_start, ""), |(value, remainder)| (value, remainder)),
+        };
+        attributes.insert(key.trim(), value);
+        rest = remainder.strip_prefix(',').unwrap_or(remainder);
+    }
+    attributes
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Parses a ca65/ld65 debug file (`ld65 --dbgfile`), placing ROM symbols through their segment's
+/// offset into the output file.
+pub fn import(text: &str, mapper: &dyn Mapper, prg_file_offset: usize) -> Result<SymbolTable, SymbolFileError> {
+    let mut segments = HashMap::new();
+    let mut symbol_lines = vec![];
+    for (idx, line) in text.lines().enumerate() {
+        let Some((record, fields)) = line.trim_end_matches('\r').split_once(char::is_whitespace) else {
+            continue;
+        };
+        let parse_error = |message: &str| SymbolFileError::Parse { line: idx + 1, message: message.to_string() };
+        let fields = attributes(fields.trim_start());
+        match record {
+            "seg" => {
+                let id = fields.get("id").and_then(|id| parse_number(id)).ok_or_else(|| parse_error("missing segment id"))?;
+                let start = fields.get("start").and_then(|start| parse_number(start))
+                    .ok_or_else(|| parse_error("missing segment start"))?;
+                let file_offset = fields.get("ooffs").and_then(|offset| parse_number(offset)).map(|offset| offset as usize);
+                segments.insert(id, Segment { start, file_offset });
+            },
+            "sym" => symbol_lines.push((idx, fields)),
+            _ => {},
+        }
+    }
+
+    let mut symbols = SymbolTable::default();
+    for (idx, fields) in symbol_lines {
+        let parse_error = |message: &str| SymbolFileError::Parse { line: idx + 1, message: message.to_string() };
+        let name = fields.get("name").ok_or_else(|| parse_error("missing symbol name"))?;
+        // Imports repeat their export, and cheap locals are only unique within their scope.
+        if fields.get("type") == Some(&"imp") || name.starts_with('@') {
+            continue;
+        }
+        let value = fields.get("val").and_then(|value| parse_number(value))
+            .ok_or_else(|| parse_error("missing symbol value"))?;
+        let Ok(address) = u16::try_from(value) else {
+            continue;
+        };
+
+        let segment = match fields.get("seg") {
+            Some(id) => {
+                let id = parse_number(id).ok_or_else(|| parse_error("invalid segment id"))?;
+                Some(segments.get(&id).ok_or_else(|| parse_error("unknown segment"))?)
+            },
+            None => None,
+        };
+        let location = match segment {
+            Some(Segment { start, file_offset: Some(file_offset) }) if address >= PRGROM_START => {
+                let prg_offset = (file_offset + (value - start) as usize).checked_sub(prg_file_offset)
+                    .filter(|offset| *offset < mapper.prg_size())
+                    .ok_or_else(|| parse_error("symbol outside PRG-ROM"))?;
+                Some(mapper.location(prg_offset))
+            },
+            _ if address >= PRGROM_START => mapper.resolve(address, &mapper.power_on_context()),
+            _ => None,
+        };
+        symbols.insert(Symbol::new(*name, SymbolKind::User, address, location));
+    }
+    Ok(symbols)
+}
+
+/// Writes a debug file with one segment per PRG-ROM bank, readable by Mesen and other ca65-aware tools.
+pub fn export(symbols: &SymbolTable, mapper: &dyn Mapper, prg_file_offset: usize) -> String {
+    let bank_count = mapper.prg_bank_count();
+    let mut output = String::new();
+    output.push_str("version\tmajor=2,minor=0\n");
+    output.push_str(&format!(
+        "info\tcsym=0,file=0,lib=0,line=0,mod=0,scope=1,seg={},span=0,sym={},type=0\n",
+        bank_count, symbols.len(),
+    ));
+    for bank in 0..bank_count {
+        let bank_offset = bank * mapper.prg_bank_size();
+        output.push_str(&format!(
+            "seg\tid={},name=\"BANK{}\",start=0x{:06X},size=0x{:04X},addrsize=absolute,type=ro,oname=\"\",ooffs={}\n",
+            bank, bank, mapper.cpu_address(bank_offset), mapper.prg_bank_size(), prg_file_offset + bank_offset,
+        ));
+    }
+    output.push_str("scope\tid=0,name=\"\",mod=0\n");
+    for (id, symbol) in symbols.iter().enumerate() {
+        match symbol.location {
+            Some(location) => output.push_str(&format!(
+                "sym\tid={},name=\"{}\",addrsize=absolute,scope=0,def=0,val=0x{:04X},seg={},type=lab\n",
+                id, symbol.name, mapper.cpu_address(location.prg_offset), location.bank,
+            )),
+            None => output.push_str(&format!(
+                "sym\tid={},name=\"{}\",addrsize={},scope=0,def=0,val=0x{:04X},type=equ\n",
+                id, symbol.name, if symbol.address < 0x100 { "zeropage" } else { "absolute" }, symbol.address,
+            )),
+        }
+    }
+    output
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+
+use crate::mapper::{Mapper, PRGROM_START};
+use crate::symbols::{Symbol, SymbolFileError, SymbolKind, SymbolTable};
+
+
+
+/// FCEUX names its symbol files per 16KB PRG-ROM bank, regardless of the mapper.
+const NL_BANK_SIZE: usize = 0x4000;
+
+
+
+/// Which address space an FCEUX `.nl` file covers, taken from its file name
+/// (`game.nes.ram.nl` or `game.nes.<bank>.nl`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NlFile {
+    Ram,
+    Bank(usize),
+}
+impl NlFile {
+    /// Determines the address space from a file name, e.g. `game.nes.3.nl`.
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let stem = name.strip_suffix(".nl")?;
+        match stem.rsplit_once('.')?.1 {
+            "ram" => Some(NlFile::Ram),
+            bank => bank.parse().ok().map(NlFile::Bank),
+        }
+    }
+    /// The suffix FCEUX expects after the ROM file name.
+    pub fn file_suffix(&self) -> String {
+        match self {
+            NlFile::Ram => "ram.nl".to_string(),
+            NlFile::Bank(bank) => format!("{}.nl", bank),
+        }
+    }
+}
+
+
+
+/// Parses an FCEUX `.nl` file, with lines like `$C000#Label#Comment`.
+pub fn import(text: &str, file: NlFile, mapper: &dyn Mapper) -> Result<SymbolTable, SymbolFileError> {
+    let mut parsed: Vec<Symbol> = vec![];
+    let mut continued = false;
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        // Multi-line comments end each continued line with a backslash.
+        if continued {
+            continued = line.ends_with('\\');
+            if let Some(comment) = parsed.last_mut().and_then(|symbol| symbol.comment.as_mut()) {
+                comment.push('\n');
+                comment.push_str(line.trim_end_matches('\\'));
+            }
+            continue;
+        }
+        let Some(entry) = line.strip_prefix('$') else {
+            continue;
+        };
+        let parse_error = |message: &str| SymbolFileError::Parse { line: idx + 1, message: message.to_string() };
+
+        let mut fields = entry.splitn(3, '#');
+        let address_field = fields.next().unwrap_or_default();
+        // Arrays are written as `$0300/20`; only their start is named.
+        let address_field = address_field.split_once('/').map_or(address_field, |(addr, _)| addr);
+        let address = u16::from_str_radix(address_field, 16)
+            .map_err(|_| parse_error("invalid address"))?;
+        let name = fields.next().ok_or_else(|| parse_error("missing label"))?;
+        if name.is_empty() {
+            continue;
+        }
+
+        let location = match file {
+            NlFile::Bank(bank) if address >= PRGROM_START => {
+                let prg_offset = bank * NL_BANK_SIZE + (address - PRGROM_START) as usize % NL_BANK_SIZE;
+                Some(mapper.location(prg_offset))
+            },
+            _ => None,
+        };
+        let comment = fields.next().unwrap_or_default();
+        continued = comment.ends_with('\\');
+        let mut symbol = Symbol::new(name, SymbolKind::User, address, location);
+        symbol.comment = Some(comment.trim_end_matches('\\').to_string()).filter(|comment| !comment.is_empty() || continued);
+        parsed.push(symbol);
+    }
+
+    let mut symbols = SymbolTable::default();
+    for symbol in parsed {
+        symbols.insert(symbol);
+    }
+    Ok(symbols)
+}
+
+/// Writes one `.nl` file per 16KB PRG-ROM bank with symbols, plus one for RAM.
+pub fn export(symbols: &SymbolTable) -> BTreeMap<NlFile, String> {
+    let mut files = BTreeMap::<NlFile, String>::new();
+    for symbol in symbols.iter() {
+        let file = match symbol.location {
+            Some(location) => NlFile::Bank(location.prg_offset / NL_BANK_SIZE),
+            None => NlFile::Ram,
+        };
+        let comment = symbol.comment.as_deref().unwrap_or_default().replace('\n', "\\\n");
+        files.entry(file).or_default()
+            .push_str(&format!("${:04X}#{}#{}\n", symbol.address, symbol.name, comment));
+    }
+    files
+}
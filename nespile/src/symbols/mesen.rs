@@ -0,0 +1,83 @@
+use crate::mapper::{Mapper, PRGROM_START};
+use crate::symbols::{Symbol, SymbolFileError, SymbolKind, SymbolTable};
+
+
+
+const INTERNAL_RAM_SIZE: u16 = 0x0800;
+const SAVE_RAM_START: u16 = 0x6000;
+const SAVE_RAM_END: u16 = 0x8000;
+
+
+
+/// Parses a Mesen `.mlb` file, with lines like `P:0ABC:Label:Comment`.
+///
+/// Both the Mesen 1 (`P`, `R`, `S`, `W`, `G`) and Mesen 2 (`NesPrgRom`, ...) memory types are understood.
+pub fn import(text: &str, mapper: &dyn Mapper) -> Result<SymbolTable, SymbolFileError> {
+    let mut symbols = SymbolTable::default();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parse_error = |message: &str| SymbolFileError::Parse { line: idx + 1, message: message.to_string() };
+
+        let mut fields = line.splitn(4, ':');
+        let memory_type = fields.next().unwrap_or_default();
+        let offset_field = fields.next().ok_or_else(|| parse_error("missing address"))?;
+        // Multi-byte labels are written as `0300-031F`; only their start is named.
+        let offset_field = offset_field.split_once('-').map_or(offset_field, |(start, _)| start);
+        let offset = usize::from_str_radix(offset_field, 16)
+            .map_err(|_| parse_error("invalid address"))?;
+        let name = fields.next().unwrap_or_default();
+        if name.is_empty() {
+            continue;
+        }
+
+        let (address, location) = match memory_type {
+            "P" | "NesPrgRom" => {
+                if offset >= mapper.prg_size() {
+                    return Err(parse_error("PRG-ROM offset out of range"));
+                }
+                (mapper.cpu_address(offset), Some(mapper.location(offset)))
+            },
+            "R" | "NesInternalRam" => (offset as u16, None),
+            "S" | "W" | "NesSaveRam" | "NesWorkRam" => (SAVE_RAM_START.wrapping_add(offset as u16), None),
+            // Cartridge addresses are only meaningful in whichever bank is mapped there at power on.
+            "G" | "NesMemory" if offset >= PRGROM_START as usize => {
+                let location = mapper.resolve(offset as u16, &mapper.power_on_context())
+                    .ok_or_else(|| parse_error("CPU address in a switchable bank; use a PRG-ROM label"))?;
+                (offset as u16, Some(location))
+            },
+            "G" | "NesMemory" => (offset as u16, None),
+            // CHR and other memory types have no CPU address.
+            _ => continue,
+        };
+        let mut symbol = Symbol::new(name, SymbolKind::User, address, location);
+        symbol.comment = fields.next()
+            .filter(|comment| !comment.is_empty())
+            .map(|comment| comment.replace("\\n", "\n"));
+        symbols.insert(symbol);
+    }
+    Ok(symbols)
+}
+
+/// Writes a Mesen `.mlb` file using the Mesen 1 memory types.
+pub fn export(symbols: &SymbolTable) -> String {
+    symbols.iter()
+        .map(|symbol| {
+            let (memory_type, offset) = match (symbol.location, symbol.address) {
+                (Some(location), _) => ("P", location.prg_offset),
+                (None, address) if address < INTERNAL_RAM_SIZE => ("R", address as usize),
+                (None, address) if (SAVE_RAM_START..SAVE_RAM_END).contains(&address) =>
+                    ("S", (address - SAVE_RAM_START) as usize),
+                (None, address) => ("G", address as usize),
+            };
+            let comment = symbol.comment.as_deref().unwrap_or_default().replace('\n', "\\n");
+            if comment.is_empty() {
+                format!("{}:{:04X}:{}\n", memory_type, offset, symbol.name)
+            } else {
+                format!("{}:{:04X}:{}:{}\n", memory_type, offset, symbol.name, comment)
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,85 @@
+use std::io::Cursor;
+
+use binrw::{BinRead, Endian};
+use nespile::assembler::{assemble, AssembleError};
+use nespile::parser::address_mode::{AddrModeNoZeropageY, AddrModeRelative};
+use nespile::parser::opcodes::Opcode;
+
+
+
+#[test]
+fn test_parse_to_source_string() {
+    for byte in 0..=255u8 {
+        let opcode = Opcode::read_options(&mut Cursor::new([byte, 0x34, 0x12]), Endian::Little, ())
+            .expect("Could not decode opcode");
+        let source = opcode.to_source_string();
+        assert_eq!(source.parse::<Opcode>(), Ok(opcode), "parsing '{}' from ${:02x}", source, byte);
+    }
+    assert_eq!("lda #$10".parse::<Opcode>(), Ok(Opcode::LDA(AddrModeNoZeropageY::Immediate(0x10))));
+    assert_eq!(Opcode::BNE(AddrModeRelative::Relative(0xf9)).to_source_string(), "BNE   *-$05");
+}
+
+#[test]
+fn test_assemble_branch_targets() {
+    // Bare numbers are targets, however many digits they are written with; offsets are relative to `*`.
+    let assembly = assemble(".org $0010
+BNE $14
+BEQ *-$05
+BCC *+$02").expect("Could not assemble branches");
+    assert_eq!(assembly.sections[0].bytes, vec![0xd0, 0x02, 0xf0, 0xf9, 0x90, 0x00]);
+    assert!(matches!(assemble("BNE $f9"), Err(AssembleError::OutOfRange { line: 1, .. })));
+}
+
+#[test]
+fn test_assemble_program() {
+    let assembly = assemble("
+        PPUCTRL = $2000
+        ptr = $10
+
+        .org $c000
+        reset:  LDX   #<table_end - table   ; forward references
+        loop:   LDA   table-1,X
+                STA   (ptr),Y
+                STA   PPUCTRL
+                LDA   ptr
+                LDA   $0010
+                DEX
+                BNE   loop
+                JMP   (vector)
+        table:  .byte 1, %10, 'A', \"hi\"
+        table_end:
+        vector: .word reset, * + 2
+
+        .org $fffc
+                .dw reset
+    ").expect("Could not assemble program");
+
+    assert_eq!(assembly.symbols["table"], 0xc015);
+    assert_eq!(assembly.sections.len(), 2);
+    assert_eq!(assembly.sections[0].origin, 0xc000);
+    assert_eq!(assembly.sections[0].bytes, vec![
+        0xa2, 0x05,             // LDX #5
+        0xbd, 0x14, 0xc0,       // LDA table-1,X
+        0x91, 0x10,             // STA ($10),Y
+        0x8d, 0x00, 0x20,       // STA $2000
+        0xa5, 0x10,             // LDA $10
+        0xad, 0x10, 0x00,       // LDA $0010
+        0xca,                   // DEX
+        0xd0, 0xf0,             // BNE loop
+        0x6c, 0x1a, 0xc0,       // JMP (vector)
+        0x01, 0x02, 0x41, 0x68, 0x69,
+        0x00, 0xc0, 0x1c, 0xc0,
+    ]);
+    assert_eq!(assembly.sections[1].origin, 0xfffc);
+    assert_eq!(assembly.sections[1].bytes, vec![0x00, 0xc0]);
+    assert_eq!(assembly.instructions().count(), 9);
+}
+
+#[test]
+fn test_assemble_errors() {
+    assert_eq!(assemble("JMP nowhere").unwrap_err(), AssembleError::UndefinedSymbol { line: 1, name: "nowhere".to_string() });
+    assert_eq!(assemble("STA #$10").unwrap_err(), AssembleError::InvalidOperand { line: 1, mnemonic: "STA".to_string() });
+    assert!(matches!(assemble("loop: BNE far\n.org $d000\nfar: RTS"), Err(AssembleError::OutOfRange { line: 1, .. })));
+    assert!(matches!(assemble("LDA #$100"), Err(AssembleError::OutOfRange { line: 1, value: 0x100 })));
+    assert!(matches!(assemble("a: a: NOP"), Err(AssembleError::DuplicateSymbol { .. })));
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use common::{lifted_program, CALL_LOOP};
+use nespile::codegen;
+use nespile::codegen::c::CSource;
+use nespile::mapper::PrgLocation;
+use nespile::symbols::{Symbol, SymbolKind};
+
+
+
+/// Compiles the source with the system's C compiler, if there is one.
+fn check_with_cc(generated: &CSource) {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("c_codegen");
+    fs::create_dir_all(&dir).expect("Could not create output directory");
+    fs::write(dir.join("game.h"), &generated.header).expect("Could not write header");
+    fs::write(dir.join("game.c"), &generated.source).expect("Could not write source");
+
+    let Ok(output) = Command::new("cc").args(["-std=c99", "-pedantic-errors", "-fsyntax-only", "game.c"]).current_dir(&dir).output() else {
+        return;
+    };
+    assert!(output.status.success(), "cc rejected the source:\n{}", String::from_utf8_lossy(&output.stderr));
+}
+
+
+
+#[test]
+fn test_generates_header_with_hooks_and_functions() {
+    let (program, symbols) = lifted_program(&CALL_LOOP);
+    let header = codegen::c::generate(&program, &symbols, "game").header;
+
+    assert!(header.starts_with("/* Transpiled by nespile. */\n#ifndef GAME_H\n#define GAME_H\n"), "{}", header);
+    assert!(header.contains("uint8_t nes_read(uint16_t address);\nvoid nes_write(uint16_t address, uint8_t value);\n"), "{}", header);
+    assert!(header.contains("void nes_reset(nes_cpu *cpu);\nvoid nes_nmi(nes_cpu *cpu);\nvoid nes_irq(nes_cpu *cpu);\n"), "{}", header);
+    assert!(header.contains("void reset(nes_cpu *cpu); /* $C000, bank 0 */\nvoid sub_C00A(nes_cpu *cpu); /* $C00A, bank 0 */\nvoid nmi(nes_cpu *cpu); /* $C00D, bank 0 */\n"), "{}", header);
+}
+
+#[test]
+fn test_generates_labelled_blocks() {
+    let (program, symbols) = lifted_program(&CALL_LOOP);
+    let source = codegen::c::generate(&program, &symbols, "game").source;
+
+    assert!(source.contains("#include \"game.h\"\n"), "{}", source);
+    assert!(source.contains("void nes_nmi(nes_cpu *cpu) {\n    nes_interrupt(cpu);\n    nmi(cpu);\n}\n"), "{}", source);
+    assert!(source.contains("void reset(nes_cpu *cpu) {\n    goto b0;\nb0: { /* $C000 */\n    cpu->x = 0x03;\n"), "{}", source);
+    assert!(source.contains("    sub_C00A(cpu); goto b5;\n"), "{}", source);
+    assert!(source.contains("    if ((!cpu->z)) { goto b2; } else { goto b8; }\n"), "{}", source);
+    assert!(source.contains("b8: { /* $C008 */\n    nes_halt(cpu); return;\n}\n"), "{}", source);
+    // Stack accesses go through RAM, and the return address is only read
+    assert!(source.contains("    nes_store((0x0100 | ((uint16_t)cpu->sp)), 0xc0);\n"), "{}", source);
+    assert!(source.contains("    (void)(((uint16_t)t1) | ((uint16_t)(((uint16_t)t2) << 0x08)));\n    return;\n"), "{}", source);
+}
+
+#[test]
+fn test_accesses_constant_addresses_directly() {
+    let code = [
+        0xad, 0x02, 0x20,   // $C000: LDA $2002
+        0x8d, 0x01, 0x08,   // $C003: STA $0801
+        0x02,               // $C006: STP
+    ];
+    let (program, symbols) = lifted_program(&code);
+    let source = codegen::c::generate(&program, &symbols, "game").source;
+
+    assert!(source.contains("    uint8_t t0 = nes_read(0x2002);\n    cpu->a = t0;\n"), "{}", source);
+    assert!(source.contains("    nes_ram[0x001] = cpu->a;\n"), "{}", source);
+}
+
+#[test]
+fn test_avoids_library_names() {
+    let (program, mut symbols) = lifted_program(&CALL_LOOP);
+    for (prg_offset, name) in [(0x0, "rand"), (0xa, "uint32_t"), (0xd, "INT8_MAX")] {
+        let location = PrgLocation { bank: 0, prg_offset };
+        symbols.insert(Symbol::new(name, SymbolKind::User, 0xc000 + prg_offset as u16, Some(location)));
+    }
+    let generated = codegen::c::generate(&program, &symbols, "game");
+
+    // Nothing includes `stdlib.h`, so its names are free.
+    assert!(generated.header.contains("void rand(nes_cpu *cpu); /* $C000, bank 0 */\n"), "{}", generated.header);
+    assert!(generated.header.contains("void uint32_t_b0(nes_cpu *cpu); /* $C00A, bank 0 */\n"), "{}", generated.header);
+    assert!(generated.header.contains("void INT8_MAX_b0(nes_cpu *cpu); /* $C00D, bank 0 */\n"), "{}", generated.header);
+    check_with_cc(&generated);
+}
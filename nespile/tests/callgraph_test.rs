@@ -0,0 +1,59 @@
+mod common;
+
+use std::collections::BTreeSet;
+
+use common::make_program;
+use nespile::analysis::callgraph::CallGraph;
+use nespile::analysis::cfg::ControlFlowGraph;
+use nespile::parser::disassembler::Vector;
+use nespile::symbols::SymbolTable;
+
+
+
+const CODE: [u8; 22] = [
+    0x20, 0x08, 0xc0,   // $C000: JSR $C008
+    0x4c, 0x00, 0xc0,   // $C003: JMP $C000
+    0xff, 0xff,         // $C006: (data)
+    0xca,               // $C008: DEX
+    0xf0, 0x03,         // $C009: BEQ $C00E
+    0x20, 0x08, 0xc0,   // $C00B: JSR $C008
+    0x60,               // $C00E: RTS
+    0x20, 0x13, 0xc0,   // $C00F: JSR $C013
+    0x40,               // $C012: RTI
+    0x4c, 0x08, 0xc0,   // $C013: JMP $C008
+];
+
+
+#[test]
+fn test_callers_and_callees() {
+    let program = make_program(&CODE, 0xc00f);
+    let cfg = ControlFlowGraph::build(&program);
+    let graph = CallGraph::build(&cfg);
+
+    assert_eq!(graph.callees.keys().copied().collect::<Vec<_>>(), vec![0x0, 0x8, 0xf, 0x13]);
+    assert_eq!(graph.callees[&0x0], BTreeSet::from([0x8]));
+    assert_eq!(graph.callees[&0xf], BTreeSet::from([0x13]));
+    // A jump into another function is a tail call.
+    assert_eq!(graph.callees[&0x13], BTreeSet::from([0x8]));
+    assert_eq!(graph.callers[&0x8], BTreeSet::from([0x0, 0x8, 0x13]));
+
+    assert!(graph.is_recursive(0x8));
+    assert!(!graph.is_recursive(0x0));
+    assert!(graph.unreachable().is_empty());
+}
+
+#[test]
+fn test_vector_reachability() {
+    let program = make_program(&CODE, 0xc00f);
+    let cfg = ControlFlowGraph::build(&program);
+    let graph = CallGraph::build(&cfg);
+
+    assert_eq!(graph.reached_from_vector(Vector::NMI), BTreeSet::from([0x8, 0xf, 0x13]));
+    assert_eq!(graph.reached_from_vector(Vector::Reset), BTreeSet::from([0x0, 0x8]));
+    assert_eq!(graph.vectors_reaching(0x13), vec![Vector::NMI]);
+    assert_eq!(graph.vectors_reaching(0x8), vec![Vector::NMI, Vector::Reset, Vector::IRQ]);
+
+    let report = graph.report(&cfg, &SymbolTable::generate(&program));
+    assert!(report.contains("nmi ($c00f, bank 0)\n    runs from: NMI\n    calls: sub_C013\n    called by: -\n"));
+    assert!(report.contains("    called by: reset, sub_C008, sub_C013\n    recursive\n"));
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::make_file;
+use nespile::cdl::{CdlError, CodeDataLog, CODE, DATA, INDIRECT_CODE};
+use nespile::parser::NesProgram;
+use nespile::parser::disassembler::ByteKind;
+
+
+
+const CODE_BYTES: [u8; 12] = [
+    0x20, 0x07, 0xc0,   // $C000: JSR $C007, which never returns
+    0xa9, 0x00,         // $C003: data, which would decode as LDA #$00
+    0xe8,               // $C005: INX, only reached in play
+    0x60,               // $C006: RTS
+    0x68,               // $C007: PLA
+    0x68,               // $C008: PLA
+    0x4c, 0x00, 0xc0,   // $C009: JMP $C000
+];
+
+
+#[test]
+fn test_seeds_code_and_data() {
+    let file = make_file(&CODE_BYTES, 0xc000, 0xc000, 0xc000, 0x2000);
+    let mut log = vec![0u8; 0x4000 + 0x2000];
+    log[0x3..0x5].fill(DATA);
+    log[0x5..0x7].fill(CODE | 0x08);
+    let cdl = CodeDataLog::parse(&log, 0x4000, 0x2000).unwrap();
+
+    let program = NesProgram::try_from(&file).unwrap();
+    assert_eq!(program.byte_kinds[0x3], ByteKind::Opcode);
+
+    let program = NesProgram::with_code_data_log(&file, &cdl).unwrap();
+    assert_eq!(program.byte_kinds[0x3..0x5], [ByteKind::Data, ByteKind::Data]);
+    assert_eq!(program.instruction_at(0xc005).map(|instruction| instruction.prg_offset), Some(0x5));
+    assert!(program.instructions.contains_key(&0x6));
+    assert_eq!(cdl.coverage(&program), (2, 2));
+}
+
+#[test]
+fn test_exports_disassembly() {
+    let file = make_file(&CODE_BYTES, 0xc000, 0xc000, 0xc000, 0x2000);
+    let program = NesProgram::try_from(&file).unwrap();
+
+    let cdl = CodeDataLog::from_program(&program, file.chrrom_data.len());
+    assert_eq!(cdl.prg[..3], [CODE | 0x08; 3]);
+    assert_eq!(cdl.prg[0x3ffa], 0);
+    assert!(cdl.prg.iter().all(|flags| flags & INDIRECT_CODE == 0));
+
+    let bytes = cdl.to_bytes();
+    assert_eq!(bytes.len(), 0x6000);
+    assert_eq!(CodeDataLog::parse(&bytes, 0x4000, 0x2000).unwrap(), cdl);
+    assert!(matches!(
+        CodeDataLog::parse(&bytes[1..], 0x4000, 0x2000),
+        Err(CdlError::SizeMismatch { expected: 0x6000, found: 0x5fff })
+    ));
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::make_program;
+use nespile::analysis::cfg::{ControlFlowGraph, Edge, EdgeKind};
+use nespile::cpu::ControlFlow;
+use nespile::parser::disassembler::Vector;
+
+
+
+const CODE: [u8; 18] = [
+    0xa2, 0x00,         // $C000: LDX #$00
+    0x20, 0x0b, 0xc0,   // $C002: JSR $C00B
+    0xe8,               // $C005: INX
+    0xd0, 0xfa,         // $C006: BNE $C002
+    0x4c, 0x00, 0xc0,   // $C008: JMP $C000
+    0xa9, 0x01,         // $C00B: LDA #$01
+    0xf0, 0x01,         // $C00D: BEQ $C010
+    0x60,               // $C00F: RTS
+    0xea,               // $C010: NOP
+    0x60,               // $C011: RTS
+];
+
+
+#[test]
+fn test_basic_blocks_and_edges() {
+    let program = make_program(&CODE, 0xc011);
+    let cfg = ControlFlowGraph::build(&program);
+
+    assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), vec![0x0, 0x2, 0x5, 0x8, 0xb, 0xf, 0x10, 0x11]);
+    assert_eq!(cfg.blocks[&0x0].instructions.len(), 1);
+    assert_eq!(cfg.blocks[&0xb].instructions.len(), 2);
+    assert_eq!(cfg.blocks[&0xb].exit(), ControlFlow::Branch);
+    assert_eq!(cfg.block_containing(0xe).map(|block| block.id), Some(0xb));
+
+    let edge = |from, to, kind| Edge { from, to, kind };
+    assert_eq!(cfg.edges, vec![
+        edge(0x0, 0x2, EdgeKind::Fallthrough),
+        edge(0x2, 0x5, EdgeKind::Fallthrough),
+        edge(0x2, 0xb, EdgeKind::Call),
+        edge(0x5, 0x2, EdgeKind::Branch),
+        edge(0x5, 0x8, EdgeKind::Fallthrough),
+        edge(0x8, 0x0, EdgeKind::Jump),
+        edge(0xb, 0xf, EdgeKind::Fallthrough),
+        edge(0xb, 0x10, EdgeKind::Branch),
+        edge(0xf, 0x5, EdgeKind::Return),
+        edge(0x10, 0x11, EdgeKind::Fallthrough),
+        edge(0x11, 0x5, EdgeKind::Return),
+    ]);
+}
+
+#[test]
+fn test_functions() {
+    let program = make_program(&CODE, 0xc011);
+    let cfg = ControlFlowGraph::build(&program);
+
+    assert_eq!(cfg.vectors, vec![(Vector::NMI, 0x11), (Vector::Reset, 0x0), (Vector::IRQ, 0x0)]);
+    assert_eq!(cfg.functions.keys().copied().collect::<Vec<_>>(), vec![0x0, 0xb, 0x11]);
+    assert_eq!(cfg.functions[&0x0].blocks.iter().copied().collect::<Vec<_>>(), vec![0x0, 0x2, 0x5, 0x8]);
+    // The NMI handler is also the subroutine's tail.
+    assert_eq!(cfg.functions[&0xb].blocks.iter().copied().collect::<Vec<_>>(), vec![0xb, 0xf, 0x10, 0x11]);
+    assert_eq!(cfg.functions[&0x11].blocks.len(), 1);
+}
@@ -0,0 +1,64 @@
+//! ROM-building fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use std::io::Cursor;
+
+use binrw::BinRead;
+use nespile::analysis::cfg::ControlFlowGraph;
+use nespile::ir;
+use nespile::parser::NesProgram;
+use nespile::parser::rom::NesFile;
+use nespile::symbols::SymbolTable;
+
+
+
+/// NROM-128 ROM image with `code` at $C000, the given NMI, reset and IRQ vectors, and `chr_size` bytes of
+/// CHR-ROM filled with $55.
+pub fn make_rom(code: &[u8], nmi: u16, reset: u16, irq: u16, chr_size: usize) -> Vec<u8> {
+    let mut prgrom = vec![0xffu8; 0x4000];
+    prgrom[..code.len()].copy_from_slice(code);
+    prgrom[0x3ffa..].copy_from_slice(&[
+        nmi as u8, (nmi >> 8) as u8,
+        reset as u8, (reset >> 8) as u8,
+        irq as u8, (irq >> 8) as u8,
+    ]);
+
+    let mut rom = b"NES\x1a\x01\x00\x00\x00".to_vec();
+    rom[5] = (chr_size / 0x2000) as u8;
+    rom.extend_from_slice(&[0u8; 8]);
+    rom.extend_from_slice(&prgrom);
+    rom.resize(rom.len() + chr_size, 0x55);
+    rom
+}
+
+/// [`make_rom`], parsed.
+pub fn make_file(code: &[u8], nmi: u16, reset: u16, irq: u16, chr_size: usize) -> NesFile {
+    NesFile::read(&mut Cursor::new(make_rom(code, nmi, reset, irq, chr_size))).expect("Could not parse ROM")
+}
+
+/// NROM-128 program with `code` at $C000 and the NMI at `nmi`; reset and IRQ point at $C000.
+pub fn make_program(code: &[u8], nmi: u16) -> NesProgram {
+    NesProgram::try_from(&make_file(code, nmi, 0xc000, 0xc000, 0)).expect("Could not disassemble ROM")
+}
+
+/// Lifts `code` with reset at $C000 and both NMI and IRQ at $C00D, as in [`CALL_LOOP`].
+pub fn lifted_program(code: &[u8]) -> (ir::Program, SymbolTable) {
+    let file = make_file(code, 0xc00d, 0xc000, 0xc00d, 0);
+    let program = NesProgram::try_from(&file).expect("Could not disassemble ROM");
+    let symbols = SymbolTable::generate(&program);
+    (ir::lift_program(&ControlFlowGraph::build(&program)), symbols)
+}
+
+/// A loop calling a subroutine, ending in `STP`, with an `RTI` for the interrupt vectors.
+pub const CALL_LOOP: [u8; 14] = [
+    0xa2, 0x03,         // $C000: LDX #$03
+    0x20, 0x0a, 0xc0,   // $C002: JSR $C00A
+    0xca,               // $C005: DEX
+    0xd0, 0xfa,         // $C006: BNE $C002
+    0x02,               // $C008: STP
+    0xea,               // $C009: NOP
+    0x0a,               // $C00A: ASL A
+    0x60,               // $C00B: RTS
+    0x00,               // $C00C: BRK
+    0x40,               // $C00D: RTI
+];
@@ -0,0 +1,59 @@
+use nespile::cpu::{cycle_table, Cycles};
+use nespile::parser::address_mode::{AddrModeNoZeropageY, AddrModeRelative};
+use nespile::parser::opcodes::Opcode;
+
+
+
+/// Base cycles of every opcode, per https://www.nesdev.org/wiki/CPU_unofficial_opcodes
+const BASE_CYCLES: [u8; 256] = [
+    7, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    6, 6, 2, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    6, 6, 2, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+    2, 6, 2, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5,
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4,
+    2, 5, 2, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4,
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6,
+    2, 5, 2, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7,
+];
+
+/// Opcodes taking an extra cycle when indexing crosses a page.
+const PAGE_CROSSING: [u8; 32] = [
+    0x11, 0x19, 0x1c, 0x1d, 0x31, 0x39, 0x3c, 0x3d, 0x51, 0x59, 0x5c, 0x5d, 0x71, 0x79, 0x7c, 0x7d,
+    0xb1, 0xb3, 0xb9, 0xbb, 0xbc, 0xbd, 0xbe, 0xbf, 0xd1, 0xd9, 0xdc, 0xdd, 0xf1, 0xf9, 0xfc, 0xfd,
+];
+
+
+#[test]
+fn test_cycle_table() {
+    let table = cycle_table();
+    for byte in 0..=255u8 {
+        let cycles = table[byte as usize];
+        assert_eq!(cycles.base, BASE_CYCLES[byte as usize], "base cycles of ${:02x}", byte);
+
+        let is_branch = byte & 0x1f == 0x10;
+        let crosses = PAGE_CROSSING.contains(&byte) || is_branch;
+        assert_eq!(cycles.page_cross, crosses as u8, "page crossing of ${:02x}", byte);
+        assert_eq!(cycles.branch_taken, is_branch as u8, "branch penalty of ${:02x}", byte);
+    }
+}
+
+#[test]
+fn test_cycle_totals() {
+    let lda = Opcode::LDA(AddrModeNoZeropageY::AbsoluteX(0x1234)).cycles();
+    assert_eq!(lda.total(false, false), 4);
+    assert_eq!(lda.total(true, false), 5);
+
+    let bne = Opcode::BNE(AddrModeRelative::Relative(0x10)).cycles();
+    assert_eq!(bne, Cycles { base: 2, page_cross: 1, branch_taken: 1 });
+    assert_eq!(bne.total(true, false), 2);
+    assert_eq!(bne.total(false, true), 3);
+    assert_eq!(bne.total(true, true), 4);
+}
@@ -0,0 +1,497 @@
+use std::io::Cursor;
+
+use binrw::BinRead;
+use nespile::mapper::{Mapper, NRom, PrgLocation, UxRom};
+use nespile::parser::disassembler::{ByteKind, Disassembler, DisassemblyWarning, UnresolvedTarget, Vector};
+use nespile::parser::disassembler::inline_data::InlineData;
+use nespile::parser::disassembler::jump_table::{Dispatch, JumpTable};
+use nespile::parser::opcodes::Opcode;
+use nespile::parser::NesProgram;
+use nespile::parser::rom::NesFile;
+
+
+
+/// 16KB PRG-ROM (mirrored at $8000 and $C000) with `code` at $C000.
+fn make_prgrom(code: &[u8], nmi: u16, reset: u16, irq: u16) -> Vec<u8> {
+    let mut prgrom = vec![0xffu8; 0x4000];
+    prgrom[..code.len()].copy_from_slice(code);
+    prgrom[0x3ffa..].copy_from_slice(&[
+        nmi as u8, (nmi >> 8) as u8,
+        reset as u8, (reset >> 8) as u8,
+        irq as u8, (irq >> 8) as u8,
+    ]);
+    prgrom
+}
+
+
+#[test]
+fn test_follows_control_flow_from_vectors() {
+    let prgrom = make_prgrom(&[
+        0xa9, 0x01,         // $C000: LDA #$01
+        0xf0, 0x03,         // $C002: BEQ $C007
+        0x4c, 0x09, 0xc0,   // $C004: JMP $C009
+        0x60,               // $C007: RTS
+        0xff,               // $C008: (data)
+        0x20, 0x0d, 0xc0,   // $C009: JSR $C00D
+        0x00,               // $C00C: BRK
+        0x60,               // $C00D: RTS
+    ], 0xc007, 0xc000, 0xc007);
+
+    let mapper = NRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    assert_eq!(
+        disassembly.instructions.keys().copied().collect::<Vec<usize>>(),
+        vec![ 0x0, 0x2, 0x4, 0x7, 0x9, 0xc, 0xd ]
+    );
+    assert!(matches!(disassembly.instructions[&0x4].opcode, Opcode::JMP(_)));
+    assert_eq!(disassembly.instructions[&0x4].address, 0xc004);
+    assert_eq!(disassembly.instructions[&0x4].bytes, vec![ 0x4c, 0x09, 0xc0 ]);
+    assert_eq!(disassembly.byte_kinds[0x8], ByteKind::Data);
+    assert_eq!(disassembly.byte_kinds[0xa], ByteKind::Operand);
+    assert!(disassembly.byte_kinds[0xe..].iter().all(|kind| *kind == ByteKind::Data));
+    assert_eq!(disassembly.vectors, vec![
+        (Vector::NMI, 0xc007),
+        (Vector::Reset, 0xc000),
+        (Vector::IRQ, 0xc007),
+    ]);
+}
+#[test]
+fn test_stops_at_indirect_jump() {
+    let prgrom = make_prgrom(&[
+        0x6c, 0x00, 0x02,   // $C000: JMP ($0200)
+        0xea,               // $C003: (data)
+    ], 0xc000, 0xc000, 0xc000);
+
+    let mapper = NRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    assert_eq!(disassembly.instructions.len(), 1);
+    assert_eq!(disassembly.byte_kinds[0x3], ByteKind::Data);
+}
+#[test]
+fn test_resolves_banks_through_mapper() {
+    // UxROM with four 16KB banks; the last is fixed at $C000.
+    let mut prgrom = make_prgrom(&[
+        0x20, 0x00, 0x80,   // $C000: JSR $8000 (switchable, bank unknown)
+        0x4c, 0x06, 0xc0,   // $C003: JMP $C006
+        0x60,               // $C006: RTS
+    ], 0xc006, 0xc000, 0xc006);
+    prgrom.splice(0..0, vec![0xffu8; 3 * 0x4000]);
+
+    let mapper = UxRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    let first = &disassembly.instructions[&0xc000];
+    assert_eq!(first.address, 0xc000);
+    assert_eq!(first.bank, 3);
+    assert_eq!(mapper.cpu_address(0xc006), 0xc006);
+    assert_eq!(mapper.cpu_address(0x4006), 0x8006);
+    assert_eq!(disassembly.unresolved, vec![ UnresolvedTarget { from: 0xc000, target: 0x8000 } ]);
+}
+#[test]
+fn test_falls_back_for_unsupported_mapper() {
+    // MMC5 with four 16KB banks; the last 32KB is assumed to be at $8000.
+    let mut prgrom = make_prgrom(&[
+        0x4c, 0x00, 0x80,   // $C000: JMP $8000
+    ], 0xc000, 0xc000, 0xc000);
+    let mut banks = vec![0xffu8; 3 * 0x4000];
+    banks[0x8000] = 0x60;   // $8000 in the last 32KB: RTS
+    prgrom.splice(0..0, banks);
+    let mut rom = b"NES\x1a\x04\x00\x50\x00".to_vec();
+    rom.extend_from_slice(&[0u8; 8]);
+    rom.extend_from_slice(&prgrom);
+    let file = NesFile::read(&mut Cursor::new(rom)).expect("Could not parse ROM");
+    let program = NesProgram::try_from(&file).expect("Could not disassemble ROM");
+
+    assert_eq!(program.mapper.number(), 5);
+    assert_eq!(program.instructions.keys().copied().collect::<Vec<usize>>(), vec![ 0x8000, 0xc000 ]);
+    assert_eq!(program.instruction_at(0xc000).map(|i| i.bank), Some(1));
+    assert_eq!(program.warnings, vec![ DisassemblyWarning::UnsupportedMapper { number: 5 } ]);
+}
+#[test]
+fn test_resolves_calls_after_bank_switch() {
+    // UxROM: the fixed bank selects bank 1 through a subroutine, then calls into it.
+    let mut prgrom = make_prgrom(&[
+        0xa9, 0x01,         // $C000: LDA #$01
+        0x20, 0x09, 0xc0,   // $C002: JSR $C009
+        0x20, 0x00, 0x80,   // $C005: JSR $8000
+        0x60,               // $C008: RTS
+        0x8d, 0x00, 0xc0,   // $C009: STA $C000
+        0x60,               // $C00C: RTS
+    ], 0xc008, 0xc000, 0xc008);
+    let mut banks = vec![0xffu8; 3 * 0x4000];
+    banks[0x4000] = 0x60;   // bank 1, $8000: RTS
+    prgrom.splice(0..0, banks);
+
+    let mapper = UxRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    let call = &disassembly.instructions[&0xc005];
+    assert_eq!(call.target.map(|t| t.bank), Some(1));
+    assert!(matches!(disassembly.instructions[&0x4000].opcode, Opcode::RTS));
+    assert!(disassembly.unresolved.is_empty());
+    assert_eq!(disassembly.bank_switches.len(), 1);
+    assert_eq!(disassembly.bank_switches[0].prg_offset, 0xc009);
+    assert_eq!(disassembly.bank_switches[0].bank, Some(1));
+}
+#[test]
+fn test_forgets_banks_after_calls_that_may_switch() {
+    // UxROM: after selecting bank 1, calls too long to simulate only lose it if they write the register.
+    let mut prgrom = make_prgrom(&[
+        0xa9, 0x01,         // $C000: LDA #$01
+        0x20, 0x13, 0xc0,   // $C002: JSR $C013
+        0x20, 0x17, 0xc0,   // $C005: JSR $C017
+        0x20, 0x00, 0x80,   // $C008: JSR $8000 (bank 1)
+        0x20, 0x1b, 0xc0,   // $C00B: JSR $C01B
+        0x20, 0x00, 0x80,   // $C00E: JSR $8000 (bank unknown)
+        0x60,               // $C011: RTS
+        0xea,
+        0x8d, 0x00, 0xc0,   // $C013: STA $C000
+        0x60,               // $C016: RTS
+        0xca,               // $C017: DEX
+        0xd0, 0xfd,         // $C018: BNE $C017
+        0x60,               // $C01A: RTS
+        0xca,               // $C01B: DEX
+        0xd0, 0xfd,         // $C01C: BNE $C01B
+        0x8e, 0x00, 0xc0,   // $C01E: STX $C000
+        0x60,               // $C021: RTS
+    ], 0xc011, 0xc000, 0xc011);
+    let mut banks = vec![0xffu8; 3 * 0x4000];
+    banks[0x4000] = 0x60;   // bank 1, $8000: RTS
+    prgrom.splice(0..0, banks);
+
+    let mapper = UxRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    assert_eq!(disassembly.instructions[&0xc008].target.map(|t| t.bank), Some(1));
+    assert_eq!(disassembly.instructions[&0xc00e].target, None);
+    assert_eq!(disassembly.unresolved, vec![ UnresolvedTarget { from: 0xc00e, target: 0x8000 } ]);
+}
+#[test]
+fn test_forgets_registers_where_paths_join() {
+    // UxROM: a branch skips the second load, so the bank stored depends on the path taken.
+    let mut prgrom = make_prgrom(&[
+        0xa9, 0x01,         // $C000: LDA #$01
+        0xa6, 0x00,         // $C002: LDX $00
+        0xf0, 0x02,         // $C004: BEQ $C008
+        0xa9, 0x02,         // $C006: LDA #$02
+        0x8d, 0x00, 0x80,   // $C008: STA $8000
+        0x20, 0x00, 0x80,   // $C00B: JSR $8000 (bank unknown)
+        0xa9, 0x01,         // $C00E: LDA #$01
+        0x8d, 0x00, 0x80,   // $C010: STA $8000
+        0xa6, 0x00,         // $C013: LDX $00
+        0xd0, 0xf9,         // $C015: BNE $C010
+        0x60,               // $C017: RTS
+    ], 0xc017, 0xc000, 0xc017);
+    let mut banks = vec![0xffu8; 3 * 0x4000];
+    banks[0x4000] = 0x60;   // bank 1, $8000: RTS
+    banks[0x8000] = 0x60;   // bank 2, $8000: RTS
+    prgrom.splice(0..0, banks);
+
+    let mapper = UxRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    assert_eq!(disassembly.instructions[&0xc00b].target, None);
+    assert_eq!(disassembly.unresolved, vec![ UnresolvedTarget { from: 0xc00b, target: 0x8000 } ]);
+    // The branch back to $C010, found after the store, lands between its load and the store.
+    let switches: Vec<_> = disassembly.bank_switches.iter().map(|switch| (switch.prg_offset, switch.bank)).collect();
+    assert_eq!(switches, vec![ (0xc008, None), (0xc010, None) ]);
+}
+#[test]
+fn test_branch_targets_and_warnings() {
+    let mut prgrom = make_prgrom(&[
+        0xd0, 0xfe,         // $C000: BNE $C000
+        0x60,               // $C002: RTS
+    ], 0xc000, 0xfff0, 0xc000);
+    prgrom[0x3ff0..0x3ff3].copy_from_slice(&[
+        0xd0, 0x20,         // $FFF0: BNE $0012
+        0x60,               // $FFF2: RTS
+    ]);
+
+    let mapper = NRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    let backwards = &disassembly.instructions[&0x0];
+    assert_eq!(backwards.branch_target(), Some(0xc000));
+    assert!(!backwards.branch_wraps());
+    assert_eq!(backwards.to_source_string(), "BNE   $c000");
+
+    let wrapping = &disassembly.instructions[&0x3ff0];
+    assert_eq!(wrapping.branch_target(), Some(0x0012));
+    assert!(wrapping.branch_wraps());
+    assert_eq!(disassembly.warnings, vec![
+        DisassemblyWarning::BranchWraparound { from: wrapping.location(), target: 0x0012 },
+    ]);
+}
+
+#[test]
+fn test_recovers_jump_tables() {
+    let mut code = vec![
+        0xa2, 0x01,         // $C000: LDX #$01
+        0xe0, 0x02,         // $C002: CPX #$02
+        0xb0, 0x0d,         // $C004: BCS $C013
+        0xbd, 0x20, 0xc0,   // $C006: LDA $C020,X
+        0x85, 0x00,         // $C009: STA $00
+        0xbd, 0x22, 0xc0,   // $C00B: LDA $C022,X
+        0x85, 0x01,         // $C00E: STA $01
+        0x6c, 0x00, 0x00,   // $C010: JMP ($0000)
+        0x60,               // $C013: RTS
+        0xa0, 0x00,         // $C014: LDY #$00
+        0xb9, 0x25, 0xc0,   // $C016: LDA $C025,Y
+        0x48,               // $C019: PHA
+        0xb9, 0x24, 0xc0,   // $C01A: LDA $C024,Y
+        0x48,               // $C01D: PHA
+        0x60,               // $C01E: RTS
+        0xff,
+        0x14, 0x30,         // $C020: split low bytes, sized by the CPX
+        0xc0, 0xc0,         // $C022: split high bytes
+        0x2f, 0xc0,         // $C024: words of address - 1, ended by $FFFF
+    ];
+    code.resize(0x30, 0xff);
+    code.extend_from_slice(&[
+        0xe8,               // $C030: INX
+        0x60,               // $C031: RTS
+    ]);
+    let prgrom = make_prgrom(&code, 0xc013, 0xc000, 0xc013);
+
+    let mapper = NRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    let location = |prg_offset| PrgLocation { bank: 0, prg_offset };
+    assert_eq!(disassembly.jump_tables, vec![
+        JumpTable {
+            prg_offset: 0x10,
+            dispatch: Dispatch::IndirectJump,
+            lo_table: 0xc020,
+            hi_table: 0xc022,
+            stride: 1,
+            limit: Some(2),
+            targets: vec![(0xc014, location(0x14)), (0xc030, location(0x30))],
+        },
+        JumpTable {
+            prg_offset: 0x1e,
+            dispatch: Dispatch::PushedReturn,
+            lo_table: 0xc024,
+            hi_table: 0xc025,
+            stride: 2,
+            limit: None,
+            targets: vec![(0xc030, location(0x30))],
+        },
+    ]);
+    assert!(disassembly.instructions.contains_key(&0x30));
+    assert!(disassembly.byte_kinds[0x20..0x26].iter().all(|kind| *kind == ByteKind::Data));
+}
+#[test]
+fn test_bounds_jump_tables_only_by_their_index() {
+    let mut code = vec![
+        0xa5, 0x10,         // $C000: LDA $10
+        0xc9, 0x05,         // $C002: CMP #$05, of a value other than the index
+        0xb0, 0x0f,         // $C004: BCS $C015
+        0xa6, 0x11,         // $C006: LDX $11
+        0xbd, 0x40, 0xc0,   // $C008: LDA $C040,X
+        0x85, 0x00,         // $C00B: STA $00
+        0xbd, 0x42, 0xc0,   // $C00D: LDA $C042,X
+        0x85, 0x01,         // $C010: STA $01
+        0x6c, 0x00, 0x00,   // $C012: JMP ($0000)
+        0x60,               // $C015: RTS
+        0xa5, 0x12,         // $C016: LDA $12
+        0xc9, 0x02,         // $C018: CMP #$02
+        0xb0, 0xf9,         // $C01A: BCS $C015
+        0x0a,               // $C01C: ASL A
+        0xa8,               // $C01D: TAY, so the index is what was compared
+        0xb9, 0x49, 0xc0,   // $C01E: LDA $C049,Y
+        0x48,               // $C021: PHA
+        0xb9, 0x48, 0xc0,   // $C022: LDA $C048,Y
+        0x48,               // $C025: PHA
+        0x60,               // $C026: RTS
+    ];
+    code.resize(0x30, 0xff);
+    code.extend_from_slice(&[
+        0xe8,               // $C030: INX
+        0x60,               // $C031: RTS
+    ]);
+    code.resize(0x40, 0xff);
+    code.extend_from_slice(&[
+        0x15, 0xff,         // $C040: split low bytes
+        0xc0, 0xff,         // $C042: split high bytes
+        0xff, 0xff, 0xff, 0xff,
+        0x14, 0xc0,         // $C048: words of address - 1, sized by the CMP
+        0x2f, 0xc0,
+    ]);
+    let prgrom = make_prgrom(&code, 0xc016, 0xc000, 0xc015);
+
+    let mapper = NRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    let tables: Vec<_> = disassembly.jump_tables.iter()
+        .map(|table| (table.prg_offset, table.limit, table.targets.iter().map(|(address, _)| *address).collect::<Vec<_>>()))
+        .collect();
+    assert_eq!(tables, vec![
+        (0x12, None, vec![0xc015]),
+        (0x26, Some(2), vec![0xc015, 0xc030]),
+    ]);
+}
+#[test]
+fn test_stops_unbounded_jump_tables() {
+    let mut code = vec![
+        0xa2, 0x00,         // $C000: LDX #$00
+        0xad, 0x26, 0xc0,   // $C002: LDA $C026
+        0xbd, 0x22, 0xc0,   // $C005: LDA $C022,X
+        0x85, 0x00,         // $C008: STA $00
+        0xbd, 0x23, 0xc0,   // $C00A: LDA $C023,X
+        0x85, 0x01,         // $C00D: STA $01
+        0x6c, 0x00, 0x00,   // $C00F: JMP ($0000)
+        0xa0, 0x00,         // $C012: LDY #$00
+        0xb9, 0x2b, 0xc0,   // $C014: LDA $C02B,Y
+        0x48,               // $C017: PHA
+        0xb9, 0x2a, 0xc0,   // $C018: LDA $C02A,Y
+        0x48,               // $C01B: PHA
+        0x60,               // $C01C: RTS
+        0xff, 0xff, 0xff, 0xff, 0xff,
+        0x12, 0xc0,         // $C022: words, the first taken on trust
+        0x00, 0xc0,         // $C024: already reached
+        0x00, 0xc0,         // $C026: read by the LDA at $C002
+        0xff, 0xff,
+        0x2f, 0xc0,         // $C02A: words of address - 1
+        0x47, 0xc2,         // $C02C: data following the table, pointing at filler
+        0x0f, 0xd3,
+        0xe8,               // $C030: INX
+        0x60,               // $C031: RTS
+    ];
+    code.resize(0x40, 0xff);
+    let prgrom = make_prgrom(&code, 0xc031, 0xc000, 0xc031);
+
+    let mapper = NRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    let targets: Vec<Vec<u16>> = disassembly.jump_tables.iter()
+        .map(|table| table.targets.iter().map(|(address, _)| *address).collect())
+        .collect();
+    assert_eq!(targets, vec![vec![0xc012, 0xc000], vec![0xc030]]);
+    assert!(disassembly.jump_tables.iter().all(|table| table.limit.is_none()));
+    assert!(!disassembly.instructions.contains_key(&0x248));
+    assert!(!disassembly.instructions.contains_key(&0x1310));
+}
+
+#[test]
+fn test_skips_inline_data_after_calls() {
+    let mut code = vec![
+        0x20, 0x10, 0xc0,   // $C000: JSR $C010
+        0x01, 0x02,         // $C003: (2 inline bytes)
+        0x20, 0x20, 0xc0,   // $C005: JSR $C020
+        0x05,               // $C008: (1 inline byte)
+        0x4c, 0x09, 0xc0,   // $C009: JMP $C009
+    ];
+    code.resize(0x10, 0xff);
+    code.extend_from_slice(&[
+        0x68,               // $C010: PLA
+        0x18,               // $C011: CLC
+        0x69, 0x02,         // $C012: ADC #$02
+        0x85, 0x00,         // $C014: STA $00
+        0x68,               // $C016: PLA
+        0x69, 0x00,         // $C017: ADC #$00
+        0x48,               // $C019: PHA
+        0xa5, 0x00,         // $C01A: LDA $00
+        0x48,               // $C01C: PHA
+        0x60,               // $C01D: RTS
+        0xff, 0xff,
+        0xba,               // $C020: TSX
+        0xfe, 0x01, 0x01,   // $C021: INC $0101,X
+        0xd0, 0x03,         // $C024: BNE $C029
+        0xfe, 0x02, 0x01,   // $C026: INC $0102,X
+        0x60,               // $C029: RTS
+    ]);
+    let prgrom = make_prgrom(&code, 0xc009, 0xc000, 0xc009);
+
+    let mapper = NRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    assert_eq!(disassembly.inline_data, vec![
+        InlineData { call: 0x0, prg_offset: 0x3, len: 2 },
+        InlineData { call: 0x5, prg_offset: 0x8, len: 1 },
+    ]);
+    assert!(disassembly.instructions.contains_key(&0x5));
+    assert!(disassembly.instructions.contains_key(&0x9));
+    assert_eq!(disassembly.byte_kinds[0x3..0x5], [ByteKind::Data, ByteKind::Data]);
+    assert_eq!(disassembly.byte_kinds[0x8], ByteKind::Data);
+}
+#[test]
+fn test_skips_inline_data_only_of_known_length() {
+    let mut code = vec![
+        0x20, 0x10, 0xc0,   // $C000: JSR $C010
+        0x20, 0x30, 0xc0,   // $C003: JSR $C030
+        0x20, 0x50, 0xc0,   // $C006: JSR $C050
+        0x07,               // $C009: (1 inline byte)
+        0x4c, 0x0a, 0xc0,   // $C00A: JMP $C00A
+    ];
+    // Prints a string stored after the call, calling out for each character.
+    let print = [
+        0x68,               // PLA
+        0x85, 0x00,         // STA $00
+        0x68,               // PLA
+        0x85, 0x01,         // STA $01
+        0xa0, 0x00,         // LDY #$00
+        0xe6, 0x00,         // $xx18: INC $00
+        0xd0, 0x02,         // BNE $xx1E
+        0xe6, 0x01,         // INC $01
+        0xb1, 0x00,         // $xx1E: LDA ($00),Y
+        0xf0, 0x06,         // BEQ $xx28
+        0x20, 0x60, 0xc0,   // JSR $C060
+        0x4c, 0x18, 0xc0,   // JMP $xx18
+        0x6c, 0x00, 0x00,   // $xx28: JMP ($0000)
+    ];
+    code.resize(0x10, 0xff);
+    code.extend_from_slice(&print);
+    code.resize(0x30, 0xff);
+    // The same, writing each character out without a call.
+    code.extend_from_slice(&print);
+    code[0x42..0x48].copy_from_slice(&[
+        0x8d, 0x07, 0x20,   // $C042: STA $2007
+        0x4c, 0x38, 0xc0,   // $C045: JMP $C038
+    ]);
+    code.resize(0x50, 0xff);
+    code.extend_from_slice(&[
+        0xba,               // $C050: TSX
+        0x48,               // $C051: PHA
+        0xfe, 0x01, 0x01,   // $C052: INC $0101,X
+        0x68,               // $C055: PLA
+        0x60,               // $C056: RTS
+    ]);
+    code.resize(0x60, 0xff);
+    code.push(0x60);        // $C060: RTS
+    let prgrom = make_prgrom(&code, 0xc060, 0xc000, 0xc060);
+
+    let mapper = NRom::new(prgrom.len());
+    let mut disassembler = Disassembler::new(&prgrom, &mapper);
+    disassembler.add_vector_entry_points();
+    let disassembly = disassembler.run();
+
+    assert_eq!(disassembly.inline_data, vec![
+        InlineData { call: 0x6, prg_offset: 0x9, len: 1 },
+    ]);
+    assert!(disassembly.instructions.contains_key(&0x3));
+    assert!(disassembly.instructions.contains_key(&0x6));
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use binrw::{BinRead, Endian};
+
+use nespile::cpu::{Bus, Flags, Interpreter};
+use nespile::ir::{self, BinaryOp, Exit, Expr, Flag, Reg, Stmt, Type, UnaryOp};
+use nespile::parser::instruction::Instruction;
+use nespile::parser::opcodes::Opcode;
+use nespile_runtime::Cpu;
+
+
+
+/// 64KB of RAM with `code` at $0200 and the reset vector pointing there.
+struct Ram(Vec<u8>);
+impl Ram {
+    fn with_code(code: &[u8]) -> Self {
+        let mut ram = vec![0u8; 0x10000];
+        ram[0x0200..0x0200 + code.len()].copy_from_slice(code);
+        ram[0xfffc..0xfffe].copy_from_slice(&[0x00, 0x02]);
+        Ram(ram)
+    }
+}
+impl Bus for Ram {
+    fn read(&mut self, address: u16) -> u8 {
+        self.0[address as usize]
+    }
+    fn write(&mut self, address: u16, value: u8) {
+        self.0[address as usize] = value;
+    }
+}
+
+/// Resets the CPU and steps until it halts.
+fn run(ram: &mut Ram) -> Interpreter {
+    let mut cpu = Interpreter::new();
+    cpu.reset(ram);
+    while !cpu.halted {
+        cpu.step(ram);
+    }
+    cpu
+}
+
+/// Evaluates an IR expression, holding every type in a `u16`.
+fn eval(expr: &Expr, cpu: &Cpu, temps: &HashMap<u32, u16>, ram: &Ram) -> u16 {
+    let wrap = |ty: Type, value: u16| match ty {
+        Type::Bool => value & 1,
+        Type::U8 => value & 0xff,
+        Type::U16 => value,
+    };
+    match expr {
+        Expr::Bool(value) => u16::from(*value),
+        Expr::U8(value) => u16::from(*value),
+        Expr::U16(value) => *value,
+        Expr::Reg(reg) => u16::from(match reg { Reg::A => cpu.a, Reg::X => cpu.x, Reg::Y => cpu.y, Reg::SP => cpu.sp }),
+        Expr::Flag(flag) => u16::from(cpu.p() & flag.mask() != 0),
+        Expr::Temp(temp) => temps[&temp.id],
+        Expr::Load(address) => u16::from(ram.0[eval(address, cpu, temps, ram) as usize]),
+        Expr::Cast(Type::Bool, value) => u16::from(eval(value, cpu, temps, ram) != 0),
+        Expr::Cast(ty, value) => wrap(*ty, eval(value, cpu, temps, ram)),
+        Expr::Unary(op, value) => {
+            let value_ty = value.ty();
+            let value = eval(value, cpu, temps, ram);
+            match op {
+                UnaryOp::Not => wrap(value_ty, !value),
+                UnaryOp::Negative => u16::from(value & 0x80 != 0),
+                UnaryOp::IsZero => u16::from(value == 0),
+            }
+        },
+        Expr::Binary(op, lhs, rhs) => {
+            let ty = lhs.ty();
+            let (lhs, rhs) = (eval(lhs, cpu, temps, ram), eval(rhs, cpu, temps, ram));
+            wrap(ty, match op {
+                BinaryOp::Add => lhs.wrapping_add(rhs),
+                BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                BinaryOp::And => lhs & rhs,
+                BinaryOp::Or => lhs | rhs,
+                BinaryOp::Xor => lhs ^ rhs,
+                BinaryOp::Shl => lhs << rhs,
+                BinaryOp::Shr => lhs >> rhs,
+                BinaryOp::Ge => return u16::from(lhs >= rhs),
+            })
+        },
+    }
+}
+
+/// Runs one lifted instruction, returning the address it continues at, or `None` if it halts.
+fn run_lifted(instruction: &Instruction, cpu: &mut Cpu, ram: &mut Ram) -> Option<u16> {
+    let lifted = ir::lift_instruction(instruction);
+    let mut temps = HashMap::new();
+    for stmt in &lifted.stmts {
+        match stmt {
+            Stmt::Let(temp, value) => { temps.insert(temp.id, eval(value, cpu, &temps, ram)); },
+            Stmt::SetReg(reg, value) => {
+                let value = eval(value, cpu, &temps, ram) as u8;
+                match reg { Reg::A => cpu.a = value, Reg::X => cpu.x = value, Reg::Y => cpu.y = value, Reg::SP => cpu.sp = value }
+            },
+            Stmt::SetFlag(flag, value) => {
+                let value = eval(value, cpu, &temps, ram) != 0;
+                match flag { Flag::C => cpu.c = value, Flag::Z => cpu.z = value, Flag::I => cpu.i = value,
+                             Flag::D => cpu.d = value, Flag::V => cpu.v = value, Flag::N => cpu.n = value }
+            },
+            Stmt::Store(address, value) => {
+                let (address, value) = (eval(address, cpu, &temps, ram), eval(value, cpu, &temps, ram));
+                ram.0[address as usize] = value as u8;
+            },
+        }
+    }
+    let next = instruction.address.wrapping_add(instruction.size() as u16);
+    match lifted.exit {
+        Exit::Next => Some(next),
+        Exit::Branch { condition, target } => Some(if eval(&condition, cpu, &temps, ram) != 0 { target } else { next }),
+        Exit::Jump(target) | Exit::Call(target) => Some(target),
+        Exit::JumpIndirect(address) | Exit::Return(address) | Exit::ReturnFromInterrupt(address) =>
+            Some(eval(&address, cpu, &temps, ram)),
+        Exit::Interrupt(vector) => Some(u16::from_le_bytes([ram.0[vector as usize], ram.0[vector as usize + 1]])),
+        Exit::Halt => None,
+    }
+}
+
+#[test]
+fn test_matches_lifted_ir() {
+    // A small xorshift, so every opcode runs from a few arbitrary but repeatable states.
+    let mut seed = 0x2a03u32;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as u8
+    };
+
+    for byte in 0..=0xffu8 {
+        for _ in 0..8 {
+            let mut ram = Ram(std::iter::repeat_with(&mut random).take(0x10000).collect());
+            ram.0[0x0200] = byte;
+            let cpu = Cpu { a: random(), x: random(), y: random(), sp: random(), ..Cpu::default() };
+            let mut cpu = Cpu { c: random() & 1 != 0, z: random() & 1 != 0, v: random() & 1 != 0, n: random() & 1 != 0, ..cpu };
+
+            let mut interpreter = Interpreter::from(cpu);
+            interpreter.pc = 0x0200;
+            let mut interpreted = Ram(ram.0.clone());
+            interpreter.step(&mut interpreted);
+
+            let bytes = ram.0[0x0200..0x0203].to_vec();
+            let opcode = Opcode::read_options(&mut Cursor::new(&bytes), Endian::Little, ()).expect("Could not decode opcode");
+            let instruction = Instruction {
+                address: 0x0200,
+                bank: 0,
+                prg_offset: 0,
+                file_offset: 0,
+                bytes: bytes[..opcode.size()].to_vec(),
+                opcode,
+                target: None,
+            };
+            let pc = run_lifted(&instruction, &mut cpu, &mut ram);
+
+            let context = format!("${:02x} `{}`", byte, opcode.to_source_string());
+            assert_eq!(Cpu::from(&interpreter), cpu, "registers after {}", context);
+            assert_eq!(interpreter.halted, pc.is_none(), "halting after {}", context);
+            if let Some(pc) = pc {
+                assert_eq!(interpreter.pc, pc, "PC after {}", context);
+            }
+            assert!(interpreted.0 == ram.0, "memory after {}", context);
+        }
+    }
+}
+
+#[test]
+fn test_counts_cycles() {
+    let mut ram = Ram::with_code(&[
+        0xa2, 0x03,         // $0200: LDX #$03      2
+        0xca,               // $0202: DEX           2 x3
+        0xd0, 0xfd,         // $0203: BNE $0202     3 x2, 2
+        0xa0, 0x01,         // $0205: LDY #$01      2
+        0xb9, 0xff, 0x02,   // $0207: LDA $02FF,Y   5, crossing into $0300
+        0x02,               // $020A: STP           2
+    ]);
+    ram.0[0x0300] = 0x42;
+    let cpu = run(&mut ram);
+
+    assert_eq!((cpu.a, cpu.x, cpu.y), (0x42, 0x00, 0x01));
+    assert_eq!(cpu.cycles, 7 + 2 + 2 * 3 + 3 * 2 + 2 + 2 + 5 + 2);
+    assert_eq!(cpu.pc, 0x020b);
+}
+
+#[test]
+fn test_executes_unofficial_opcodes() {
+    let mut ram = Ram::with_code(&[
+        0xa7, 0x10,         // $0200: LAX $10
+        0xc7, 0x11,         // $0202: DCP $11
+        0xe7, 0x12,         // $0204: ISC $12
+        0xa2, 0x0f,         // $0206: LDX #$0F
+        0x87, 0x13,         // $0208: SAX $13
+        0x07, 0x14,         // $020A: SLO $14
+        0xcb, 0x02,         // $020C: AXS #$02
+        0x02,               // $020E: STP
+    ]);
+    ram.0[0x10..0x15].copy_from_slice(&[0x81, 0x82, 0x7f, 0x00, 0x40]);
+    let cpu = run(&mut ram);
+
+    // DCP leaves $81, equal to A; ISC subtracts $80 with carry set, leaving $01
+    assert_eq!(&ram.0[0x10..0x15], &[0x81, 0x81, 0x80, 0x01, 0x80]);
+    // SLO ORs $80 into A, then AXS sets X to (A AND X) - 2
+    assert_eq!((cpu.a, cpu.x), (0x81, 0xff));
+    assert!(!cpu.flag(Flags::C));
+    assert!(cpu.flag(Flags::N));
+}
+
+#[test]
+fn test_ignores_decimal_mode() {
+    let mut ram = Ram::with_code(&[
+        0xf8,               // $0200: SED
+        0x18,               // $0201: CLC
+        0xa9, 0x09,         // $0202: LDA #$09
+        0x69, 0x01,         // $0204: ADC #$01
+        0x08,               // $0206: PHP
+        0x02,               // $0207: STP
+    ]);
+    let cpu = run(&mut ram);
+
+    assert_eq!(cpu.a, 0x0a);
+    assert!(cpu.flag(Flags::D));
+    // PHP pushes P with both break bits set
+    assert_eq!(ram.0[0x0100 | (cpu.sp as usize + 1)], 0x3c);
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use std::io::Cursor;
+
+use binrw::{BinRead, Endian};
+use common::make_file;
+use nespile::analysis::cfg::ControlFlowGraph;
+use nespile::ir::{self, Exit, Expr, Stmt, Target, Terminator, Type};
+use nespile::parser::NesProgram;
+use nespile::parser::instruction::Instruction;
+use nespile::parser::opcodes::Opcode;
+
+
+
+fn instruction(bytes: &[u8]) -> Instruction {
+    let opcode = Opcode::read_options(&mut Cursor::new(bytes), Endian::Little, ()).unwrap();
+    let size = opcode.size();
+    Instruction {
+        address: 0x8000,
+        bank: 0,
+        prg_offset: 0,
+        file_offset: 0x10,
+        bytes: bytes[..size].to_vec(),
+        opcode,
+        target: None,
+    }
+}
+
+fn lifted(bytes: &[u8]) -> Vec<String> {
+    ir::lift_instruction(&instruction(bytes)).stmts.iter().map(|stmt| stmt.to_string()).collect()
+}
+
+/// Whether an expression's operands have the types its operator expects.
+fn well_typed(expr: &Expr) -> bool {
+    match expr {
+        Expr::Load(address) => address.ty() == Type::U16 && well_typed(address),
+        Expr::Cast(_, value) | Expr::Unary(_, value) => well_typed(value),
+        Expr::Binary(ir::BinaryOp::Shl | ir::BinaryOp::Shr, value, amount) =>
+            well_typed(value) && matches!(**amount, Expr::U8(_)),
+        Expr::Binary(_, lhs, rhs) => lhs.ty() == rhs.ty() && well_typed(lhs) && well_typed(rhs),
+        _ => true,
+    }
+}
+
+fn has_load(expr: &Expr) -> bool {
+    match expr {
+        Expr::Load(_) => true,
+        Expr::Cast(_, value) | Expr::Unary(_, value) => has_load(value),
+        Expr::Binary(_, lhs, rhs) => has_load(lhs) || has_load(rhs),
+        _ => false,
+    }
+}
+
+#[test]
+fn test_lifts_arithmetic_with_separate_flags() {
+    // ADC #$10
+    assert_eq!(lifted(&[0x69, 0x10]), vec![
+        "t0: u16 = ((u16(A) + $0010) + u16(C))",
+        "t1: u8 = u8(t0)",
+        "V = neg(((A ^ t1) & ($10 ^ t1)))",
+        "C = bool((t0 >> $08))",
+        "A = t1",
+        "Z = zero(A)",
+        "N = neg(A)",
+    ]);
+
+    // DCP ($20),Y decrements memory, then compares it with A
+    assert_eq!(lifted(&[0xd3, 0x20]), vec![
+        "t0: u8 = [$0020]",
+        "t1: u8 = [$0021]",
+        "t2: u16 = (u16(t0) | (u16(t1) << $08))",
+        "t3: u16 = (t2 + u16(Y))",
+        "t4: u8 = [t3]",
+        "t5: u8 = (t4 - $01)",
+        "[t3] = t5",
+        "t6: u8 = (A - t5)",
+        "Z = zero(t6)",
+        "N = neg(t6)",
+        "C = (A >= t5)",
+    ]);
+
+    // JMP ($02ff) reads its high byte from $0200
+    let jmp = ir::lift_instruction(&instruction(&[0x6c, 0xff, 0x02]));
+    assert_eq!(jmp.stmts[1].to_string(), "t1: u8 = [$0200]");
+    assert!(matches!(jmp.exit, Exit::JumpIndirect(_)));
+}
+
+#[test]
+fn test_lifts_every_opcode_well_typed() {
+    for byte in 0..=0xffu8 {
+        let lifted = ir::lift_instruction(&instruction(&[byte, 0x34, 0x12]));
+        for stmt in &lifted.stmts {
+            let ok = match stmt {
+                Stmt::Let(temp, value) => temp.ty == value.ty() && well_typed(value),
+                Stmt::SetReg(_, value) => value.ty() == Type::U8 && well_typed(value),
+                Stmt::SetFlag(_, value) => value.ty() == Type::Bool && well_typed(value),
+                Stmt::Store(address, value) =>
+                    address.ty() == Type::U16 && value.ty() == Type::U8 && well_typed(address) && well_typed(value),
+            };
+            assert!(ok, "${:02x} lifts to ill-typed `{}`", byte, stmt);
+            let nested_load = match stmt {
+                Stmt::Let(_, Expr::Load(address)) => has_load(address),
+                Stmt::Let(_, value) | Stmt::SetReg(_, value) | Stmt::SetFlag(_, value) => has_load(value),
+                Stmt::Store(address, value) => has_load(address) || has_load(value),
+            };
+            assert!(!nested_load, "${:02x} lifts a load outside a let: `{}`", byte, stmt);
+        }
+    }
+}
+
+#[test]
+fn test_lifts_program_blocks() {
+    let code = [
+        0xa2, 0x03,         // $C000: LDX #$03
+        0x20, 0x09, 0xc0,   // $C002: JSR $C009
+        0xca,               // $C005: DEX
+        0xd0, 0xfa,         // $C006: BNE $C002
+        0x02,               // $C008: STP
+        0x0a,               // $C009: ASL A
+        0x60,               // $C00A: RTS
+    ];
+    let program = NesProgram::try_from(&make_file(&code, 0xc000, 0xc000, 0xc000, 0)).expect("Could not disassemble ROM");
+
+    let lifted = ir::lift_program(&ControlFlowGraph::build(&program));
+    assert_eq!(lifted.functions.keys().copied().collect::<Vec<_>>(), vec![0x0, 0x9]);
+
+    let main = &lifted.functions[&0x0];
+    assert_eq!(main.blocks[&0x0].terminator, Terminator::Goto(Target::Block(0x2)));
+    assert_eq!(main.blocks[&0x2].terminator, Terminator::Call { callee: Target::Block(0x9), return_to: Target::Block(0x5) });
+    assert!(matches!(&main.blocks[&0x5].terminator,
+        Terminator::Branch { taken: Target::Block(0x2), not_taken: Target::Block(0x8), .. }));
+    assert_eq!(main.blocks[&0x8].terminator, Terminator::Halt);
+
+    let asl = lifted.functions[&0x9].to_string();
+    assert!(asl.contains("t0: u8 = (A << $01)\n    Z = zero(t0)\n    N = neg(t0)\n    C = neg(A)\n    A = t0\n"), "{}", asl);
+    assert!(asl.contains("    return (t3 + $0001)\n"), "{}", asl);
+}
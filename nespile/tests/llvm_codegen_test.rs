@@ -0,0 +1,114 @@
+mod common;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use common::{lifted_program, CALL_LOOP};
+use nespile::codegen;
+
+
+
+/// The lines of one function's definition.
+fn function<'a>(module: &'a str, name: &str) -> Vec<&'a str> {
+    module.lines()
+        .skip_while(|line| !line.starts_with(&format!("define void @{}(", name)))
+        .take_while(|line| *line != "}")
+        .collect()
+}
+
+/// Lines with each SSA value renamed `%tN` in order of first use, so they match however many values came before.
+fn renumbered(lines: &[&str]) -> Vec<String> {
+    let mut names = Vec::<String>::new();
+    lines.iter()
+        .map(|line| {
+            let mut renamed = String::new();
+            let mut rest = *line;
+            while let Some(start) = rest.find("%v") {
+                let digits = rest[start + 2..].chars().take_while(char::is_ascii_digit).count();
+                let value = &rest[start..start + 2 + digits];
+                let index = names.iter().position(|name| name == value).unwrap_or_else(|| {
+                    names.push(value.to_string());
+                    names.len() - 1
+                });
+                renamed.push_str(&rest[..start]);
+                renamed.push_str(&format!("%t{}", index));
+                rest = &rest[start + 2 + digits..];
+            }
+            renamed + rest
+        })
+        .collect()
+}
+
+/// Compiles `module` with `llc` if it's installed, retrying with opaque pointers enabled for LLVM 14.
+fn check_with_llc(module: &str) {
+    let run = |args: &[&str]| {
+        let mut llc = match Command::new("llc").args(args).args(["-filetype=null", "-o", "-", "-"])
+            .stdin(Stdio::piped()).stderr(Stdio::piped()).spawn() {
+            Ok(llc) => llc,
+            Err(_) => return None,
+        };
+        llc.stdin.take().expect("llc has stdin").write_all(module.as_bytes()).expect("Could not write to llc");
+        Some(llc.wait_with_output().expect("Could not run llc"))
+    };
+    let Some(output) = run(&[]) else {
+        return;
+    };
+    let output = if output.status.success() { output } else { run(&["-opaque-pointers"]).expect("llc ran once") };
+    assert!(output.status.success(), "llc rejected the module:\n{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_generates_entry_points_and_hooks() {
+    let (program, symbols) = lifted_program(&CALL_LOOP);
+    let module = codegen::llvm::generate(&program, &symbols);
+
+    assert!(module.contains("declare i8 @nes_read(i16)\ndeclare void @nes_write(i16, i8)\ndeclare void @nes_unresolved(ptr, i16)\n"), "{}", module);
+    assert_eq!(function(&module, "nes_nmi"), vec![
+        "define void @nes_nmi(ptr %cpu) {",
+        "  call void @nes_interrupt(ptr %cpu)",
+        "  call void @nmi(ptr %cpu)",
+        "  ret void",
+    ]);
+    assert!(module.contains("define void @sub_C00A(ptr %cpu) {\n"), "{}", module);
+}
+
+#[test]
+fn test_keeps_registers_in_allocas_and_computes_flags_lazily() {
+    let (program, symbols) = lifted_program(&CALL_LOOP);
+    let module = codegen::llvm::generate(&program, &symbols);
+
+    let reset = function(&module, "reset");
+    assert!(reset.contains(&"  %a = alloca i8"), "{:#?}", reset);
+    assert!(reset.contains(&"  %c = alloca i1"), "{:#?}", reset);
+    assert!(reset.contains(&"  br label %b0"), "{:#?}", reset);
+    assert!(reset.contains(&"  call void @sub_C00A(ptr %cpu)"), "{:#?}", reset);
+    assert!(reset.contains(&"  call void @llvm.trap()"), "{:#?}", reset);
+
+    // LDX #$03 stores the byte Z and N are tested from, rather than testing it
+    let ldx: Vec<_> = reset.iter().skip_while(|line| !line.starts_with("b0:")).skip(1).take(5).copied().collect();
+    assert_eq!(renumbered(&ldx), vec![
+        "  store i8 3, ptr %x",
+        "  %t0 = load i8, ptr %x",
+        "  store i8 %t0, ptr %z",
+        "  %t1 = load i8, ptr %x",
+        "  store i8 %t1, ptr %n",
+    ]);
+    // BNE only compares Z where it branches on it
+    let bne = reset.iter().position(|line| line.starts_with("b5:")).unwrap();
+    assert_eq!(renumbered(&reset[bne..(bne + 12).min(reset.len())]), vec![
+        "b5: ; $C005",
+        "  %t0 = load i8, ptr %x",
+        "  %t1 = sub i8 %t0, 1",
+        "  store i8 %t1, ptr %x",
+        "  %t2 = load i8, ptr %x",
+        "  store i8 %t2, ptr %z",
+        "  %t3 = load i8, ptr %x",
+        "  store i8 %t3, ptr %n",
+        "  %t4 = load i8, ptr %z",
+        "  %t5 = icmp eq i8 %t4, 0",
+        "  %t6 = xor i1 %t5, -1",
+        "  br i1 %t6, label %b2, label %b8",
+    ], "{:#?}", &reset[bne..]);
+
+    check_with_llc(&module);
+}
@@ -0,0 +1,101 @@
+use nespile::mapper::{AxRom, BankContext, CnRom, Mapper, MapperRegisters, Mmc1, Mmc3, PrgLocation, RegisterWrite};
+
+
+
+/// Writes `value` to an MMC1 register one bit at a time, returning the effect of each write.
+fn mmc1_serial_write(mapper: &Mmc1, addr: u16, value: u8, registers: &mut MapperRegisters) -> Vec<RegisterWrite> {
+    (0..5).map(|bit| mapper.register_write(addr, Some((value >> bit) & 0x01), registers)).collect()
+}
+
+
+#[test]
+fn test_mmc1_commits_after_five_writes() {
+    // 128KB: eight 16KB banks, the last fixed at $C000.
+    let mapper = Mmc1::new(0x20000);
+    let mut registers = MapperRegisters::default();
+
+    let writes = mmc1_serial_write(&mapper, 0xe000, 0x03, &mut registers);
+    assert_eq!(writes[..4], [RegisterWrite::Other; 4]);
+    assert_eq!(writes[4], RegisterWrite::Prg { window: 0x8000, bank: Some(3) });
+    assert_eq!(registers.shift_count, 0);
+
+    let mut banks = BankContext::default();
+    banks.select(0x8000, 3);
+    assert_eq!(mapper.resolve(0x8010, &banks), Some(PrgLocation { bank: 3, prg_offset: 0xc010 }));
+    assert_eq!(mapper.resolve(0xc010, &banks), Some(PrgLocation { bank: 7, prg_offset: 0x1c010 }));
+
+    // Other registers, and bits that aren't known, don't select a known bank.
+    assert_eq!(mmc1_serial_write(&mapper, 0xa000, 0x03, &mut registers)[4], RegisterWrite::Other);
+    mapper.register_write(0xe000, None, &mut registers);
+    let writes: Vec<_> = (0..4).map(|_| mapper.register_write(0xe000, Some(0), &mut registers)).collect();
+    assert_eq!(writes[3], RegisterWrite::Prg { window: 0x8000, bank: None });
+    assert_eq!(mapper.register_write(0x6000, Some(0), &mut registers), RegisterWrite::NotRegister);
+}
+#[test]
+fn test_mmc1_resets_shift_register() {
+    let mapper = Mmc1::new(0x20000);
+    let mut registers = MapperRegisters::default();
+
+    mapper.register_write(0xe000, Some(1), &mut registers);
+    mapper.register_write(0xe000, Some(1), &mut registers);
+    assert_eq!(mapper.register_write(0x8000, Some(0x80), &mut registers), RegisterWrite::Other);
+    assert_eq!(registers, MapperRegisters { shift: Some(0), ..MapperRegisters::default() });
+
+    // The bits written before the reset are discarded.
+    let writes = mmc1_serial_write(&mapper, 0xe000, 0x05, &mut registers);
+    assert_eq!(writes[4], RegisterWrite::Prg { window: 0x8000, bank: Some(5) });
+}
+#[test]
+fn test_mmc3_selects_r6_and_r7() {
+    // 128KB: sixteen 8KB banks, the last two fixed at $C000 and $E000.
+    let mapper = Mmc3::new(0x20000);
+    let mut registers = MapperRegisters::default();
+
+    assert_eq!(mapper.register_write(0x8001, Some(3), &mut registers), RegisterWrite::Prg { window: 0x8000, bank: None });
+    assert_eq!(mapper.register_write(0x8000, Some(6), &mut registers), RegisterWrite::Other);
+    assert_eq!(mapper.register_write(0x8001, Some(3), &mut registers), RegisterWrite::Prg { window: 0x8000, bank: Some(3) });
+    assert_eq!(mapper.register_write(0x8000, Some(7), &mut registers), RegisterWrite::Other);
+    assert_eq!(mapper.register_write(0x9fff, Some(4), &mut registers), RegisterWrite::Prg { window: 0xa000, bank: Some(4) });
+    // CHR banks, mirroring and IRQs.
+    assert_eq!(mapper.register_write(0x8000, Some(2), &mut registers), RegisterWrite::Other);
+    assert_eq!(mapper.register_write(0x8001, Some(4), &mut registers), RegisterWrite::Other);
+    assert_eq!(mapper.register_write(0xa000, Some(1), &mut registers), RegisterWrite::Other);
+    assert_eq!(mapper.register_write(0x7fff, Some(1), &mut registers), RegisterWrite::NotRegister);
+
+    let mut banks = BankContext::default();
+    banks.select(0x8000, 3);
+    banks.select(0xa000, 4);
+    assert_eq!(mapper.resolve(0x8010, &banks), Some(PrgLocation { bank: 3, prg_offset: 0x6010 }));
+    assert_eq!(mapper.resolve(0xa010, &banks), Some(PrgLocation { bank: 4, prg_offset: 0x8010 }));
+    assert_eq!(mapper.resolve(0xc010, &banks), Some(PrgLocation { bank: 14, prg_offset: 0x1c010 }));
+    assert_eq!(mapper.resolve(0xe010, &banks), Some(PrgLocation { bank: 15, prg_offset: 0x1e010 }));
+}
+#[test]
+fn test_axrom_switches_32kb_banks() {
+    // 128KB: four 32KB banks, the last selected at power-on.
+    let mapper = AxRom::new(0x20000);
+    let banks = mapper.power_on_context();
+    assert_eq!(mapper.resolve(0xfffc, &banks), Some(PrgLocation { bank: 3, prg_offset: 0x1fffc }));
+
+    // Bit 4 selects the nametable.
+    let write = mapper.register_write(0x8000, Some(0x11), &mut MapperRegisters::default());
+    assert_eq!(write, RegisterWrite::Prg { window: 0x8000, bank: Some(1) });
+    let mut banks = BankContext::default();
+    banks.select(0x8000, 1);
+    assert_eq!(mapper.resolve(0x8000, &banks), Some(PrgLocation { bank: 1, prg_offset: 0x8000 }));
+    assert_eq!(mapper.resolve(0xfffc, &banks), Some(PrgLocation { bank: 1, prg_offset: 0xfffc }));
+    assert_eq!(mapper.resolve(0xfffc, &BankContext::default()), None);
+}
+#[test]
+fn test_cnrom_keeps_prg_fixed() {
+    // 16KB, mirrored at $8000 and $C000.
+    let mapper = CnRom::new(0x4000);
+    let mut registers = MapperRegisters::default();
+
+    assert_eq!(mapper.register_write(0x8000, Some(1), &mut registers), RegisterWrite::Other);
+    assert_eq!(mapper.register_write(0xffff, Some(3), &mut registers), RegisterWrite::Other);
+    assert_eq!(mapper.register_write(0x6000, Some(1), &mut registers), RegisterWrite::NotRegister);
+    let banks = BankContext::default();
+    assert_eq!(mapper.resolve(0x8010, &banks), Some(PrgLocation { bank: 0, prg_offset: 0x10 }));
+    assert_eq!(mapper.resolve(0xc010, &banks), Some(PrgLocation { bank: 0, prg_offset: 0x10 }));
+}
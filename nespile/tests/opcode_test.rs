@@ -0,0 +1,52 @@
+use std::io::Cursor;
+
+use binrw::{BinRead, Endian};
+use nespile::parser::address_mode::{AddrModeNOP, AddrModeNoZeropageY};
+use nespile::parser::opcodes::{is_official_opcode, Opcode};
+
+
+
+fn decode(bytes: &[u8]) -> Opcode {
+    Opcode::read_options(&mut Cursor::new(bytes), Endian::Little, ())
+        .expect("Could not decode opcode")
+}
+
+
+#[test]
+fn test_decode_unofficial_nops() {
+    // $1A is an implied NOP like $EA, not a second accumulator mode.
+    assert!(matches!(decode(&[0x1a]), Opcode::NOP(AddrModeNOP::Implied)));
+    // $7C is absolute,X like the rest of its column.
+    assert!(matches!(decode(&[0x7c, 0x34, 0x12]), Opcode::NOP(AddrModeNOP::AbsoluteX(0x1234))));
+
+    // Unofficial NOPs take operands.
+    assert!(matches!(decode(&[0x80, 0x10]), Opcode::NOP(AddrModeNOP::Immediate(0x10))));
+    assert!(matches!(decode(&[0x04, 0x10]), Opcode::NOP(AddrModeNOP::ZeroPage(0x10))));
+    assert!(matches!(decode(&[0x14, 0x10]), Opcode::NOP(AddrModeNOP::ZeroPageX(0x10))));
+    assert!(matches!(decode(&[0x0c, 0x00, 0x20]), Opcode::NOP(AddrModeNOP::Absolute(0x2000))));
+    assert_eq!(decode(&[0x0c, 0x00, 0x20]).size(), 3);
+}
+
+#[test]
+fn test_encode_round_trip() {
+    for byte in 0..=255u8 {
+        let bytes = [byte, 0x34, 0x12];
+        let opcode = decode(&bytes);
+        let encoded = opcode.encode().expect("Could not encode opcode");
+
+        assert_eq!(encoded.len(), opcode.size(), "size of ${:02x}", byte);
+        assert_eq!(encoded[1..], bytes[1..opcode.size()], "operand of ${:02x}", byte);
+        assert_eq!(format!("{:?}", decode(&encoded)), format!("{:?}", opcode), "re-decoding ${:02x}", byte);
+        if is_official_opcode(byte) {
+            assert_eq!(encoded[0], byte, "official opcode ${:02x}", byte);
+        }
+    }
+}
+
+#[test]
+fn test_encode_canonical_bytes() {
+    assert_eq!(Opcode::NOP(AddrModeNOP::Implied).encode().unwrap(), vec![0xea]);
+    assert_eq!(Opcode::NOP(AddrModeNOP::ZeroPage(0x10)).encode().unwrap(), vec![0x04, 0x10]);
+    assert_eq!(Opcode::SBC(AddrModeNoZeropageY::Immediate(0x01)).encode().unwrap(), vec![0xe9, 0x01]);
+    assert_eq!(Opcode::STP.encode().unwrap(), vec![0x02]);
+}
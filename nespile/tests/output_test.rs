@@ -0,0 +1,122 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use common::{make_file, make_rom};
+use nespile::analysis::cfg::ControlFlowGraph;
+use nespile::output::{asm6, ca65, dot, AssemblyOutput};
+use nespile::parser::NesProgram;
+use nespile::symbols::SymbolTable;
+
+
+
+/// The ROM built from `CODE`, with every vector pointing at $C000 and 8KB of CHR-ROM, and its disassembly.
+fn make_rom_and_program() -> (Vec<u8>, NesProgram) {
+    let program = NesProgram::try_from(&make_file(&CODE, 0xc000, 0xc000, 0xc000, 0x2000)).expect("Could not disassemble ROM");
+    (make_rom(&CODE, 0xc000, 0xc000, 0xc000, 0x2000), program)
+}
+
+/// Whether `tool` can be run, so tests needing an assembler can skip without one.
+fn on_path(tool: &str) -> bool {
+    Command::new(tool).arg("--version").output().is_ok()
+}
+
+/// Writes `output` into a fresh directory, as `game.s` with `game.chr` and `game.cfg` beside it.
+fn write_output(name: &str, output: &AssemblyOutput) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("Could not create output directory");
+    fs::write(dir.join("game.s"), &output.source).expect("Could not write source");
+    fs::write(dir.join("game.chr"), &output.chr).expect("Could not write CHR-ROM");
+    if let Some(linker_config) = &output.linker_config {
+        fs::write(dir.join("game.cfg"), linker_config).expect("Could not write linker config");
+    }
+    dir
+}
+
+fn run(dir: &Path, tool: &str, args: &[&str]) {
+    let status = Command::new(tool).args(args).current_dir(dir).status()
+        .unwrap_or_else(|_| panic!("Could not run {}", tool));
+    assert!(status.success(), "{} failed", tool);
+}
+
+const CODE: [u8; 13] = [
+    0xad, 0x10, 0x00,   // $C000: LDA $0010 (absolute)
+    0xa7, 0x10,         // $C003: LAX $10 (unofficial)
+    0xd0, 0xf9,         // $C005: BNE $C000
+    0x8d, 0x00, 0x20,   // $C007: STA $2000
+    0x4c, 0x00, 0xc0,   // $C00A: JMP $C000
+];
+
+
+#[test]
+fn test_ca65_output() {
+    let (rom, program) = make_rom_and_program();
+    let output = ca65::generate(&rom, &program, &SymbolTable::generate(&program), "game.chr");
+
+    assert_eq!(output.chr, vec![0x55u8; 0x2000]);
+    assert!(output.source.contains("PPUCTRL = $2000"));
+    assert!(output.source.contains(".segment \"HEADER\"\n    .byte $4e, $45, $53, $1a, $01, $01, $00, $00"));
+    assert!(output.source.contains(".segment \"BANK0\"\nreset:\n    LDA   a:$0010\n"));
+    assert!(output.source.contains("    BNE   reset\n    STA   PPUCTRL\n"));
+    assert!(output.source.contains("    .word reset, reset, reset"));
+    assert!(output.source.contains(".segment \"CHR\"\n    .incbin \"game.chr\""));
+
+    let linker_config = output.linker_config.expect("Expected a linker config");
+    assert!(linker_config.contains("PRG0: start = $c000, size = $4000, fill = yes, file = %O;"));
+    assert!(linker_config.contains("BANK0: load = PRG0, type = ro;"));
+}
+
+#[test]
+fn test_asm6_output() {
+    let (rom, program) = make_rom_and_program();
+    let output = asm6::generate(&rom, &program, &SymbolTable::generate(&program), "game.chr");
+
+    assert!(output.linker_config.is_none());
+    assert!(output.source.contains(".org $0000\n    .db $4e, $45, $53, $1a"));
+    // asm6 can't force absolute addressing of a zeropage address, so the instruction is kept as bytes.
+    assert!(output.source.contains(".base $c000\nreset:\n    .db $ad, $10, $00"));
+    assert!(output.source.contains("    JMP   reset\n"));
+    assert!(output.source.contains("    .dw reset, reset, reset"));
+    assert!(output.source.contains(".incbin \"game.chr\""));
+}
+
+#[test]
+fn test_dot_output() {
+    let (_, program) = make_rom_and_program();
+    let symbols = SymbolTable::generate(&program);
+    let cfg = ControlFlowGraph::build(&program);
+
+    let graphs = dot::function_graphs(&cfg, &program, &symbols);
+    assert_eq!(graphs.len(), 1);
+    let (file_name, graph) = &graphs[0];
+    assert_eq!(file_name, "reset.dot");
+    assert!(graph.starts_with("digraph \"reset\" {\n"));
+    assert!(graph.contains("    b0 [label=\"reset:\\l$c000  LDA   $0010\\l$c003  LAX   $10\\l$c005  BNE   reset\\l\"];\n"));
+    assert!(graph.contains("    b0 -> b0 [color=darkgreen];\n"));
+    assert!(graph.contains("    b0 -> b7 [color=black];\n"));
+    assert!(graph.contains("    b7 -> b0 [color=blue];\n"));
+
+    let combined = dot::program_graph(&cfg, &program, &symbols);
+    assert!(combined.contains("  subgraph cluster_b0 {\n    label=\"reset\";\n"));
+}
+
+#[test]
+fn test_reassembles_identical_rom() {
+    let (rom, program) = make_rom_and_program();
+    let symbols = SymbolTable::generate(&program);
+
+    if on_path("ca65") && on_path("ld65") {
+        let dir = write_output("reassemble_ca65", &ca65::generate(&rom, &program, &symbols, "game.chr"));
+        run(&dir, "ca65", &["game.s", "-o", "game.o"]);
+        run(&dir, "ld65", &["-C", "game.cfg", "game.o", "-o", "game.nes"]);
+        assert_eq!(fs::read(dir.join("game.nes")).expect("Could not read ca65 ROM"), rom);
+    }
+    if on_path("asm6") {
+        let dir = write_output("reassemble_asm6", &asm6::generate(&rom, &program, &symbols, "game.chr"));
+        run(&dir, "asm6", &["game.s", "game.nes"]);
+        assert_eq!(fs::read(dir.join("game.nes")).expect("Could not read asm6 ROM"), rom);
+    }
+}
@@ -0,0 +1,74 @@
+use std::io::Cursor;
+
+use binrw::BinRead;
+use nespile::parser::rom::{ConsoleType, CpuPpuTiming, NesFormatVersion, NesHeader, TVSystem, VsHardwareType, VsPpuType};
+
+
+
+fn parse_header(bytes: [u8; 12]) -> NesHeader {
+    let mut data = b"NES\x1a".to_vec();
+    data.extend_from_slice(&bytes);
+    NesHeader::read(&mut Cursor::new(data))
+        .expect("Could not parse header")
+}
+
+
+#[test]
+fn test_ines_header() {
+    let header = parse_header([0x02, 0x01, 0x13, 0x40, 0x00, 0x01, 0, 0, 0, 0, 0, 0]);
+
+    assert_eq!(header.version, NesFormatVersion::INES);
+    assert_eq!(header.prgrom_size, 0x8000);
+    assert_eq!(header.chrrom_size, 0x2000);
+    assert_eq!(header.mapper_number(), 0x41);
+    assert_eq!(header.submapper(), 0);
+    assert_eq!(header.timing(), CpuPpuTiming::PAL);
+    assert_eq!(header.prgram_size(), 0);
+    assert_eq!(header.prgnvram_size(), 0x2000);
+}
+#[test]
+fn test_nes2_header() {
+    let header = parse_header([0x20, 0x00, 0x40, 0x18, 0x51, 0x10, 0x07, 0x79, 0x03, 0x00, 0x01, 0x2a]);
+
+    assert_eq!(header.version, NesFormatVersion::NES2_0);
+    assert_eq!(header.mapper_number(), 0x114);
+    assert_eq!(header.submapper(), 5);
+    assert_eq!(header.prgrom_size, 0x20 * 0x4000);
+    assert_eq!(header.chrrom_size, 0x100 * 0x2000);
+    assert_eq!(header.prgram_size(), 0x2000);
+    assert_eq!(header.prgnvram_size(), 0);
+    assert_eq!(header.chrram_size(), 0x8000);
+    assert_eq!(header.chrnvram_size(), 0x40 << 7);
+    assert_eq!(header.timing(), CpuPpuTiming::Dendy);
+    assert_eq!(header.console_type(), ConsoleType::NES);
+    assert_eq!(header.misc_rom_count(), 1);
+    assert_eq!(header.default_expansion_device(), 0x2a);
+}
+#[test]
+fn test_nes2_exponent_multiplier_size() {
+    // PRG-ROM: 2^10 * 3, CHR-ROM: 2^4 * 1
+    let header = parse_header([0x29, 0x10, 0x00, 0x08, 0x00, 0xff, 0, 0, 0, 0, 0, 0]);
+
+    assert_eq!(header.prgrom_size, 1024 * 3);
+    assert_eq!(header.chrrom_size, 16);
+}
+#[test]
+fn test_nes2_vs_system() {
+    let header = parse_header([0x02, 0x02, 0x00, 0x09, 0x00, 0x00, 0, 0, 0, 0x53, 0, 0]);
+
+    assert_eq!(header.console_type(), ConsoleType::VsSystem);
+    let vs = header.vs_system_type().expect("Expected a Vs. System type");
+    assert_eq!(vs.ppu, VsPpuType::RP2C04_0002);
+    assert_eq!(vs.hardware, VsHardwareType::DualSystemNormal);
+    assert_eq!(header.extended_console_type(), None);
+}
+#[test]
+#[allow(deprecated)]
+fn test_deprecated_flags9() {
+    let ines = parse_header([0x02, 0x01, 0x00, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(ines.flags9().tv_system(), TVSystem::PAL);
+
+    let nes2 = parse_header([0x02, 0x01, 0x00, 0x08, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0, 0]);
+    assert_eq!(nes2.flags9().tv_system(), TVSystem::PAL);
+    assert_eq!(nes2.prgram_size(), 0);
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use common::{lifted_program, CALL_LOOP};
+use nespile::codegen;
+use nespile::codegen::rust::DEFAULT_RUNTIME_PATH;
+
+
+
+#[test]
+fn test_generates_entry_points_for_vectors() {
+    let (program, symbols) = lifted_program(&CALL_LOOP);
+    let output = codegen::rust::generate(&program, &symbols, "game", Some("../nespile_runtime"));
+
+    assert!(output.manifest.contains("name = \"game\"\n"), "{}", output.manifest);
+    assert!(output.manifest.contains("nespile_runtime = { path = \"../nespile_runtime\" }\n"), "{}", output.manifest);
+    let default = codegen::rust::generate(&program, &symbols, "game", None).manifest;
+    assert!(default.contains(&format!("nespile_runtime = {{ path = \"{}\" }}\n", DEFAULT_RUNTIME_PATH)), "{}", default);
+    assert!(output.lib.contains("pub fn reset<B: Bus>(cpu: &mut Cpu, bus: &mut B) {\n    cpu.reset();\n    functions::reset(cpu, bus);\n}\n"), "{}", output.lib);
+    assert!(output.lib.contains("pub fn nmi<B: Bus>(cpu: &mut Cpu, bus: &mut B) {\n    cpu.interrupt(bus, 0);\n    functions::nmi(cpu, bus);\n}\n"), "{}", output.lib);
+    // IRQ shares NMI's handler, which is named for NMI
+    assert!(output.lib.contains("    functions::nmi(cpu, bus);\n}\n\npub fn irq"), "{}", output.lib);
+}
+
+#[test]
+fn test_generates_block_dispatch_loop() {
+    let (program, symbols) = lifted_program(&CALL_LOOP);
+    let functions = codegen::rust::generate(&program, &symbols, "game", Some("../nespile_runtime")).functions;
+
+    assert!(functions.contains("pub fn reset<B: Bus>(cpu: &mut Cpu, bus: &mut B) {\n    let mut block = 0x0;\n    loop {\n        match block {\n"), "{}", functions);
+    assert!(functions.contains("                sub_C00A(cpu, bus); block = 0x5;\n"), "{}", functions);
+    assert!(functions.contains("                if (!cpu.z) { block = 0x2; } else { block = 0x8; }\n"), "{}", functions);
+    assert!(functions.contains("                panic!(\"STP in block at $C008\");\n"), "{}", functions);
+    assert!(functions.contains("                let t0: u8 = (cpu.a << 0x01u8);\n                cpu.z = (t0 == 0);\n"), "{}", functions);
+    assert!(functions.contains("                bus.write((0x0100u16 | u16::from(cpu.sp)), 0xc0u8);\n"), "{}", functions);
+}
+
+#[test]
+fn test_generated_crate_builds() {
+    let (program, symbols) = lifted_program(&CALL_LOOP);
+    let output = codegen::rust::generate(&program, &symbols, "game", None);
+
+    // Outside the workspace, which cargo would otherwise take the crate to be part of.
+    let dir = std::env::temp_dir().join(format!("nespile_rust_codegen_{}", std::process::id()));
+    fs::create_dir_all(dir.join("src")).expect("Could not create crate directory");
+    fs::write(dir.join("Cargo.toml"), &output.manifest).expect("Could not write manifest");
+    fs::write(dir.join("src/lib.rs"), &output.lib).expect("Could not write lib.rs");
+    fs::write(dir.join("src/functions.rs"), &output.functions).expect("Could not write functions.rs");
+
+    let result = Command::new(env!("CARGO")).args(["check", "--offline", "--quiet"])
+        .env("CARGO_TARGET_DIR", std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("rust_codegen"))
+        .current_dir(&dir)
+        .output();
+    fs::remove_dir_all(&dir).expect("Could not remove crate directory");
+    let output = result.expect("Could not run cargo");
+    assert!(output.status.success(), "The generated crate didn't build:\n{}", String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,65 @@
+use std::io::Cursor;
+
+use binrw::{BinRead, Endian};
+use nespile::cpu::{ControlFlow, Flags, MemoryAccess, Registers, Semantics};
+use nespile::parser::opcodes::Opcode;
+
+
+
+fn semantics(bytes: &[u8]) -> Semantics {
+    Opcode::read_options(&mut Cursor::new(bytes), Endian::Little, ()).unwrap().semantics()
+}
+
+#[test]
+fn test_official_semantics() {
+    // LDA ($10),Y
+    let lda = semantics(&[0xb1, 0x10]);
+    assert_eq!(lda.registers_read, Registers::Y);
+    assert_eq!(lda.registers_written, Registers::A);
+    assert_eq!(lda.flags_written, Flags::N | Flags::Z);
+    assert_eq!(lda.memory, MemoryAccess::Read);
+
+    // ASL A touches no memory, ROL $10 rewrites it
+    let asl = semantics(&[0x0a]);
+    assert_eq!((asl.registers_read, asl.memory), (Registers::A, MemoryAccess::None));
+    let rol = semantics(&[0x26, 0x10]);
+    assert_eq!((rol.registers_read, rol.flags_read, rol.memory), (Registers::NONE, Flags::C, MemoryAccess::ReadModifyWrite));
+
+    let jsr = semantics(&[0x20, 0x00, 0x80]);
+    assert_eq!((jsr.stack, jsr.control_flow), (2, ControlFlow::Call));
+    assert!(jsr.registers_written.contains(Registers::SP));
+    let rti = semantics(&[0x40]);
+    assert_eq!((rti.stack, rti.flags_written, rti.control_flow), (-3, Flags::ALL, ControlFlow::ReturnFromInterrupt));
+    assert_eq!(semantics(&[0x6c, 0x00, 0x02]).control_flow, ControlFlow::IndirectJump);
+    assert_eq!(semantics(&[0xd0, 0xfe]).flags_read, Flags::Z);
+}
+
+#[test]
+fn test_unofficial_semantics() {
+    // SLO $1234,X
+    let slo = semantics(&[0x1f, 0x34, 0x12]);
+    assert_eq!(slo.registers_read, Registers::A | Registers::X);
+    assert_eq!(slo.registers_written, Registers::A);
+    assert_eq!(slo.flags_written, Flags::NZC);
+    assert_eq!(slo.memory, MemoryAccess::ReadModifyWrite);
+
+    // RRA $10
+    let rra = semantics(&[0x67, 0x10]);
+    assert_eq!((rra.flags_read, rra.flags_written), (Flags::C, Flags::NVZC));
+
+    // LAX $10,Y
+    let lax = semantics(&[0xb7, 0x10]);
+    assert_eq!((lax.registers_read, lax.registers_written), (Registers::Y, Registers::A | Registers::X));
+
+    assert_eq!(semantics(&[0x02]).control_flow, ControlFlow::Halt);
+}
+
+#[test]
+fn test_stack_effects() {
+    // BRK, JSR, RTI, RTS, PHP, PLP, PHA and PLA
+    const STACK_OPCODES: [(u8, i8); 8] = [(0x00, 3), (0x20, 2), (0x40, -3), (0x60, -2), (0x08, 1), (0x28, -1), (0x48, 1), (0x68, -1)];
+    for byte in 0..=255u8 {
+        let expected = STACK_OPCODES.iter().find(|(opcode, _)| *opcode == byte).map_or(0, |(_, stack)| *stack);
+        assert_eq!(semantics(&[byte, 0, 0]).stack, expected, "opcode ${:02x}", byte);
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::Cursor;
+
+use binrw::BinRead;
+use nespile::mapper::PrgLocation;
+use nespile::parser::NesProgram;
+use nespile::parser::rom::NesFile;
+use nespile::symbols::fceux::{self, NlFile};
+use nespile::symbols::{ca65, mesen, SymbolKind, SymbolTable};
+
+
+
+/// UxROM file with two 16KB banks, every vector pointing at $C000 in the fixed bank.
+fn make_program() -> NesProgram {
+    let mut prgrom = vec![0xeau8; 0x8000];
+    prgrom[0x4000] = 0x4c;
+    prgrom[0x4001..0x4003].copy_from_slice(&[0x00, 0xc0]);
+    prgrom[0x7ffa..].copy_from_slice(&[0x00, 0xc0, 0x00, 0xc0, 0x00, 0xc0]);
+
+    let mut data = b"NES\x1a\x02\x00\x20\x00".to_vec();
+    data.extend_from_slice(&[0u8; 8]);
+    data.extend_from_slice(&prgrom);
+    let file = NesFile::read(&mut Cursor::new(data)).expect("Could not parse ROM");
+    NesProgram::try_from(&file).expect("Could not disassemble ROM")
+}
+
+
+#[test]
+fn test_fceux_import_export() {
+    let program = make_program();
+    let text = "$C000#Main#Entry point\\\ncontinued\n$C010/4#Table#\n";
+    let symbols = fceux::import(text, NlFile::Bank(1), program.mapper.as_ref()).expect("Could not parse .nl");
+    let main = symbols.rom_symbol(0x4000).expect("Expected a symbol at bank 1");
+    assert_eq!(main.name, "Main");
+    assert_eq!(main.kind, SymbolKind::User);
+    assert_eq!(main.location, Some(PrgLocation { bank: 1, prg_offset: 0x4000 }));
+    assert_eq!(main.comment.as_deref(), Some("Entry point\ncontinued"));
+    assert_eq!(symbols.rom_symbol(0x4010).map(|s| s.name.as_str()), Some("Table"));
+
+    let ram = fceux::import("$0300#Buffer#\n", NlFile::Ram, program.mapper.as_ref()).expect("Could not parse .nl");
+    assert_eq!(ram.ram_symbol(0x300).map(|s| s.name.as_str()), Some("Buffer"));
+
+    assert_eq!(NlFile::from_file_name("game.nes.1.nl"), Some(NlFile::Bank(1)));
+    assert_eq!(NlFile::from_file_name("game.nes.ram.nl"), Some(NlFile::Ram));
+
+    let exported = fceux::export(&symbols);
+    assert_eq!(exported.get(&NlFile::Bank(1)).map(String::as_str),
+        Some("$C000#Main#Entry point\\\ncontinued\n$C010#Table#\n"));
+}
+
+#[test]
+fn test_mesen_import_export() {
+    let program = make_program();
+    let text = "P:4000:Main:Entry point\nR:0010-0011:Pointer\nS:0000:Save\nP:4003:\nC:0000:Tiles\n";
+    let symbols = mesen::import(text, program.mapper.as_ref()).expect("Could not parse .mlb");
+    let main = symbols.rom_symbol(0x4000).expect("Expected a ROM symbol");
+    assert_eq!(main.address, 0xc000);
+    assert_eq!(main.comment.as_deref(), Some("Entry point"));
+    assert_eq!(symbols.ram_symbol(0x10).map(|s| s.name.as_str()), Some("Pointer"));
+    assert_eq!(symbols.ram_symbol(0x6000).map(|s| s.name.as_str()), Some("Save"));
+    assert_eq!(symbols.len(), 3);
+
+    assert_eq!(mesen::export(&symbols), "R:0010:Pointer\nS:0000:Save\nP:4000:Main:Entry point\n");
+
+    // CPU addresses in cartridge space name the PRG-ROM mapped there, or can't be placed at all.
+    let fixed = mesen::import("G:C010:Table\n", program.mapper.as_ref()).expect("Could not parse .mlb");
+    assert_eq!(fixed.rom_symbol(0x4010).map(|s| s.name.as_str()), Some("Table"));
+    assert!(mesen::import("G:8010:Table\n", program.mapper.as_ref()).is_err());
+}
+
+#[test]
+fn test_ca65_round_trip() {
+    let program = make_program();
+    let text = concat!(
+        "version\tmajor=2,minor=0\n",
+        "seg\tid=0,name=\"CODE\",start=0x00C000,size=0x4000,addrsize=absolute,type=ro,oname=\"game.nes\",ooffs=16400\n",
+        "seg\tid=1,name=\"BSS\",start=0x000300,size=0x0100,addrsize=absolute,type=rw\n",
+        "sym\tid=0,name=\"main\",addrsize=absolute,scope=0,def=1,val=0xC000,seg=0,type=lab\n",
+        "sym\tid=1,name=\"@loop\",addrsize=absolute,scope=1,def=2,val=0xC003,seg=0,type=lab\n",
+        "sym\tid=2,name=\"buffer\",addrsize=absolute,scope=0,def=3,val=0x300,seg=1,type=lab\n",
+    );
+    let symbols = ca65::import(text, program.mapper.as_ref(), program.prg_file_offset).expect("Could not parse .dbg");
+    assert_eq!(symbols.rom_symbol(0x4000).map(|s| s.name.as_str()), Some("main"));
+    assert_eq!(symbols.ram_symbol(0x300).map(|s| s.name.as_str()), Some("buffer"));
+    assert_eq!(symbols.len(), 2);
+
+    let exported = ca65::export(&symbols, program.mapper.as_ref(), program.prg_file_offset);
+    let reimported = ca65::import(&exported, program.mapper.as_ref(), program.prg_file_offset).expect("Could not parse exported .dbg");
+    assert_eq!(reimported.rom_symbol(0x4000).map(|s| s.name.as_str()), Some("main"));
+    assert_eq!(reimported.ram_symbol(0x300).map(|s| s.name.as_str()), Some("buffer"));
+}
+
+#[test]
+fn test_user_symbols_override_generated() {
+    let program = make_program();
+    let mut symbols = SymbolTable::generate(&program);
+    symbols.merge(mesen::import("P:4000:Main\n", program.mapper.as_ref()).expect("Could not parse .mlb"));
+    assert!(program.to_source_string_with(&symbols).contains("Main:\n$c000    JMP   Main"));
+}
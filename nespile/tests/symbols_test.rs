@@ -0,0 +1,32 @@
+mod common;
+
+use common::make_program;
+use nespile::symbols::{SymbolKind, SymbolTable};
+
+
+
+#[test]
+fn test_generated_symbols() {
+    let program = make_program(&[
+        0x20, 0x06, 0xc0,   // $C000: JSR $C006
+        0x4c, 0x00, 0xc0,   // $C003: JMP $C000
+        0xbd, 0x20, 0xc0,   // $C006: LDA $C020,X
+        0x8d, 0x14, 0x40,   // $C009: STA $4014
+        0x60,               // $C00C: RTS
+    ], 0xc000);
+    let symbols = SymbolTable::generate(&program);
+
+    let reset = symbols.rom_symbol(0x0).expect("Expected a reset symbol");
+    assert_eq!(reset.name, "reset");
+    assert_eq!(reset.kind, SymbolKind::Vector);
+    assert_eq!(symbols.rom_symbol(0x6).map(|s| s.name.as_str()), Some("sub_C006"));
+    assert_eq!(symbols.rom_symbol(0x20).map(|s| s.name.as_str()), Some("tbl_C020"));
+    assert_eq!(symbols.ram_symbol(0x4014).map(|s| s.name.as_str()), Some("OAMDMA"));
+
+    let source = program.to_source_string_with(&symbols);
+    assert!(source.contains("$c000    JSR   sub_C006"));
+    assert!(source.contains("$c003    JMP   reset"));
+    assert!(source.contains("$c006    LDA   tbl_C020,X"));
+    assert!(source.contains("$c009    STA   OAMDMA"));
+    assert!(source.contains("tbl_C020:\n$c020    .byte $ff"));
+}
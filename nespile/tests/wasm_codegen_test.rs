@@ -0,0 +1,30 @@
+mod common;
+
+use common::{lifted_program, CALL_LOOP};
+use nespile::codegen;
+
+
+
+#[test]
+fn test_exports_entry_points_for_vectors() {
+    let (program, symbols) = lifted_program(&CALL_LOOP);
+    let output = codegen::wasm::generate(&program, &symbols);
+
+    assert!(output.contains("(import \"nes\" \"read\" (func $nes_read (param i32) (result i32)))"), "{}", output);
+    assert!(output.contains("(import \"nes\" \"write\" (func $nes_write (param i32 i32)))"), "{}", output);
+    assert!(output.contains("  (func $nes_reset (export \"reset\")\n    (global.set $sp (i32.and (i32.sub (global.get $sp) (i32.const 3)) (i32.const 0xff)))\n    (global.set $i (i32.const 1))\n    (call $reset))\n"), "{}", output);
+    // IRQ shares NMI's handler, which is named for NMI
+    assert!(output.contains("  (func $nes_irq (export \"irq\")\n    (call $nes_interrupt)\n    (call $nmi))\n"), "{}", output);
+}
+
+#[test]
+fn test_generates_block_dispatch_loop() {
+    let (program, symbols) = lifted_program(&CALL_LOOP);
+    let output = codegen::wasm::generate(&program, &symbols);
+
+    assert!(output.contains("    (loop $dispatch\n    (block $b8\n    (block $b5\n    (block $b2\n    (block $b0\n      (br_table $b0 $b2 $b5 $b8 (local.get $block)))\n"), "{}", output);
+    assert!(output.contains("      (call $sub_C00A) (local.set $block (i32.const 2)) (br $dispatch)\n"), "{}", output);
+    assert!(output.contains("      (if (i32.eqz (global.get $z)) (then (local.set $block (i32.const 1)) (br $dispatch)) (else (local.set $block (i32.const 3)) (br $dispatch)))\n"), "{}", output);
+    assert!(output.contains("      ;; $C008\n      (unreachable)\n    ))\n"), "{}", output);
+    assert!(output.contains("      (local.set $t0 (i32.and (i32.shl (global.get $a) (i32.const 0x01)) (i32.const 0xff)))\n      (global.set $z (i32.eqz (local.get $t0)))\n"), "{}", output);
+}
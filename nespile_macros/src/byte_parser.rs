@@ -3,24 +3,36 @@ use std::collections::HashMap;
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::punctuated::Punctuated;
-use syn::{parse_macro_input, Fields, Ident, ItemEnum, Token};
+use syn::{parse_macro_input, Fields, Ident, ItemEnum, LitInt, Token};
 use syn::parse::{Parse, ParseStream};
 
 
 
+mod keyword {
+    syn::custom_keyword!(write);
+}
+
 struct Args {
+    /// Whether `write;` came before the variants, asking for `BinWrite` too.
+    write: bool,
     match_cases: Vec<Ident>
 }
 impl Parse for Args {
     fn parse(input: ParseStream) -> syn::Result<Self> {
+        let write = input.peek(keyword::write) && input.peek2(Token![;]);
+        if write {
+            input.parse::<keyword::write>()?;
+            input.parse::<Token![;]>()?;
+        }
         let vars = Punctuated::<Ident, Token![,]>::parse_terminated(input)?;
         Ok(Args {
+            write,
             match_cases: vars.into_iter().collect(),
         })
     }
 }
 impl Args {
-    pub fn to_matches(self) -> [Ident; 256] {
+    pub fn into_matches(self) -> [Ident; 256] {
         self.match_cases.into_iter().cycle().take(256).collect::<Vec<Ident>>()
             .try_into().expect("Could not convert Vec to [Ident; 256]")
     }
@@ -28,20 +40,85 @@ impl Args {
 
 
 
+/// Takes the bytes of `#[prefer_byte(..)]` attributes off a variant.
+fn take_preferred_bytes(variant: &mut syn::Variant) -> Vec<u8> {
+    let (preferred, attrs): (Vec<_>, Vec<_>) = variant.attrs.drain(..)
+        .partition(|attr| attr.path().is_ident("prefer_byte"));
+    variant.attrs = attrs;
+    preferred.into_iter()
+        .map(|attr| attr.parse_args::<LitInt>()
+            .and_then(|byte| byte.base10_parse::<u8>())
+            .expect("`prefer_byte` takes a single byte"))
+        .collect()
+}
+
+/// With `write;` before the variants, also generates `BinWrite`, encoding each variant with the first
+/// byte listed for it that its fields' `MatchesOpcode` implementations accept, which must be in scope.
+/// A variant marked `#[prefer_byte(..)]` tries those bytes first.
 pub fn parse_byte_with(arg_tokens: TokenStream, enum_tokens: TokenStream) -> TokenStream {
-    let enum_type = parse_macro_input!(enum_tokens as ItemEnum);
+    let mut enum_type = parse_macro_input!(enum_tokens as ItemEnum);
     let args = parse_macro_input!(arg_tokens as Args);
 
+    let preferred_bytes = enum_type.variants.iter_mut()
+        .map(|v| (v.ident.clone(), take_preferred_bytes(v)))
+        .collect::<HashMap<Ident, Vec<u8>>>();
     let ItemEnum{
         ident: enum_ident,
         variants: enum_variants,
         ..
     } = enum_type.clone();
-    let variant_map = enum_variants.into_iter()
-        .map(|v| (v.ident, v.fields))
+    let variant_map = enum_variants.iter()
+        .map(|v| (v.ident.clone(), v.fields.clone()))
         .collect::<HashMap<Ident, Fields>>();
 
-    let match_lines = args.to_matches().into_iter()
+    let write = args.write;
+    let matches = args.into_matches();
+    let mut variant_bytes = HashMap::<Ident, Vec<u8>>::new();
+    for (idx, ident) in matches.iter().enumerate() {
+        variant_bytes.entry(ident.clone()).or_default().push(idx as u8);
+    }
+
+    let field_types = enum_variants.iter()
+        .flat_map(|v| v.fields.iter().map(|field| field.ty.clone()))
+        .collect::<Vec<syn::Type>>();
+    let write_lines = enum_variants.iter()
+        .filter(|v| variant_bytes.contains_key(&v.ident))
+        .map(|v| {
+            let ident = &v.ident;
+            let mut candidates = preferred_bytes[ident].clone();
+            candidates.extend(&variant_bytes[ident]);
+
+            match &v.fields {
+                Fields::Unit => {
+                    let byte = candidates[0];
+                    quote! { #enum_ident::#ident => #byte.write_options(writer, endian, ())? }
+                },
+                Fields::Unnamed(fields) => {
+                    let field_idents = (0..fields.unnamed.len())
+                        .map(|idx| quote::format_ident!("f{}", idx))
+                        .collect::<Vec<Ident>>();
+                    let variant_name = ident.to_string();
+
+                    quote! { #enum_ident::#ident(#(#field_idents),*) => {
+                        let Some(byte) = [#(#candidates),*].into_iter()
+                            .find(|byte| #(#field_idents.matches_opcode(*byte))&&*)
+                        else {
+                            return Err(binrw::Error::AssertFail {
+                                pos: writer.stream_position()?,
+                                message: format!("No opcode byte encodes {} with its operand", #variant_name),
+                            });
+                        };
+                        byte.write_options(writer, endian, ())?;
+                        #(#field_idents.write_options(writer, endian, ())?;)*
+                    } }
+                },
+                _ =>
+                    panic!("You cannot use `parse_byte_with` with an enum that has named fields in variants"),
+            }
+        })
+        .collect::<Vec<proc_macro2::TokenStream>>();
+
+    let match_lines = matches.into_iter()
         .enumerate()
         .map(|(idx, ident)| {
             let variant_fields = variant_map.get(&ident)
@@ -68,6 +145,32 @@ pub fn parse_byte_with(arg_tokens: TokenStream, enum_tokens: TokenStream) -> Tok
         })
         .collect::<Vec<proc_macro2::TokenStream>>();
     
+    let write_impl = write.then(|| quote! {
+        impl BinWrite for #enum_ident
+        where
+            #(for<'a> #field_types: BinWrite<Args<'a> = ()> + MatchesOpcode,)*
+        {
+            type Args<'a> = ();
+
+            fn write_options<W: std::io::Write + std::io::Seek>(
+                &self,
+                writer: &mut W,
+                endian: binrw::Endian,
+                _args: Self::Args<'_>,
+            ) -> binrw::BinResult<()> {
+                match self {
+                    #(#write_lines,)*
+                    #[allow(unreachable_patterns)]
+                    _ => return Err(binrw::Error::AssertFail {
+                        pos: writer.stream_position()?,
+                        message: "Variant has no opcode byte".to_string(),
+                    }),
+                }
+                Ok(())
+            }
+        }
+    });
+
     TokenStream::from(quote! {
         #enum_type
 
@@ -86,6 +189,8 @@ pub fn parse_byte_with(arg_tokens: TokenStream, enum_tokens: TokenStream) -> Tok
                 }
             }
         }
+
+        #write_impl
     })
 
     // enum_type.to_token_stream().into()
@@ -13,13 +13,13 @@ fn get_address_mode_pattern(address_mode: &str) -> proc_macro2::TokenStream {
             0x8c..=0x8f | 0xac..=0xaf | 0xcc..=0xcf | 0xec..=0xef
         },
         "AbsoluteX" => quote!{
-            0x1c..=0x1f | 0x3c..=0x3f | 0x5c..=0x5f | 0x7d..=0x7f |
+            0x1c..=0x1f | 0x3c..=0x3f | 0x5c..=0x5f | 0x7c..=0x7f |
             0x9c | 0x9d | 0xbc | 0xbd | 0xdc..=0xdf | 0xfc..=0xff
         },
         "AbsoluteY" => quote! { b if (b & 0x1d == 0x19) || (b & 0xde == 0x9e) },
         "Immediate" => quote! { b if (b & 0x1d == 0x09) || (b & 0x9d == 0x80) },
         "Implied" => quote! {
-            b if (b & 0x1f == 0x08) || (b & 0x1f == 0x0a) || (b & 0x1f == 0x12) || (b & 0x1f == 0x18) || (b & 0x1f == 0x0a) ||
+            b if (b & 0x1f == 0x08) || (b & 0x1f == 0x0a) || (b & 0x1f == 0x12) || (b & 0x1f == 0x18) || (b & 0x1f == 0x1a) ||
                  (b & 0x9f == 0x02) || (b & 0x9f == 0x00 && b != 0x20)
         },
         "Indirect" => quote!{ 0x6c },
@@ -93,3 +93,65 @@ pub fn derive_address_mode_parse(item: TokenStream) -> TokenStream {
 }
 
 
+
+
+pub fn derive_address_mode_write(item: TokenStream) -> TokenStream {
+    let enum_type = parse_macro_input!(item as ItemEnum);
+    let enum_ident = enum_type.ident.clone();
+
+    let (writers, matchers): (Vec<_>, Vec<_>) = enum_type.variants.into_iter()
+        .map(|variant| {
+            let variant_ident = variant.ident;
+            let pattern = get_address_mode_pattern(&variant_ident.to_string());
+
+            match variant.fields {
+                Fields::Unit => (
+                    quote!{ #enum_ident::#variant_ident => {} },
+                    quote!{ #enum_ident::#variant_ident => matches!(opcode, #pattern) },
+                ),
+                Fields::Unnamed(fields) => {
+                    let field_idents = (0..fields.unnamed.len())
+                        .map(|idx| quote::format_ident!("f{}", idx))
+                        .collect::<Vec<syn::Ident>>();
+
+                    (
+                        quote!{ #enum_ident::#variant_ident(#(#field_idents),*) => {
+                            #(#field_idents.write_options(writer, endian, ())?;)*
+                        } },
+                        quote!{ #enum_ident::#variant_ident(..) => matches!(opcode, #pattern) },
+                    )
+                },
+                _ =>
+                    panic!("You cannot derive BinWriteAddressMode for an enum that has variants with named fields"),
+            }
+        })
+        .unzip();
+
+
+    TokenStream::from(quote! {
+        impl BinWrite for #enum_ident {
+            type Args<'a> = ();
+
+            /// Writes the operand only; the opcode byte is written by the instruction.
+            fn write_options<W: io::Write + io::Seek>(
+                &self,
+                writer: &mut W,
+                endian: binrw::Endian,
+                _args: Self::Args<'_>,
+            ) -> binrw::BinResult<()> {
+                match self {
+                    #(#writers,)*
+                }
+                Ok(())
+            }
+        }
+
+        impl MatchesOpcode for #enum_ident {
+            fn matches_opcode(&self, opcode: u8) -> bool {
+                match self {
+                    #(#matchers,)*
+                }
+            }
+        }
+    })
+}
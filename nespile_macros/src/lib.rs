@@ -20,14 +20,18 @@ pub fn parse_byte_with(arg_tokens: TokenStream, enum_tokens: TokenStream) -> Tok
 pub fn derive_address_mode_parse(item: TokenStream) -> TokenStream {
     crate::derive_address_mode::derive_address_mode_parse(item)
 }
+#[proc_macro_derive(BinWriteAddressMode)]
+pub fn derive_address_mode_write(item: TokenStream) -> TokenStream {
+    crate::derive_address_mode::derive_address_mode_write(item)
+}
 #[proc_macro_derive(OpcodeArgs)]
 pub fn derive_opcode_arguments(item: TokenStream) -> TokenStream {
     let enum_type = parse_macro_input!(item as ItemEnum);
     let enum_ident = enum_type.ident;
 
-    let variant_matchers = enum_type.variants.into_iter()
+    let variant_matchers = enum_type.variants.iter()
         .filter_map(|variant| {
-            let variant_ident = variant.ident;
+            let variant_ident = &variant.ident;
 
             match variant.fields {
                 Fields::Unnamed(_) => {
@@ -40,6 +44,22 @@ pub fn derive_opcode_arguments(item: TokenStream) -> TokenStream {
         })
         .collect::<Vec<proc_macro2::TokenStream>>();
 
+    let constructors = enum_type.variants.iter()
+        .map(|variant| {
+            let variant_ident = &variant.ident;
+            let variant_name = variant_ident.to_string();
+
+            match variant.fields {
+                Fields::Unnamed(_) => quote! {
+                    (#variant_name, Some(mode)) => mode.try_into().ok().map(#enum_ident::#variant_ident)
+                },
+                _ => quote! {
+                    (#variant_name, None) => Some(#enum_ident::#variant_ident)
+                },
+            }
+        })
+        .collect::<Vec<proc_macro2::TokenStream>>();
+
     TokenStream::from(quote! {
         impl #enum_ident {
             pub fn argument(&self) -> Option<AddressMode> {
@@ -48,6 +68,14 @@ pub fn derive_opcode_arguments(item: TokenStream) -> TokenStream {
                     _ => None
                 }
             }
+
+            /// The variant named `name` with the given argument, if it takes that address mode.
+            pub fn from_argument(name: &str, argument: Option<AddressMode>) -> Option<Self> {
+                match (name, argument) {
+                    #(#constructors,)*
+                    _ => None
+                }
+            }
         }
     })
 }
@@ -0,0 +1,100 @@
+use std::io::Cursor;
+
+use binrw::{BinRead, BinWrite, Endian};
+use nespile_macros::parse_byte_with;
+
+
+
+/// The bound `parse_byte_with(write; ..)` puts on every field.
+trait MatchesOpcode {
+    fn matches_opcode(&self, opcode: u8) -> bool;
+}
+
+#[derive(Debug, PartialEq)]
+enum Parity {
+    Even,
+    Odd
+}
+impl BinRead for Parity {
+    type Args<'a> = u8;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        _reader: &mut R,
+        _endian: Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(if args.count_ones() & 0x01 == 0x00 { Parity::Even } else { Parity::Odd })
+    }
+}
+impl BinWrite for Parity {
+    type Args<'a> = ();
+
+    /// Writes nothing, since the opcode byte carries the parity.
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        _writer: &mut W,
+        _endian: Endian,
+        _args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        Ok(())
+    }
+}
+impl MatchesOpcode for Parity {
+    fn matches_opcode(&self, opcode: u8) -> bool {
+        let even = opcode.count_ones() & 0x01 == 0x00;
+        matches!((self, even), (Parity::Even, true) | (Parity::Odd, false))
+    }
+}
+
+
+
+#[parse_byte_with(write;
+    Even,
+    Odd
+)]
+enum UnitTest {
+    Even,
+    Odd
+}
+
+#[parse_byte_with(write;
+    Even,
+    Odd
+)]
+#[derive(Debug, PartialEq)]
+enum NestedTest {
+    #[prefer_byte(0x0a)]
+    Even(Parity),
+    Odd(Parity)
+}
+
+
+
+#[test]
+fn test_unit_write_options() {
+    let mut writer = Cursor::new(Vec::new());
+    UnitTest::Odd.write_options(&mut writer, Endian::Little, ()).unwrap();
+    UnitTest::Even.write_options(&mut writer, Endian::Little, ()).unwrap();
+
+    assert_eq!(writer.into_inner(), vec![ 0x01u8, 0x00u8 ]);
+}
+#[test]
+fn test_nested_write_options() {
+    let mut writer = Cursor::new(Vec::new());
+    for nested in [NestedTest::Even(Parity::Even), NestedTest::Odd(Parity::Odd), NestedTest::Even(Parity::Odd), NestedTest::Odd(Parity::Even)] {
+        nested.write_options(&mut writer, Endian::Little, ()).unwrap();
+    }
+
+    assert_eq!(writer.into_inner(), vec![ 0x0au8, 0x01u8, 0x02u8, 0x03u8 ]);
+}
+#[test]
+fn test_nested_round_trip() {
+    for byte in 0..=0xffu8 {
+        let nested = NestedTest::read_options(&mut Cursor::new([byte]), Endian::Little, ()).unwrap();
+        let mut writer = Cursor::new(Vec::new());
+        nested.write_options(&mut writer, Endian::Little, ()).unwrap();
+
+        let written = writer.into_inner()[0];
+        assert_eq!(NestedTest::read_options(&mut Cursor::new([written]), Endian::Little, ()).unwrap(), nested);
+    }
+}
@@ -0,0 +1,84 @@
+//! State and memory access shared by code that nespile transpiles to Rust.
+
+
+
+/// Address of the stack's page.
+const STACK: u16 = 0x0100;
+
+/// The 6502's registers, with each status flag kept as its own boolean.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Cpu {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: u8,
+    /// Carry.
+    pub c: bool,
+    /// Zero.
+    pub z: bool,
+    /// Interrupt disable.
+    pub i: bool,
+    /// Decimal, which the NES's 2A03 ignores.
+    pub d: bool,
+    /// Overflow.
+    pub v: bool,
+    /// Negative.
+    pub n: bool,
+}
+impl Cpu {
+    /// State at power on, per https://www.nesdev.org/wiki/CPU_power_up_state
+    pub fn new() -> Self {
+        Cpu { sp: 0xfd, i: true, ..Cpu::default() }
+    }
+
+    /// The flags packed into P, with the unused bit set and the break bit clear.
+    pub fn p(&self) -> u8 {
+        0x20 | self.c as u8 | (self.z as u8) << 1 | (self.i as u8) << 2 | (self.d as u8) << 3 |
+            (self.v as u8) << 6 | (self.n as u8) << 7
+    }
+    pub fn set_p(&mut self, p: u8) {
+        self.c = p & 0x01 != 0;
+        self.z = p & 0x02 != 0;
+        self.i = p & 0x04 != 0;
+        self.d = p & 0x08 != 0;
+        self.v = p & 0x40 != 0;
+        self.n = p & 0x80 != 0;
+    }
+
+    pub fn push<B: Bus>(&mut self, bus: &mut B, value: u8) {
+        bus.write(STACK | self.sp as u16, value);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+    pub fn pull<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        bus.read(STACK | self.sp as u16)
+    }
+
+    /// What the reset signal does before the reset handler runs: SP moves down three bytes and interrupts are disabled.
+    pub fn reset(&mut self) {
+        self.sp = self.sp.wrapping_sub(3);
+        self.i = true;
+    }
+    /// What an NMI or IRQ does before its handler runs: pushes `return_address` and P, then disables interrupts.
+    /// Transpiled code has no program counter, so the return address is only there for `RTI` to pull.
+    pub fn interrupt<B: Bus>(&mut self, bus: &mut B, return_address: u16) {
+        self.push(bus, (return_address >> 8) as u8);
+        self.push(bus, return_address as u8);
+        let p = self.p();
+        self.push(bus, p);
+        self.i = true;
+    }
+}
+
+/// The CPU's view of memory: RAM, the PPU and APU registers, and the cartridge through its mapper.
+pub trait Bus {
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+
+    /// Runs code at `address` that was never transpiled, such as the target of an unrecovered
+    /// indirect jump, until it returns. Panics unless overridden, for example with an interpreter.
+    fn unresolved(&mut self, cpu: &mut Cpu, address: u16) {
+        let _ = cpu;
+        panic!("No transpiled code at ${:04x}", address);
+    }
+}